@@ -8,8 +8,8 @@ use gear::Engine;
 async fn main() {
     env_logger::init();
 
-    Engine::new().await.run(move |engine, event| match event {
-        Event::UpdateEvent { delta_time: _ } => {
+    Engine::new().await.unwrap().run(move |engine, event| match event {
+        Event::UpdateEvent { .. } => {
             engine.renderer.set_clear_color([0.03, 0.03, 0.03, 1.0]).submit();
         },
         Event::TerminateEvent => (),
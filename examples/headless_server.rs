@@ -0,0 +1,21 @@
+// Copyright 2021 Chay Nabors.
+
+use gear::event::Event;
+use gear::event::NetworkEvent;
+use gear::Engine;
+use gear::Packet;
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() {
+    env_logger::init();
+
+    let mut engine = Engine::builder().headless(true).build().await.unwrap();
+    let socket = engine.network.bind("127.0.0.1:12345").unwrap();
+
+    engine.run(move |_engine, event| match event {
+        Event::NetworkEvent(NetworkEvent::Message(packet)) => {
+            socket.send(Packet::reliable_unordered(packet.addr(), packet.payload().to_vec())).unwrap();
+        },
+        _ => (),
+    });
+}
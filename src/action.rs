@@ -0,0 +1,208 @@
+// Copyright 2021 Chay Nabors.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::fs::File;
+use std::path::Path;
+
+use ron::ser::PrettyConfig;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::gamepad::GamepadAxis;
+use crate::gamepad::GamepadButton;
+use crate::input::KeyCode;
+use crate::input::MouseButton;
+use crate::result::Result;
+use crate::result::WithPath;
+use crate::Loadable;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum InputSource {
+    Key(KeyCode),
+    MouseButton(MouseButton),
+    GamepadButton(GamepadButton),
+}
+
+/// An input source, optionally chorded with modifier keys that must be held
+/// at the same time, e.g. `Ctrl + S`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Binding {
+    pub source: InputSource,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub modifiers: Vec<KeyCode>,
+}
+
+impl Binding {
+    pub fn key(key: KeyCode) -> Self {
+        Self { source: InputSource::Key(key), modifiers: vec![] }
+    }
+
+    pub fn mouse_button(button: MouseButton) -> Self {
+        Self { source: InputSource::MouseButton(button), modifiers: vec![] }
+    }
+
+    pub fn gamepad_button(button: GamepadButton) -> Self {
+        Self { source: InputSource::GamepadButton(button), modifiers: vec![] }
+    }
+
+    pub fn with_modifier(mut self, modifier: KeyCode) -> Self {
+        self.modifiers.push(modifier);
+        self
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum AxisBinding {
+    /// Two digital inputs driving the axis to -1 and +1.
+    Digital { negative: Binding, positive: Binding },
+    /// An analog gamepad axis, read from whichever gamepad is deflected furthest.
+    Gamepad(GamepadAxis),
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Axis2dBinding {
+    pub x: AxisBinding,
+    pub y: AxisBinding,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct InputContext {
+    #[serde(default)]
+    actions: BTreeMap<String, Vec<Binding>>,
+    #[serde(default)]
+    axes: BTreeMap<String, Vec<AxisBinding>>,
+    #[serde(default)]
+    axes_2d: BTreeMap<String, Vec<Axis2dBinding>>,
+}
+
+impl InputContext {
+    pub fn bind_action(&mut self, action: &str, binding: Binding) -> &mut Self {
+        self.actions.entry(action.to_string()).or_default().push(binding);
+        self
+    }
+
+    pub fn bind_axis(&mut self, axis: &str, binding: AxisBinding) -> &mut Self {
+        self.axes.entry(axis.to_string()).or_default().push(binding);
+        self
+    }
+
+    pub fn bind_axis_2d(&mut self, axis: &str, binding: Axis2dBinding) -> &mut Self {
+        self.axes_2d.entry(axis.to_string()).or_default().push(binding);
+        self
+    }
+
+    /// Removes every binding for `name`, whether it is an action or an axis.
+    pub fn unbind(&mut self, name: &str) -> &mut Self {
+        self.actions.remove(name);
+        self.axes.remove(name);
+        self.axes_2d.remove(name);
+        self
+    }
+
+    pub fn action_bindings(&self, action: &str) -> &[Binding] {
+        self.actions.get(action).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    pub fn axis_bindings(&self, axis: &str) -> &[AxisBinding] {
+        self.axes.get(axis).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    pub fn axis_2d_bindings(&self, axis: &str) -> &[Axis2dBinding] {
+        self.axes_2d.get(axis).map(Vec::as_slice).unwrap_or(&[])
+    }
+}
+
+/// Named input contexts, of which only the one on top of the context stack is
+/// active. Pushing a `menu` context over `gameplay` disables the gameplay
+/// bindings until it is popped again.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ActionMap {
+    contexts: BTreeMap<String, InputContext>,
+    #[serde(skip)]
+    stack: Vec<String>,
+}
+
+impl ActionMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the context called `name`, creating it if it doesn't exist.
+    pub fn context_mut(&mut self, name: &str) -> &mut InputContext {
+        self.contexts.entry(name.to_string()).or_default()
+    }
+
+    pub fn context(&self, name: &str) -> Option<&InputContext> {
+        self.contexts.get(name)
+    }
+
+    pub fn push_context(&mut self, name: &str) -> &mut Self {
+        self.stack.push(name.to_string());
+        self
+    }
+
+    pub fn pop_context(&mut self) -> Option<String> {
+        self.stack.pop()
+    }
+
+    pub fn active_context(&self) -> Option<&InputContext> {
+        self.stack.last().and_then(|name| self.contexts.get(name))
+    }
+
+    /// Replaces the bindings with those from `other`, keeping the context stack.
+    pub fn replace_bindings(&mut self, other: ActionMap) -> &mut Self {
+        self.contexts = other.contexts;
+        self
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let contents = ron::ser::to_string_pretty(self, PrettyConfig::default()).with_path(&path)?;
+        fs::write(&path, contents).with_path(&path)
+    }
+}
+
+impl Loadable for ActionMap {
+    fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let file = File::open(&path).with_path(&path)?;
+        ron::de::from_reader(file).with_path(&path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempFile;
+
+    #[test]
+    fn bindings_survive_a_save_and_load() {
+        let mut actions = ActionMap::new();
+        actions
+            .context_mut("gameplay")
+            .bind_action("save", Binding::key(KeyCode::S).with_modifier(KeyCode::LControl))
+            .bind_action("fire", Binding::gamepad_button(GamepadButton::RightTrigger))
+            .bind_axis("steer", AxisBinding::Gamepad(GamepadAxis::LeftStickX));
+
+        let file = TempFile::new("action_map.ron");
+        actions.save(file.path()).unwrap();
+        let loaded = ActionMap::load(file.path()).unwrap();
+
+        let context = loaded.context("gameplay").unwrap();
+        assert_eq!(context.action_bindings("save"), actions.context("gameplay").unwrap().action_bindings("save"));
+        assert_eq!(context.action_bindings("fire"), &[Binding::gamepad_button(GamepadButton::RightTrigger)]);
+        assert_eq!(context.axis_bindings("steer"), &[AxisBinding::Gamepad(GamepadAxis::LeftStickX)]);
+    }
+
+    #[test]
+    fn unbind_removes_actions_and_axes() {
+        let mut actions = ActionMap::new();
+        let context = actions.context_mut("gameplay");
+        context
+            .bind_action("jump", Binding::key(KeyCode::Space))
+            .bind_axis("jump", AxisBinding::Gamepad(GamepadAxis::LeftTrigger));
+        context.unbind("jump");
+
+        assert!(context.action_bindings("jump").is_empty());
+        assert!(context.axis_bindings("jump").is_empty());
+    }
+}
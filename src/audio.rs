@@ -6,6 +6,7 @@ use log::info;
 use rodio::OutputStream;
 use rodio::OutputStreamHandle;
 
+use crate::result::Result;
 use crate::Sound;
 
 pub struct AudioSource {
@@ -13,9 +14,9 @@ pub struct AudioSource {
 }
 
 impl AudioSource {
-    pub fn queue_sound(&self, sound: &Sound) -> &Self {
-        self.sink.append(sound.decoder());
-        self
+    pub fn queue_sound(&self, sound: &Sound) -> Result<&Self> {
+        self.sink.append(sound.decoder()?);
+        Ok(self)
     }
 
     pub fn set_volume(&self, volume: f32) -> &Self {
@@ -25,25 +26,35 @@ impl AudioSource {
 }
 
 pub struct Audio {
-    _stream: OutputStream,
-    stream_handle: OutputStreamHandle,
+    output: Option<(OutputStream, OutputStreamHandle)>,
 }
 
 impl Audio {
-    pub(crate) fn new() -> Audio {
+    pub(crate) fn new() -> Result<Audio> {
         info!("Initializing audio backend");
-        let (stream, stream_handle) = OutputStream::try_default().unwrap();
+        let (stream, stream_handle) = OutputStream::try_default()?;
+
+        Ok(Audio { output: Some((stream, stream_handle)) })
+    }
+
+    pub(crate) fn null() -> Audio {
+        info!("Initializing null audio backend");
 
-        Audio { _stream: stream, stream_handle }
+        Audio { output: None }
     }
 
-    pub fn create_audio_source(&self) -> AudioSource {
-        AudioSource { sink: rodio::Sink::try_new(&self.stream_handle).unwrap() }
+    pub fn create_audio_source(&self) -> Result<AudioSource> {
+        match &self.output {
+            Some((_, stream_handle)) => Ok(AudioSource { sink: rodio::Sink::try_new(stream_handle)? }),
+            // An idle sink accepts sounds but is never polled, so nothing plays
+            None => Ok(AudioSource { sink: rodio::Sink::new_idle().0 }),
+        }
     }
 }
 
 impl Debug for Audio {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("Audio").field("_stream", &"stream").field("stream_handle", &"stream_handle").finish()
+        let output = self.output.as_ref().map(|_| "stream");
+        f.debug_struct("Audio").field("output", &output).finish()
     }
 }
@@ -0,0 +1,865 @@
+// Copyright 2021 Chay Nabors.
+
+use wgpu::TextureFormat;
+
+const WEIGHTS_2: [u32; 4] = [0, 21, 43, 64];
+const WEIGHTS_3: [u32; 8] = [0, 9, 18, 27, 37, 46, 55, 64];
+const WEIGHTS_4: [u32; 16] = [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
+
+// Bit i selects the subset of pixel i
+const PARTITIONS_2: [u16; 64] = [
+    0xcccc, 0x8888, 0xeeee, 0xecc8, 0xc880, 0xfeec, 0xfec8, 0xec80, 0xc800, 0xffec, 0xfe80, 0xe800, 0xffe8, 0xff00,
+    0xfff0, 0xf000, 0xf710, 0x008e, 0x7100, 0x08ce, 0x008c, 0x7310, 0x3100, 0x8cce, 0x088c, 0x3110, 0x6666, 0x366c,
+    0x17e8, 0x0ff0, 0x718e, 0x399c, 0xaaaa, 0xf0f0, 0x5a5a, 0x33cc, 0x3c3c, 0x55aa, 0x9696, 0xa55a, 0x73ce, 0x13c8,
+    0x324c, 0x3bdc, 0x6996, 0xc33c, 0x9966, 0x0660, 0x0272, 0x04e4, 0x4e40, 0x2720, 0xc936, 0x936c, 0x39c6, 0x639c,
+    0x9336, 0x9cc6, 0x817e, 0xe718, 0xccf0, 0x0fcc, 0x7744, 0xee22,
+];
+
+const PARTITIONS_3: [[u8; 16]; 64] = [
+    [0, 0, 1, 1, 0, 0, 1, 1, 0, 2, 2, 1, 2, 2, 2, 2],
+    [0, 0, 0, 1, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2, 2, 1],
+    [0, 0, 0, 0, 2, 0, 0, 1, 2, 2, 1, 1, 2, 2, 1, 1],
+    [0, 2, 2, 2, 0, 0, 2, 2, 0, 0, 1, 1, 0, 1, 1, 1],
+    [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2],
+    [0, 0, 1, 1, 0, 0, 1, 1, 0, 0, 2, 2, 0, 0, 2, 2],
+    [0, 0, 2, 2, 0, 0, 2, 2, 1, 1, 1, 1, 1, 1, 1, 1],
+    [0, 0, 1, 1, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2, 1, 1],
+    [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2],
+    [0, 0, 0, 0, 1, 1, 1, 1, 1, 1, 1, 1, 2, 2, 2, 2],
+    [0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2],
+    [0, 1, 1, 2, 0, 1, 1, 2, 0, 1, 1, 2, 0, 1, 1, 2],
+    [0, 1, 2, 2, 0, 1, 2, 2, 0, 1, 2, 2, 0, 1, 2, 2],
+    [0, 0, 1, 1, 0, 1, 1, 2, 1, 1, 2, 2, 1, 2, 2, 2],
+    [0, 0, 1, 1, 2, 0, 0, 1, 2, 2, 0, 0, 2, 2, 2, 0],
+    [0, 0, 0, 1, 0, 0, 1, 1, 0, 1, 1, 2, 1, 1, 2, 2],
+    [0, 1, 1, 1, 0, 0, 1, 1, 2, 0, 0, 1, 2, 2, 0, 0],
+    [0, 0, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2, 1, 1, 2, 2],
+    [0, 0, 2, 2, 0, 0, 2, 2, 0, 0, 2, 2, 1, 1, 1, 1],
+    [0, 1, 1, 1, 0, 1, 1, 1, 0, 2, 2, 2, 0, 2, 2, 2],
+    [0, 0, 0, 1, 0, 0, 0, 1, 2, 2, 2, 1, 2, 2, 2, 1],
+    [0, 0, 0, 0, 0, 0, 1, 1, 0, 1, 2, 2, 0, 1, 2, 2],
+    [0, 0, 0, 0, 1, 1, 0, 0, 2, 2, 1, 0, 2, 2, 1, 0],
+    [0, 1, 2, 2, 0, 1, 2, 2, 0, 0, 1, 1, 0, 0, 0, 0],
+    [0, 0, 1, 2, 0, 0, 1, 2, 1, 1, 2, 2, 2, 2, 2, 2],
+    [0, 1, 1, 0, 1, 2, 2, 1, 1, 2, 2, 1, 0, 1, 1, 0],
+    [0, 0, 0, 0, 0, 1, 1, 0, 1, 2, 2, 1, 1, 2, 2, 1],
+    [0, 0, 2, 2, 1, 1, 0, 2, 1, 1, 0, 2, 0, 0, 2, 2],
+    [0, 1, 1, 0, 0, 1, 1, 0, 2, 0, 0, 2, 2, 2, 2, 2],
+    [0, 0, 1, 1, 0, 1, 2, 2, 0, 1, 2, 2, 0, 0, 1, 1],
+    [0, 0, 0, 0, 2, 0, 0, 0, 2, 2, 1, 1, 2, 2, 2, 1],
+    [0, 0, 0, 0, 0, 0, 0, 2, 1, 1, 2, 2, 1, 2, 2, 2],
+    [0, 2, 2, 2, 0, 0, 2, 2, 0, 0, 1, 2, 0, 0, 1, 1],
+    [0, 0, 1, 1, 0, 0, 1, 2, 0, 0, 2, 2, 0, 2, 2, 2],
+    [0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2, 0],
+    [0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 0, 0, 0, 0],
+    [0, 1, 2, 0, 1, 2, 0, 1, 2, 0, 1, 2, 0, 1, 2, 0],
+    [0, 1, 2, 0, 2, 0, 1, 2, 1, 2, 0, 1, 0, 1, 2, 0],
+    [0, 0, 1, 1, 2, 2, 0, 0, 1, 1, 2, 2, 0, 0, 1, 1],
+    [0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 0, 0, 0, 0, 1, 1],
+    [0, 1, 0, 1, 0, 1, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 2, 1, 2, 1, 2, 1],
+    [0, 0, 2, 2, 1, 1, 2, 2, 0, 0, 2, 2, 1, 1, 2, 2],
+    [0, 0, 2, 2, 0, 0, 1, 1, 0, 0, 2, 2, 0, 0, 1, 1],
+    [0, 2, 2, 0, 1, 2, 2, 1, 0, 2, 2, 0, 1, 2, 2, 1],
+    [0, 1, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2, 0, 1, 0, 1],
+    [0, 0, 0, 0, 2, 1, 2, 1, 2, 1, 2, 1, 2, 1, 2, 1],
+    [0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 2, 2, 2, 2],
+    [0, 2, 2, 2, 0, 1, 1, 1, 0, 2, 2, 2, 0, 1, 1, 1],
+    [0, 0, 0, 2, 1, 1, 1, 2, 0, 0, 0, 2, 1, 1, 1, 2],
+    [0, 0, 0, 0, 2, 1, 1, 2, 2, 1, 1, 2, 2, 1, 1, 2],
+    [0, 2, 2, 2, 0, 1, 1, 1, 0, 1, 1, 1, 0, 2, 2, 2],
+    [0, 0, 0, 2, 1, 1, 1, 2, 1, 1, 1, 2, 0, 0, 0, 2],
+    [0, 1, 1, 0, 0, 1, 1, 0, 0, 1, 1, 0, 2, 2, 2, 2],
+    [0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 1, 2, 2, 1, 1, 2],
+    [0, 1, 1, 0, 0, 1, 1, 0, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 0, 2, 2, 0, 0, 1, 1, 0, 0, 1, 1, 0, 0, 2, 2],
+    [0, 0, 2, 2, 1, 1, 2, 2, 1, 1, 2, 2, 0, 0, 2, 2],
+    [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 1, 2],
+    [0, 0, 0, 2, 0, 0, 0, 1, 0, 0, 0, 2, 0, 0, 0, 1],
+    [0, 2, 2, 2, 1, 2, 2, 2, 0, 2, 2, 2, 1, 2, 2, 2],
+    [0, 1, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 1, 1, 1, 2, 0, 1, 1, 2, 2, 0, 1, 2, 2, 2, 0],
+];
+
+// Pixels whose index drops its most significant bit, beyond pixel 0
+const ANCHORS_2: [usize; 64] = [
+    15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 2, 8, 2, 2, 8, 8, 15, 2, 8, 2, 2, 8, 8, 2, 2,
+    15, 15, 6, 8, 2, 8, 15, 15, 2, 8, 2, 2, 2, 15, 15, 6, 6, 2, 6, 8, 15, 15, 2, 2, 15, 15, 15, 15, 15, 2, 2, 15,
+];
+
+const ANCHORS_3_2: [usize; 64] = [
+    3, 3, 15, 15, 8, 3, 15, 15, 8, 8, 6, 6, 6, 5, 3, 3, 3, 3, 8, 15, 3, 3, 6, 10, 5, 8, 8, 6, 8, 5, 15, 15, 8, 15, 3,
+    5, 6, 10, 8, 15, 15, 3, 15, 5, 15, 15, 15, 15, 3, 15, 5, 5, 5, 8, 5, 10, 5, 10, 8, 13, 15, 12, 3, 3,
+];
+
+const ANCHORS_3_3: [usize; 64] = [
+    15, 8, 8, 3, 15, 15, 3, 8, 15, 15, 15, 15, 15, 15, 15, 8, 15, 8, 15, 3, 15, 8, 15, 8, 3, 15, 6, 10, 15, 15, 10, 8,
+    15, 3, 15, 10, 10, 8, 9, 10, 6, 15, 8, 15, 3, 6, 6, 8, 15, 3, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 3, 15, 15, 8,
+];
+
+struct Bc7Mode {
+    subsets: usize,
+    partition_bits: u32,
+    rotation_bits: u32,
+    index_selection_bits: u32,
+    color_bits: u32,
+    alpha_bits: u32,
+    endpoint_pbits: bool,
+    shared_pbits: bool,
+    index_bits: u32,
+    secondary_index_bits: u32,
+}
+
+#[rustfmt::skip]
+const BC7_MODES: [Bc7Mode; 8] = [
+    Bc7Mode { subsets: 3, partition_bits: 4, rotation_bits: 0, index_selection_bits: 0, color_bits: 4, alpha_bits: 0, endpoint_pbits: true, shared_pbits: false, index_bits: 3, secondary_index_bits: 0 },
+    Bc7Mode { subsets: 2, partition_bits: 6, rotation_bits: 0, index_selection_bits: 0, color_bits: 6, alpha_bits: 0, endpoint_pbits: false, shared_pbits: true, index_bits: 3, secondary_index_bits: 0 },
+    Bc7Mode { subsets: 3, partition_bits: 6, rotation_bits: 0, index_selection_bits: 0, color_bits: 5, alpha_bits: 0, endpoint_pbits: false, shared_pbits: false, index_bits: 2, secondary_index_bits: 0 },
+    Bc7Mode { subsets: 2, partition_bits: 6, rotation_bits: 0, index_selection_bits: 0, color_bits: 7, alpha_bits: 0, endpoint_pbits: true, shared_pbits: false, index_bits: 2, secondary_index_bits: 0 },
+    Bc7Mode { subsets: 1, partition_bits: 0, rotation_bits: 2, index_selection_bits: 1, color_bits: 5, alpha_bits: 6, endpoint_pbits: false, shared_pbits: false, index_bits: 2, secondary_index_bits: 3 },
+    Bc7Mode { subsets: 1, partition_bits: 0, rotation_bits: 2, index_selection_bits: 0, color_bits: 7, alpha_bits: 8, endpoint_pbits: false, shared_pbits: false, index_bits: 2, secondary_index_bits: 2 },
+    Bc7Mode { subsets: 1, partition_bits: 0, rotation_bits: 0, index_selection_bits: 0, color_bits: 7, alpha_bits: 7, endpoint_pbits: true, shared_pbits: false, index_bits: 4, secondary_index_bits: 0 },
+    Bc7Mode { subsets: 2, partition_bits: 6, rotation_bits: 0, index_selection_bits: 0, color_bits: 5, alpha_bits: 5, endpoint_pbits: true, shared_pbits: false, index_bits: 2, secondary_index_bits: 0 },
+];
+
+// BC6H endpoint components, indexed as endpoint * 3 + channel
+const R0: usize = 0;
+const G0: usize = 1;
+const B0: usize = 2;
+const R1: usize = 3;
+const G1: usize = 4;
+const B1: usize = 5;
+const R2: usize = 6;
+const G2: usize = 7;
+const B2: usize = 8;
+const R3: usize = 9;
+const G3: usize = 10;
+const B3: usize = 11;
+
+struct Bc6hMode {
+    code: u32,
+    regions: usize,
+    transformed: bool,
+    endpoint_bits: u32,
+    delta_bits: [u32; 3],
+    /// (component, first bit, bit count) in the order they're stored after the mode bits
+    fields: &'static [(usize, u32, u32)],
+}
+
+#[rustfmt::skip]
+const BC6H_MODES: [Bc6hMode; 14] = [
+    Bc6hMode { code: 0x00, regions: 2, transformed: true, endpoint_bits: 10, delta_bits: [5, 5, 5], fields: &[
+        (G2, 4, 1), (B2, 4, 1), (B3, 4, 1), (R0, 0, 10), (G0, 0, 10), (B0, 0, 10), (R1, 0, 5), (G3, 4, 1), (G2, 0, 4),
+        (G1, 0, 5), (B3, 0, 1), (G3, 0, 4), (B1, 0, 5), (B3, 1, 1), (B2, 0, 4), (R2, 0, 5), (B3, 2, 1), (R3, 0, 5),
+        (B3, 3, 1),
+    ] },
+    Bc6hMode { code: 0x01, regions: 2, transformed: true, endpoint_bits: 7, delta_bits: [6, 6, 6], fields: &[
+        (G2, 5, 1), (G3, 4, 1), (G3, 5, 1), (R0, 0, 7), (B3, 0, 1), (B3, 1, 1), (B2, 4, 1), (G0, 0, 7), (B2, 5, 1),
+        (B3, 2, 1), (G2, 4, 1), (B0, 0, 7), (B3, 3, 1), (B3, 5, 1), (B3, 4, 1), (R1, 0, 6), (G2, 0, 4), (G1, 0, 6),
+        (G3, 0, 4), (B1, 0, 6), (B2, 0, 4), (R2, 0, 6), (R3, 0, 6),
+    ] },
+    Bc6hMode { code: 0x02, regions: 2, transformed: true, endpoint_bits: 11, delta_bits: [5, 4, 4], fields: &[
+        (R0, 0, 10), (G0, 0, 10), (B0, 0, 10), (R1, 0, 5), (R0, 10, 1), (G2, 0, 4), (G1, 0, 4), (G0, 10, 1),
+        (B3, 0, 1), (G3, 0, 4), (B1, 0, 4), (B0, 10, 1), (B3, 1, 1), (B2, 0, 4), (R2, 0, 5), (B3, 2, 1), (R3, 0, 5),
+        (B3, 3, 1),
+    ] },
+    Bc6hMode { code: 0x06, regions: 2, transformed: true, endpoint_bits: 11, delta_bits: [4, 5, 4], fields: &[
+        (R0, 0, 10), (G0, 0, 10), (B0, 0, 10), (R1, 0, 4), (R0, 10, 1), (G3, 4, 1), (G2, 0, 4), (G1, 0, 5),
+        (G0, 10, 1), (G3, 0, 4), (B1, 0, 4), (B0, 10, 1), (B3, 1, 1), (B2, 0, 4), (R2, 0, 4), (B3, 0, 1), (B3, 2, 1),
+        (R3, 0, 4), (G2, 4, 1), (B3, 3, 1),
+    ] },
+    Bc6hMode { code: 0x0a, regions: 2, transformed: true, endpoint_bits: 11, delta_bits: [4, 4, 5], fields: &[
+        (R0, 0, 10), (G0, 0, 10), (B0, 0, 10), (R1, 0, 4), (R0, 10, 1), (B2, 4, 1), (G2, 0, 4), (G1, 0, 4),
+        (G0, 10, 1), (B3, 0, 1), (G3, 0, 4), (B1, 0, 5), (B0, 10, 1), (B2, 0, 4), (R2, 0, 4), (B3, 1, 1), (B3, 2, 1),
+        (R3, 0, 4), (B3, 4, 1), (B3, 3, 1),
+    ] },
+    Bc6hMode { code: 0x0e, regions: 2, transformed: true, endpoint_bits: 9, delta_bits: [5, 5, 5], fields: &[
+        (R0, 0, 9), (B2, 4, 1), (G0, 0, 9), (G2, 4, 1), (B0, 0, 9), (B3, 4, 1), (R1, 0, 5), (G3, 4, 1), (G2, 0, 4),
+        (G1, 0, 5), (B3, 0, 1), (G3, 0, 4), (B1, 0, 5), (B3, 1, 1), (B2, 0, 4), (R2, 0, 5), (B3, 2, 1), (R3, 0, 5),
+        (B3, 3, 1),
+    ] },
+    Bc6hMode { code: 0x12, regions: 2, transformed: true, endpoint_bits: 8, delta_bits: [6, 5, 5], fields: &[
+        (R0, 0, 8), (G3, 4, 1), (B2, 4, 1), (G0, 0, 8), (B3, 2, 1), (G2, 4, 1), (B0, 0, 8), (B3, 3, 1), (B3, 4, 1),
+        (R1, 0, 6), (G2, 0, 4), (G1, 0, 5), (B3, 0, 1), (G3, 0, 4), (B1, 0, 5), (B3, 1, 1), (B2, 0, 4), (R2, 0, 6),
+        (R3, 0, 6),
+    ] },
+    Bc6hMode { code: 0x16, regions: 2, transformed: true, endpoint_bits: 8, delta_bits: [5, 6, 5], fields: &[
+        (R0, 0, 8), (B3, 0, 1), (B2, 4, 1), (G0, 0, 8), (G2, 5, 1), (G2, 4, 1), (B0, 0, 8), (G3, 5, 1), (B3, 4, 1),
+        (R1, 0, 5), (G3, 4, 1), (G2, 0, 4), (G1, 0, 6), (G3, 0, 4), (B1, 0, 5), (B3, 1, 1), (B2, 0, 4), (R2, 0, 5),
+        (B3, 2, 1), (R3, 0, 5), (B3, 3, 1),
+    ] },
+    Bc6hMode { code: 0x1a, regions: 2, transformed: true, endpoint_bits: 8, delta_bits: [5, 5, 6], fields: &[
+        (R0, 0, 8), (B3, 1, 1), (B2, 4, 1), (G0, 0, 8), (B2, 5, 1), (G2, 4, 1), (B0, 0, 8), (B3, 5, 1), (B3, 4, 1),
+        (R1, 0, 5), (G3, 4, 1), (G2, 0, 4), (G1, 0, 5), (B3, 0, 1), (G3, 0, 4), (B1, 0, 6), (B2, 0, 4), (R2, 0, 5),
+        (B3, 2, 1), (R3, 0, 5), (B3, 3, 1),
+    ] },
+    Bc6hMode { code: 0x1e, regions: 2, transformed: false, endpoint_bits: 6, delta_bits: [6, 6, 6], fields: &[
+        (R0, 0, 6), (G3, 4, 1), (B3, 0, 1), (B3, 1, 1), (B2, 4, 1), (G0, 0, 6), (G2, 5, 1), (B2, 5, 1), (B3, 2, 1),
+        (G2, 4, 1), (B0, 0, 6), (G3, 5, 1), (B3, 3, 1), (B3, 5, 1), (B3, 4, 1), (R1, 0, 6), (G2, 0, 4), (G1, 0, 6),
+        (G3, 0, 4), (B1, 0, 6), (B2, 0, 4), (R2, 0, 6), (R3, 0, 6),
+    ] },
+    Bc6hMode { code: 0x03, regions: 1, transformed: false, endpoint_bits: 10, delta_bits: [10, 10, 10], fields: &[
+        (R0, 0, 10), (G0, 0, 10), (B0, 0, 10), (R1, 0, 10), (G1, 0, 10), (B1, 0, 10),
+    ] },
+    Bc6hMode { code: 0x07, regions: 1, transformed: true, endpoint_bits: 11, delta_bits: [9, 9, 9], fields: &[
+        (R0, 0, 10), (G0, 0, 10), (B0, 0, 10), (R1, 0, 9), (R0, 10, 1), (G1, 0, 9), (G0, 10, 1), (B1, 0, 9),
+        (B0, 10, 1),
+    ] },
+    Bc6hMode { code: 0x0b, regions: 1, transformed: true, endpoint_bits: 12, delta_bits: [8, 8, 8], fields: &[
+        (R0, 0, 10), (G0, 0, 10), (B0, 0, 10), (R1, 0, 8), (R0, 11, 1), (R0, 10, 1), (G1, 0, 8), (G0, 11, 1),
+        (G0, 10, 1), (B1, 0, 8), (B0, 11, 1), (B0, 10, 1),
+    ] },
+    Bc6hMode { code: 0x0f, regions: 1, transformed: true, endpoint_bits: 16, delta_bits: [4, 4, 4], fields: &[
+        (R0, 0, 10), (G0, 0, 10), (B0, 0, 10), (R1, 0, 4), (R0, 15, 1), (R0, 14, 1), (R0, 13, 1), (R0, 12, 1),
+        (R0, 11, 1), (R0, 10, 1), (G1, 0, 4), (G0, 15, 1), (G0, 14, 1), (G0, 13, 1), (G0, 12, 1), (G0, 11, 1),
+        (G0, 10, 1), (B1, 0, 4), (B0, 15, 1), (B0, 14, 1), (B0, 13, 1), (B0, 12, 1), (B0, 11, 1), (B0, 10, 1),
+    ] },
+];
+
+/// Bytes per 4x4 block, or `None` if the format isn't block compressed.
+pub(crate) fn block_size(format: TextureFormat) -> Option<u32> {
+    match format {
+        TextureFormat::Bc1RgbaUnorm
+        | TextureFormat::Bc1RgbaUnormSrgb
+        | TextureFormat::Bc4RUnorm
+        | TextureFormat::Bc4RSnorm => Some(8),
+        TextureFormat::Bc2RgbaUnorm
+        | TextureFormat::Bc2RgbaUnormSrgb
+        | TextureFormat::Bc3RgbaUnorm
+        | TextureFormat::Bc3RgbaUnormSrgb
+        | TextureFormat::Bc5RgUnorm
+        | TextureFormat::Bc5RgSnorm
+        | TextureFormat::Bc6hRgbUfloat
+        | TextureFormat::Bc6hRgbSfloat
+        | TextureFormat::Bc7RgbaUnorm
+        | TextureFormat::Bc7RgbaUnormSrgb => Some(16),
+        _ => None,
+    }
+}
+
+/// The uncompressed format `decode` produces for `format`.
+pub(crate) fn decoded_format(format: TextureFormat) -> TextureFormat {
+    match format {
+        TextureFormat::Bc1RgbaUnormSrgb | TextureFormat::Bc2RgbaUnormSrgb | TextureFormat::Bc3RgbaUnormSrgb => {
+            TextureFormat::Rgba8UnormSrgb
+        },
+        TextureFormat::Bc7RgbaUnormSrgb => TextureFormat::Rgba8UnormSrgb,
+        TextureFormat::Bc4RSnorm | TextureFormat::Bc5RgSnorm => TextureFormat::Rgba8Snorm,
+        TextureFormat::Bc6hRgbUfloat | TextureFormat::Bc6hRgbSfloat => TextureFormat::Rgba16Float,
+        format if block_size(format).is_some() => TextureFormat::Rgba8Unorm,
+        format => format,
+    }
+}
+
+/// Decodes one mip level of block compressed data into `decoded_format(format)`.
+pub(crate) fn decode(format: TextureFormat, width: u32, height: u32, data: &[u8]) -> Vec<u8> {
+    match format {
+        TextureFormat::Bc1RgbaUnorm | TextureFormat::Bc1RgbaUnormSrgb => {
+            decode_blocks(width, height, data, 8, 4, |block, out| decode_bc1(block, out, false))
+        },
+        TextureFormat::Bc2RgbaUnorm | TextureFormat::Bc2RgbaUnormSrgb => {
+            decode_blocks(width, height, data, 16, 4, decode_bc2)
+        },
+        TextureFormat::Bc3RgbaUnorm | TextureFormat::Bc3RgbaUnormSrgb => {
+            decode_blocks(width, height, data, 16, 4, decode_bc3)
+        },
+        TextureFormat::Bc4RUnorm => {
+            decode_blocks(width, height, data, 8, 4, |block, out| decode_bc4(block, out, false))
+        },
+        TextureFormat::Bc4RSnorm => decode_blocks(width, height, data, 8, 4, |block, out| decode_bc4(block, out, true)),
+        TextureFormat::Bc5RgUnorm => {
+            decode_blocks(width, height, data, 16, 4, |block, out| decode_bc5(block, out, false))
+        },
+        TextureFormat::Bc5RgSnorm => {
+            decode_blocks(width, height, data, 16, 4, |block, out| decode_bc5(block, out, true))
+        },
+        TextureFormat::Bc6hRgbUfloat => {
+            decode_blocks(width, height, data, 16, 8, |block, out| decode_bc6h(block, out, false))
+        },
+        TextureFormat::Bc6hRgbSfloat => {
+            decode_blocks(width, height, data, 16, 8, |block, out| decode_bc6h(block, out, true))
+        },
+        TextureFormat::Bc7RgbaUnorm | TextureFormat::Bc7RgbaUnormSrgb => {
+            decode_blocks(width, height, data, 16, 4, decode_bc7)
+        },
+        _ => data.to_vec(),
+    }
+}
+
+fn decode_blocks(
+    width: u32,
+    height: u32,
+    data: &[u8],
+    block_size: usize,
+    pixel_size: usize,
+    decode_block: impl Fn(&[u8], &mut [u8]),
+) -> Vec<u8> {
+    let (width, height) = (width as usize, height as usize);
+    let blocks_x = (width + 3) / 4;
+    let blocks_y = (height + 3) / 4;
+
+    let mut pixels = vec![0; width * height * pixel_size];
+    let mut block_pixels = vec![0; 16 * pixel_size];
+    for (i, block) in data.chunks_exact(block_size).take(blocks_x * blocks_y).enumerate() {
+        decode_block(block, &mut block_pixels);
+
+        // Blocks on the right and bottom edges may hang off the image
+        let (x, y) = (i % blocks_x * 4, i / blocks_x * 4);
+        let row_size = (width - x).min(4) * pixel_size;
+        for row in 0..(height - y).min(4) {
+            let offset = ((y + row) * width + x) * pixel_size;
+            let block_offset = row * 4 * pixel_size;
+            pixels[offset..offset + row_size].copy_from_slice(&block_pixels[block_offset..block_offset + row_size]);
+        }
+    }
+
+    pixels
+}
+
+fn unpack_565(color: u16) -> [u32; 3] {
+    let r = (color >> 11 & 0x1f) as u32;
+    let g = (color >> 5 & 0x3f) as u32;
+    let b = (color & 0x1f) as u32;
+    [r << 3 | r >> 2, g << 2 | g >> 4, b << 3 | b >> 2]
+}
+
+fn decode_bc1(block: &[u8], out: &mut [u8], always_opaque: bool) {
+    let color0 = u16::from_le_bytes([block[0], block[1]]);
+    let color1 = u16::from_le_bytes([block[2], block[3]]);
+    let (e0, e1) = (unpack_565(color0), unpack_565(color1));
+
+    let mut palette = [[0u8, 0, 0, 255]; 4];
+    for (channel, (c0, c1)) in e0.iter().zip(&e1).enumerate() {
+        palette[0][channel] = *c0 as u8;
+        palette[1][channel] = *c1 as u8;
+        if always_opaque || color0 > color1 {
+            palette[2][channel] = ((2 * c0 + c1) / 3) as u8;
+            palette[3][channel] = ((c0 + 2 * c1) / 3) as u8;
+        } else {
+            palette[2][channel] = ((c0 + c1) / 2) as u8;
+        }
+    }
+
+    if !always_opaque && color0 <= color1 {
+        palette[3] = [0, 0, 0, 0];
+    }
+
+    let indices = u32::from_le_bytes([block[4], block[5], block[6], block[7]]);
+    for (i, pixel) in out.chunks_exact_mut(4).enumerate() {
+        pixel.copy_from_slice(&palette[(indices >> (i * 2) & 3) as usize]);
+    }
+}
+
+fn decode_bc2(block: &[u8], out: &mut [u8]) {
+    decode_bc1(&block[8..], out, true);
+
+    let alpha = u64::from_le_bytes([block[0], block[1], block[2], block[3], block[4], block[5], block[6], block[7]]);
+    for (i, pixel) in out.chunks_exact_mut(4).enumerate() {
+        pixel[3] = (alpha >> (i * 4) & 0xf) as u8 * 17;
+    }
+}
+
+fn decode_bc3(block: &[u8], out: &mut [u8]) {
+    decode_bc1(&block[8..], out, true);
+
+    let alpha = decode_channel(&block[..8], false);
+    for (pixel, alpha) in out.chunks_exact_mut(4).zip(&alpha) {
+        pixel[3] = *alpha;
+    }
+}
+
+fn decode_bc4(block: &[u8], out: &mut [u8], signed: bool) {
+    let red = decode_channel(block, signed);
+    let one = if signed { 127 } else { 255 };
+    for (pixel, red) in out.chunks_exact_mut(4).zip(&red) {
+        pixel.copy_from_slice(&[*red, 0, 0, one]);
+    }
+}
+
+fn decode_bc5(block: &[u8], out: &mut [u8], signed: bool) {
+    let red = decode_channel(&block[..8], signed);
+    let green = decode_channel(&block[8..], signed);
+    let one = if signed { 127 } else { 255 };
+    for (i, pixel) in out.chunks_exact_mut(4).enumerate() {
+        pixel.copy_from_slice(&[red[i], green[i], 0, one]);
+    }
+}
+
+/// Decodes the single channel block shared by BC3 alpha, BC4 and BC5. Signed values are returned as `i8` bits.
+fn decode_channel(block: &[u8], signed: bool) -> [u8; 16] {
+    let (e0, e1, min, max) = match signed {
+        true => ((block[0] as i8).max(-127) as i32, (block[1] as i8).max(-127) as i32, -127, 127),
+        false => (block[0] as i32, block[1] as i32, 0, 255),
+    };
+
+    let mut palette = [e0, e1, min, min, min, min, min, max];
+    if e0 > e1 {
+        for i in 1..7 {
+            palette[i as usize + 1] = ((7 - i) * e0 + i * e1) / 7;
+        }
+    } else {
+        for i in 1..5 {
+            palette[i as usize + 1] = ((5 - i) * e0 + i * e1) / 5;
+        }
+    }
+
+    let indices = u64::from_le_bytes([block[2], block[3], block[4], block[5], block[6], block[7], 0, 0]);
+    let mut values = [0; 16];
+    for (i, value) in values.iter_mut().enumerate() {
+        *value = palette[(indices >> (i * 3) & 7) as usize] as u8;
+    }
+
+    values
+}
+
+struct BitReader {
+    bits: u128,
+    position: u32,
+}
+
+impl BitReader {
+    fn new(block: &[u8]) -> Self {
+        let mut bytes = [0; 16];
+        bytes.copy_from_slice(&block[..16]);
+        Self { bits: u128::from_le_bytes(bytes), position: 0 }
+    }
+
+    fn read(&mut self, count: u32) -> u32 {
+        let value = (self.bits >> self.position) as u32 & ((1u64 << count) - 1) as u32;
+        self.position += count;
+        value
+    }
+}
+
+fn interpolate(e0: u32, e1: u32, index: u32, index_bits: u32) -> u32 {
+    let weight = match index_bits {
+        2 => WEIGHTS_2[index as usize],
+        3 => WEIGHTS_3[index as usize],
+        _ => WEIGHTS_4[index as usize],
+    };
+
+    ((64 - weight) * e0 + weight * e1 + 32) >> 6
+}
+
+fn decode_bc7(block: &[u8], out: &mut [u8]) {
+    let mut reader = BitReader::new(block);
+
+    // The mode is the number of zero bits before the first set bit
+    let mode = match (0..8).find(|_| reader.read(1) == 1) {
+        Some(mode) => &BC7_MODES[mode],
+        None => {
+            out.fill(0);
+            return;
+        },
+    };
+
+    let partition = reader.read(mode.partition_bits) as usize;
+    let rotation = reader.read(mode.rotation_bits);
+    let index_selection = reader.read(mode.index_selection_bits);
+
+    let endpoint_count = mode.subsets * 2;
+    let mut endpoints = [[0u32; 4]; 6];
+    for channel in 0..3 {
+        for endpoint in endpoints.iter_mut().take(endpoint_count) {
+            endpoint[channel] = reader.read(mode.color_bits);
+        }
+    }
+
+    if mode.alpha_bits > 0 {
+        for endpoint in endpoints.iter_mut().take(endpoint_count) {
+            endpoint[3] = reader.read(mode.alpha_bits);
+        }
+    }
+
+    let (mut color_bits, mut alpha_bits) = (mode.color_bits, mode.alpha_bits);
+    if mode.endpoint_pbits || mode.shared_pbits {
+        let mut pbits = [0; 6];
+        if mode.endpoint_pbits {
+            for pbit in pbits.iter_mut().take(endpoint_count) {
+                *pbit = reader.read(1);
+            }
+        } else {
+            for subset in 0..mode.subsets {
+                let pbit = reader.read(1);
+                pbits[subset * 2] = pbit;
+                pbits[subset * 2 + 1] = pbit;
+            }
+        }
+
+        for (endpoint, pbit) in endpoints.iter_mut().zip(&pbits).take(endpoint_count) {
+            for component in endpoint.iter_mut() {
+                *component = *component << 1 | pbit;
+            }
+        }
+
+        color_bits += 1;
+        if alpha_bits > 0 {
+            alpha_bits += 1;
+        }
+    }
+
+    // Replicate the high bits into the low bits to widen each component to 8 bits
+    for endpoint in endpoints.iter_mut().take(endpoint_count) {
+        for component in endpoint.iter_mut().take(3) {
+            *component = expand(*component, color_bits);
+        }
+        endpoint[3] = if alpha_bits > 0 { expand(endpoint[3], alpha_bits) } else { 255 };
+    }
+
+    let subset_of = |pixel: usize| match mode.subsets {
+        1 => 0,
+        2 => (PARTITIONS_2[partition] >> pixel & 1) as usize,
+        _ => PARTITIONS_3[partition][pixel] as usize,
+    };
+
+    let is_anchor = |pixel: usize| match mode.subsets {
+        1 => pixel == 0,
+        2 => pixel == 0 || pixel == ANCHORS_2[partition],
+        _ => pixel == 0 || pixel == ANCHORS_3_2[partition] || pixel == ANCHORS_3_3[partition],
+    };
+
+    let mut indices = [0; 16];
+    for (pixel, index) in indices.iter_mut().enumerate() {
+        *index = reader.read(mode.index_bits - is_anchor(pixel) as u32);
+    }
+
+    let mut secondary_indices = [0; 16];
+    if mode.secondary_index_bits > 0 {
+        for (pixel, index) in secondary_indices.iter_mut().enumerate() {
+            *index = reader.read(mode.secondary_index_bits - (pixel == 0) as u32);
+        }
+    }
+
+    for (pixel, texel) in out.chunks_exact_mut(4).enumerate() {
+        let subset = subset_of(pixel);
+        let (e0, e1) = (endpoints[subset * 2], endpoints[subset * 2 + 1]);
+
+        let mut color = [0; 4];
+        if mode.secondary_index_bits > 0 {
+            let (color_index, color_index_bits, alpha_index, alpha_index_bits) = match index_selection {
+                0 => (indices[pixel], mode.index_bits, secondary_indices[pixel], mode.secondary_index_bits),
+                _ => (secondary_indices[pixel], mode.secondary_index_bits, indices[pixel], mode.index_bits),
+            };
+            for (channel, value) in color.iter_mut().enumerate().take(3) {
+                *value = interpolate(e0[channel], e1[channel], color_index, color_index_bits);
+            }
+            color[3] = interpolate(e0[3], e1[3], alpha_index, alpha_index_bits);
+        } else {
+            for (channel, value) in color.iter_mut().enumerate() {
+                *value = interpolate(e0[channel], e1[channel], indices[pixel], mode.index_bits);
+            }
+        }
+
+        match rotation {
+            1 => color.swap(0, 3),
+            2 => color.swap(1, 3),
+            3 => color.swap(2, 3),
+            _ => (),
+        }
+
+        for (value, color) in texel.iter_mut().zip(&color) {
+            *value = *color as u8;
+        }
+    }
+}
+
+fn expand(value: u32, bits: u32) -> u32 {
+    let value = value << (8 - bits);
+    value | value >> bits
+}
+
+fn decode_bc6h(block: &[u8], out: &mut [u8], signed: bool) {
+    let mut reader = BitReader::new(block);
+
+    let mut code = reader.read(2);
+    if code > 1 {
+        code |= reader.read(3) << 2;
+    }
+
+    let mode = match BC6H_MODES.iter().find(|mode| mode.code == code) {
+        Some(mode) => mode,
+        None => {
+            out.fill(0);
+            return;
+        },
+    };
+
+    let mut components = [0i32; 12];
+    for &(component, first_bit, count) in mode.fields {
+        components[component] |= (reader.read(count) << first_bit) as i32;
+    }
+
+    let partition = if mode.regions == 2 { reader.read(5) as usize } else { 0 };
+
+    let endpoint_count = mode.regions * 2;
+    let mut endpoints = [[0i32; 3]; 4];
+    for (i, endpoint) in endpoints.iter_mut().enumerate().take(endpoint_count) {
+        endpoint.copy_from_slice(&components[i * 3..i * 3 + 3]);
+    }
+
+    // Every endpoint after the first is stored as a signed delta in transformed modes
+    if signed {
+        for component in endpoints[0].iter_mut() {
+            *component = sign_extend(*component, mode.endpoint_bits);
+        }
+    }
+
+    if signed || mode.transformed {
+        for endpoint in endpoints.iter_mut().take(endpoint_count).skip(1) {
+            for (component, bits) in endpoint.iter_mut().zip(&mode.delta_bits) {
+                *component = sign_extend(*component, *bits);
+            }
+        }
+    }
+
+    if mode.transformed {
+        let mask = (1 << mode.endpoint_bits) - 1;
+        let base = endpoints[0];
+        for endpoint in endpoints.iter_mut().take(endpoint_count).skip(1) {
+            for (component, base) in endpoint.iter_mut().zip(&base) {
+                *component = (*component + base) & mask;
+                if signed {
+                    *component = sign_extend(*component, mode.endpoint_bits);
+                }
+            }
+        }
+    }
+
+    for endpoint in endpoints.iter_mut().take(endpoint_count) {
+        for component in endpoint.iter_mut() {
+            *component = unquantize(*component, mode.endpoint_bits, signed);
+        }
+    }
+
+    let index_bits = if mode.regions == 2 { 3 } else { 4 };
+    for (pixel, texel) in out.chunks_exact_mut(8).enumerate() {
+        let anchor = pixel == 0 || (mode.regions == 2 && pixel == ANCHORS_2[partition]);
+        let index = reader.read(index_bits - anchor as u32) as usize;
+        let weight = match mode.regions {
+            2 => WEIGHTS_3[index] as i32,
+            _ => WEIGHTS_4[index] as i32,
+        };
+
+        let region = if mode.regions == 2 { (PARTITIONS_2[partition] >> pixel & 1) as usize } else { 0 };
+        let (e0, e1) = (endpoints[region * 2], endpoints[region * 2 + 1]);
+
+        for (channel, bytes) in texel.chunks_exact_mut(2).take(3).enumerate() {
+            let value = ((64 - weight) * e0[channel] + weight * e1[channel] + 32) >> 6;
+            bytes.copy_from_slice(&finish_unquantize(value, signed).to_le_bytes());
+        }
+        // Half precision 1.0
+        texel[6..8].copy_from_slice(&0x3c00u16.to_le_bytes());
+    }
+}
+
+fn sign_extend(value: i32, bits: u32) -> i32 {
+    let shift = 32 - bits;
+    value << shift >> shift
+}
+
+fn unquantize(value: i32, bits: u32, signed: bool) -> i32 {
+    if signed {
+        if bits >= 16 {
+            return value;
+        }
+
+        let magnitude = value.abs();
+        let unquantized = if magnitude == 0 {
+            0
+        } else if magnitude >= (1 << (bits - 1)) - 1 {
+            0x7fff
+        } else {
+            ((magnitude << 15) + 0x4000) >> (bits - 1)
+        };
+
+        if value < 0 {
+            -unquantized
+        } else {
+            unquantized
+        }
+    } else if bits >= 15 || value == 0 {
+        value
+    } else if value == (1 << bits) - 1 {
+        0xffff
+    } else {
+        ((value << 16) + 0x8000) >> bits
+    }
+}
+
+/// Scales an interpolated value into the bits of a half precision float.
+fn finish_unquantize(value: i32, signed: bool) -> u16 {
+    if signed {
+        let magnitude = ((value.abs() * 31) >> 5) as u16;
+        if value < 0 {
+            0x8000 | magnitude
+        } else {
+            magnitude
+        }
+    } else {
+        ((value * 31) >> 6) as u16
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Packs `(value, bit count)` fields LSB first, the way BC6H and BC7 blocks are laid out
+    fn pack(fields: &[(u32, u32)]) -> [u8; 16] {
+        let mut bits = 0u128;
+        let mut position = 0;
+        for &(value, count) in fields {
+            bits |= (value as u128) << position;
+            position += count;
+        }
+        assert_eq!(position, 128);
+        bits.to_le_bytes()
+    }
+
+    #[test]
+    fn bc1_four_color_block() {
+        // Red and blue endpoints, pixels 0 to 3 select each palette entry in turn
+        let block = [0x00, 0xf8, 0x1f, 0x00, 0xe4, 0, 0, 0];
+        let pixels = decode(TextureFormat::Bc1RgbaUnorm, 4, 4, &block);
+
+        assert_eq!(pixels.len(), 64);
+        assert_eq!(pixels[..16], [255, 0, 0, 255, 0, 0, 255, 255, 170, 0, 85, 255, 85, 0, 170, 255]);
+        assert!(pixels[16..].chunks(4).all(|pixel| pixel == [255, 0, 0, 255]));
+    }
+
+    #[test]
+    fn bc1_three_color_block_has_transparent_black() {
+        let block = [0x1f, 0x00, 0x00, 0xf8, 0xe4, 0, 0, 0];
+        let pixels = decode(TextureFormat::Bc1RgbaUnorm, 4, 4, &block);
+
+        assert_eq!(pixels[8..16], [127, 0, 127, 255, 0, 0, 0, 0]);
+    }
+
+    // Reads decoded BC6H pixels back as half precision bits
+    fn halves(pixels: &[u8]) -> Vec<u16> {
+        pixels.chunks_exact(2).map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]])).collect()
+    }
+
+    // Pixel 0 selects the first endpoint, pixel 1 the second and pixel 2 an index roughly halfway between
+    fn bc6h_indices(fields: &mut Vec<(u32, u32)>) {
+        fields.extend(&[(0, 3), (15, 4), (8, 4)]);
+        fields.extend(&[(15, 4); 13]);
+    }
+
+    #[test]
+    fn bc2_has_explicit_alpha_and_always_four_colors() {
+        // Blue and red endpoints in the order that would select three colors in BC1, alphas 0, 15 and 8 of 15
+        let block = [0xf0, 0x08, 0, 0, 0, 0, 0, 0, 0x1f, 0x00, 0x00, 0xf8, 0xe4, 0, 0, 0];
+        let pixels = decode(TextureFormat::Bc2RgbaUnorm, 4, 4, &block);
+
+        assert_eq!(pixels[..16], [0, 0, 255, 0, 255, 0, 0, 255, 85, 0, 170, 136, 170, 0, 85, 0]);
+    }
+
+    #[test]
+    fn bc3_interpolates_six_alphas() {
+        // Alpha endpoints 0 and 255 select six interpolated values plus 0 and 255, pixels use indices 2, 5, 6 and 7
+        let block = [0, 255, 0xaa, 0x0f, 0, 0, 0, 0, 0x1f, 0x00, 0x00, 0xf8, 0xe4, 0, 0, 0];
+        let pixels = decode(TextureFormat::Bc3RgbaUnorm, 4, 4, &block);
+
+        assert_eq!(pixels[..16], [0, 0, 255, 51, 255, 0, 0, 204, 85, 0, 170, 0, 170, 0, 85, 255]);
+        assert_eq!(pixels[19], 0);
+    }
+
+    #[test]
+    fn bc5_signed_channels() {
+        // Red interpolates six values between -125 and 125 with indices 0, 2, 6 and 7, green eight values between 70
+        // and -70 with indices 1, 2, 4 and 7
+        let block = [0x83, 0x7d, 0x90, 0x0f, 0, 0, 0, 0, 0x46, 0xba, 0x11, 0x0f, 0, 0, 0, 0];
+        let pixels: Vec<i8> =
+            decode(TextureFormat::Bc5RgSnorm, 4, 4, &block).into_iter().map(|value| value as i8).collect();
+
+        assert_eq!(pixels[..16], [-125, -70, 0, 127, -75, 50, 0, 127, -127, 10, 0, 127, 127, -50, 0, 127]);
+    }
+
+    #[test]
+    fn bc6h_unsigned_block() {
+        // Mode 11 with 10 bit endpoints (0, 0, 0) and (1023, 512, 0)
+        let mut fields = vec![(0x03, 5), (0, 10), (0, 10), (0, 10), (1023, 10), (512, 10), (0, 10)];
+        bc6h_indices(&mut fields);
+        let pixels = halves(&decode(TextureFormat::Bc6hRgbUfloat, 4, 4, &pack(&fields)));
+
+        assert_eq!(pixels[..12], [0, 0, 0, 0x3c00, 0x7bff, 0x3e0f, 0, 0x3c00, 0x41df, 0x20f8, 0, 0x3c00]);
+    }
+
+    #[test]
+    fn bc6h_signed_block() {
+        // Mode 11 with 10 bit endpoints (-511, 0, -256) and (511, 256, 0)
+        let mut fields = vec![(0x03, 5), (0x201, 10), (0, 10), (0x300, 10), (0x1ff, 10), (0x100, 10), (0, 10)];
+        bc6h_indices(&mut fields);
+        let pixels = halves(&decode(TextureFormat::Bc6hRgbSfloat, 4, 4, &pack(&fields)));
+
+        assert_eq!(
+            pixels[..12],
+            [0xfbff, 0, 0xbe1f, 0x3c00, 0x7bff, 0x3e1f, 0, 0x3c00, 0x07c0, 0x2100, 0x9d1e, 0x3c00]
+        );
+    }
+
+    #[test]
+    fn bc6h_transformed_endpoints_are_deltas() {
+        // Mode 12 with 11 bit base endpoint (1024, 100, 2000) and deltas (-1, 255, -256), the high base bits are
+        // stored after each delta
+        let mut fields = vec![(0x07, 5), (0, 10), (100, 10), (976, 10), (0x1ff, 9), (1, 1), (0xff, 9), (0, 1)];
+        fields.extend(&[(0x100, 9), (1, 1)]);
+        bc6h_indices(&mut fields);
+        let pixels = halves(&decode(TextureFormat::Bc6hRgbUfloat, 4, 4, &pack(&fields)));
+
+        assert_eq!(
+            pixels[..12],
+            [0x3e07, 0x0615, 0x791f, 0x3c00, 0x3df8, 0x1586, 0x699f, 0x3c00, 0x3dff, 0x0e49, 0x70e3, 0x3c00]
+        );
+    }
+
+    #[test]
+    fn bc4_interpolates_eight_values() {
+        // Pixels 0 to 2 use indices 0, 1 and 2
+        let block = [255, 0, 0b10_001_000, 0, 0, 0, 0, 0];
+        let pixels = decode(TextureFormat::Bc4RUnorm, 4, 4, &block);
+
+        assert_eq!(pixels[..12], [255, 0, 0, 255, 0, 0, 0, 255, 218, 0, 0, 255]);
+    }
+
+    #[test]
+    fn bc7_mode_6_block() {
+        // Black transparent to opaque white, pixel 0 uses the first endpoint and every other pixel the second
+        let mut fields = vec![(1 << 6, 7)];
+        fields.extend([(0, 7), (127, 7)].iter().cycle().take(8));
+        fields.extend(&[(0, 1), (1, 1), (0, 3)]);
+        fields.extend(&[(15, 4); 15]);
+        let pixels = decode(TextureFormat::Bc7RgbaUnorm, 4, 4, &pack(&fields));
+
+        assert_eq!(pixels[..4], [0, 0, 0, 0]);
+        assert!(pixels[4..].iter().all(|&value| value == 255));
+    }
+
+    #[test]
+    fn partial_blocks_are_cropped() {
+        // A 6x6 image needs 2x2 blocks, each a solid color
+        let solid = |color: u16| {
+            let [low, high] = color.to_le_bytes();
+            [low, high, low, high, 0, 0, 0, 0]
+        };
+        let data: Vec<u8> = [0xf800, 0x07e0, 0x001f, 0xffff].iter().flat_map(|&color| solid(color)).collect();
+        let pixels = decode(TextureFormat::Bc1RgbaUnorm, 6, 6, &data);
+
+        let pixel = |x: usize, y: usize| &pixels[(y * 6 + x) * 4..(y * 6 + x) * 4 + 4];
+        assert_eq!(pixels.len(), 6 * 6 * 4);
+        assert_eq!(pixel(3, 3), [255, 0, 0, 255]);
+        assert_eq!(pixel(4, 0), [0, 255, 0, 255]);
+        assert_eq!(pixel(0, 5), [0, 0, 255, 255]);
+        assert_eq!(pixel(5, 5), [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn decoded_formats_keep_srgb_and_range() {
+        assert_eq!(decoded_format(TextureFormat::Bc1RgbaUnormSrgb), TextureFormat::Rgba8UnormSrgb);
+        assert_eq!(decoded_format(TextureFormat::Bc5RgSnorm), TextureFormat::Rgba8Snorm);
+        assert_eq!(decoded_format(TextureFormat::Bc6hRgbUfloat), TextureFormat::Rgba16Float);
+        assert_eq!(decoded_format(TextureFormat::Rgba8Unorm), TextureFormat::Rgba8Unorm);
+    }
+}
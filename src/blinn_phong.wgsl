@@ -0,0 +1,40 @@
+// Appended to shader.wgsl
+
+[[stage(fragment)]]
+fn main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let albedo = textureSample(diffuse_texture, diffuse_sampler, in.tex_coord) * uniforms.diffuse;
+    // Frames without any lights are drawn unlit
+    if (lights.count == 0u) {
+        return albedo;
+    }
+
+    let normal = surface_normal(in);
+    let view_dir = normalize(uniforms.camera_position.xyz - in.world_pos);
+    let shininess = max(uniforms.specular.w, 1.0);
+
+    var color: vec3<f32> = lights.ambient.rgb * albedo.rgb;
+    var i: u32 = 0u;
+    loop {
+        if (i >= lights.count) {
+            break;
+        }
+
+        let incidence = light_incidence(i, in.world_pos);
+        let light_dir = incidence.xyz;
+        let n_dot_l = max(dot(normal, light_dir), 0.0);
+        var specular: f32 = 0.0;
+        if (n_dot_l > 0.0) {
+            let half_dir = normalize(light_dir + view_dir);
+            specular = pow(max(dot(normal, half_dir), 0.0), shininess);
+        }
+
+        let radiance = lights.lights[i].color.rgb * incidence.w;
+        color = color + radiance * (albedo.rgb * n_dot_l + uniforms.specular.rgb * specular);
+
+        continuing {
+            i = i + 1u;
+        }
+    }
+
+    return vec4<f32>(color, albedo.a);
+}
@@ -0,0 +1,26 @@
+struct VertexOutput {
+    [[location(0)]] tex_coord: vec2<f32>;
+    [[builtin(position)]] pos: vec4<f32>;
+};
+
+[[group(0), binding(0)]]
+var source_texture: texture_2d<f32>;
+[[group(0), binding(1)]]
+var source_sampler: sampler;
+
+// A single triangle covering the whole target
+[[stage(vertex)]]
+fn main([[builtin(vertex_index)]] vertex_index: u32) -> VertexOutput {
+    let x: f32 = f32(i32(vertex_index) / 2) * 4.0 - 1.0;
+    let y: f32 = f32(i32(vertex_index) % 2) * 4.0 - 1.0;
+
+    var out: VertexOutput;
+    out.tex_coord = vec2<f32>((x + 1.0) * 0.5, (1.0 - y) * 0.5);
+    out.pos = vec4<f32>(x, y, 0.0, 1.0);
+    return out;
+}
+
+[[stage(fragment)]]
+fn main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    return textureSample(source_texture, source_sampler, in.tex_coord);
+}
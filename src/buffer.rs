@@ -0,0 +1,114 @@
+// Copyright 2021 Chay Nabors.
+
+use log::info;
+use wgpu::Buffer;
+use wgpu::BufferAddress;
+use wgpu::BufferDescriptor;
+use wgpu::BufferUsage;
+use wgpu::Device;
+use wgpu::Queue;
+
+use crate::result::GearError;
+use crate::result::Result;
+
+/// Limit for buffers that are never bound whole, well below the allocation limits of common drivers.
+pub(crate) const MAX_BUFFER_SIZE: BufferAddress = 1 << 30;
+
+/// A GPU buffer that is reallocated at double its size when a frame's data doesn't fit.
+#[derive(Debug)]
+pub(crate) struct GrowableBuffer {
+    buffer: Buffer,
+    label: &'static str,
+    usage: BufferUsage,
+    size: BufferAddress,
+    limit: BufferAddress,
+}
+
+impl GrowableBuffer {
+    /// Creates a buffer that grows up to `limit` bytes, which should be the device's binding limit for buffers
+    /// that are bound whole.
+    pub fn new(
+        device: &Device,
+        label: &'static str,
+        usage: BufferUsage,
+        size: BufferAddress,
+        limit: BufferAddress,
+    ) -> GrowableBuffer {
+        let size = initial_size(size, limit);
+        let usage = usage | BufferUsage::COPY_DST;
+        GrowableBuffer { buffer: create_buffer(device, label, usage, size), label, usage, size, limit }
+    }
+
+    pub fn buffer(&self) -> &Buffer {
+        &self.buffer
+    }
+
+    /// Makes room for `size` bytes, returning whether the buffer was reallocated. The previous contents are lost.
+    pub fn reserve(&mut self, device: &Device, size: BufferAddress) -> Result<bool> {
+        if size <= self.size {
+            return Ok(false);
+        }
+
+        if size > self.limit {
+            return Err(GearError::BufferLimitExceeded { label: self.label, size, limit: self.limit });
+        }
+
+        let new_size = grown_size(self.size, size, self.limit);
+        info!("Growing {} from {} to {} bytes", self.label, self.size, new_size);
+        self.buffer = create_buffer(device, self.label, self.usage, new_size);
+        self.size = new_size;
+        Ok(true)
+    }
+
+    pub fn write(&self, queue: &Queue, data: &[u8]) {
+        if !data.is_empty() {
+            queue.write_buffer(&self.buffer, 0, data);
+        }
+    }
+}
+
+fn initial_size(size: BufferAddress, limit: BufferAddress) -> BufferAddress {
+    // Copies into buffers must be 4 byte aligned
+    ((size.max(4) + 3) & !3).min(limit)
+}
+
+fn grown_size(size: BufferAddress, required: BufferAddress, limit: BufferAddress) -> BufferAddress {
+    let mut new_size = size;
+    while new_size < required {
+        new_size *= 2;
+    }
+    new_size.min(limit)
+}
+
+fn create_buffer(device: &Device, label: &'static str, usage: BufferUsage, size: BufferAddress) -> Buffer {
+    device.create_buffer(&BufferDescriptor { label: Some(label), size, usage, mapped_at_creation: false })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn initial_size_is_aligned_and_nonzero() {
+        assert_eq!(initial_size(0, MAX_BUFFER_SIZE), 4);
+        assert_eq!(initial_size(5, MAX_BUFFER_SIZE), 8);
+        assert_eq!(initial_size(64, MAX_BUFFER_SIZE), 64);
+        assert_eq!(initial_size(MAX_BUFFER_SIZE + 1, MAX_BUFFER_SIZE), MAX_BUFFER_SIZE);
+        assert_eq!(initial_size(1 << 28, 1 << 27), 1 << 27);
+    }
+
+    #[test]
+    fn growth_doubles_until_the_data_fits() {
+        assert_eq!(grown_size(64, 65, MAX_BUFFER_SIZE), 128);
+        assert_eq!(grown_size(64, 128, MAX_BUFFER_SIZE), 128);
+        assert_eq!(grown_size(64, 1000, MAX_BUFFER_SIZE), 1024);
+    }
+
+    #[test]
+    fn growth_stops_at_the_limit() {
+        assert_eq!(grown_size(12, MAX_BUFFER_SIZE, MAX_BUFFER_SIZE), MAX_BUFFER_SIZE);
+        assert_eq!(grown_size(MAX_BUFFER_SIZE / 2 + 4, MAX_BUFFER_SIZE - 4, MAX_BUFFER_SIZE), MAX_BUFFER_SIZE);
+        // The default storage buffer binding limit
+        assert_eq!(grown_size(96 << 20, 100 << 20, 128 << 20), 128 << 20);
+    }
+}
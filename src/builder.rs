@@ -0,0 +1,202 @@
+// Copyright 2021 Chay Nabors.
+
+use std::path::Path;
+use std::path::PathBuf;
+
+use log::warn;
+use wgpu::BackendBit;
+use wgpu::PowerPreference;
+use wgpu::PresentMode;
+use winit::event_loop::EventLoop;
+
+use crate::audio::Audio;
+use crate::engine::Engine;
+use crate::gamepad::GamepadBackend;
+use crate::gamepad::GilrsBackend;
+use crate::recording::InputPlayback;
+use crate::recording::InputRecorder;
+use crate::renderer::Renderer;
+use crate::renderer::RendererConfig;
+use crate::renderer::SUPPORTED_SAMPLE_COUNTS;
+use crate::result::GearError;
+use crate::result::Result;
+use crate::window::Window;
+use crate::window::WindowConfig;
+
+#[derive(Debug)]
+pub struct EngineBuilder {
+    window_config: WindowConfig,
+    renderer_config: RendererConfig,
+    window: bool,
+    renderer: bool,
+    audio: bool,
+    gamepad_backend: Option<Box<dyn GamepadBackend>>,
+    recording_path: Option<PathBuf>,
+    playback_path: Option<PathBuf>,
+}
+
+impl Default for EngineBuilder {
+    fn default() -> Self {
+        Self {
+            window_config: WindowConfig::default(),
+            renderer_config: RendererConfig::default(),
+            window: true,
+            renderer: true,
+            audio: true,
+            gamepad_backend: None,
+            recording_path: None,
+            playback_path: None,
+        }
+    }
+}
+
+impl EngineBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Runs without a window, graphics device or audio device. Draw calls are
+    /// still recorded by the renderer and networking works as usual.
+    pub fn headless(self, headless: bool) -> Self {
+        self.with_window(!headless).with_renderer(!headless).with_audio(!headless)
+    }
+
+    pub fn with_window(mut self, enabled: bool) -> Self {
+        self.window = enabled;
+        self
+    }
+
+    /// Without a window the renderer has nothing to present to, so it falls
+    /// back to recording draw calls only.
+    pub fn with_renderer(mut self, enabled: bool) -> Self {
+        self.renderer = enabled;
+        self
+    }
+
+    pub fn with_audio(mut self, enabled: bool) -> Self {
+        self.audio = enabled;
+        self
+    }
+
+    /// Replaces the default gilrs gamepad backend, e.g. with a
+    /// `VirtualGamepadBackend` in tests.
+    pub fn with_gamepad_backend<B: 'static + GamepadBackend>(mut self, backend: B) -> Self {
+        self.gamepad_backend = Some(Box::new(backend));
+        self
+    }
+
+    /// Records every input event and frame time to `path`.
+    pub fn with_input_recording<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.recording_path = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Replays a recording made with `with_input_recording` in place of live
+    /// input, reproducing the recorded frame times as well. Live input resumes
+    /// once the recording runs out.
+    pub fn with_input_playback<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.playback_path = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    pub fn with_title(mut self, title: &str) -> Self {
+        self.window_config.title = title.to_string();
+        self
+    }
+
+    pub fn with_size(mut self, size: [u32; 2]) -> Self {
+        self.window_config.size = Some(size);
+        self
+    }
+
+    pub fn with_fullscreen(mut self, fullscreen: bool) -> Self {
+        self.window_config.fullscreen = fullscreen;
+        self
+    }
+
+    pub fn with_resizable(mut self, resizable: bool) -> Self {
+        self.window_config.resizable = resizable;
+        self
+    }
+
+    pub fn with_backends(mut self, backends: BackendBit) -> Self {
+        self.renderer_config.backends = backends;
+        self
+    }
+
+    pub fn with_power_preference(mut self, power_preference: PowerPreference) -> Self {
+        self.renderer_config.power_preference = power_preference;
+        self
+    }
+
+    pub fn with_present_mode(mut self, present_mode: PresentMode) -> Self {
+        self.renderer_config.present_mode = present_mode;
+        self
+    }
+
+    /// Sets the MSAA sample count, either 1 or 4. `build` fails with any other count.
+    pub fn with_sample_count(mut self, sample_count: u32) -> Self {
+        self.renderer_config.sample_count = sample_count;
+        self
+    }
+
+    /// Sets the initial sizes in bytes of the buffers holding each frame's vertices, indices, instance
+    /// transforms and per draw uniforms. They grow as needed, this only avoids reallocating in the first frames.
+    pub fn with_buffer_sizes(mut self, vertex: u64, index: u64, instance: u64, uniform: u64) -> Self {
+        self.renderer_config.vertex_buffer_size = vertex;
+        self.renderer_config.index_buffer_size = index;
+        self.renderer_config.instance_buffer_size = instance;
+        self.renderer_config.uniform_buffer_size = uniform;
+        self
+    }
+
+    /// Sets the width and height of each shadow map. Larger maps give sharper shadows at the cost of memory and fill
+    /// rate. Sizes past the device's texture limit are clamped to it.
+    pub fn with_shadow_map_size(mut self, size: u32) -> Self {
+        self.renderer_config.shadow_map_size = size;
+        self
+    }
+
+    pub async fn build(self) -> Result<Engine> {
+        if !SUPPORTED_SAMPLE_COUNTS.contains(&self.renderer_config.sample_count) {
+            return Err(GearError::UnsupportedSampleCount(self.renderer_config.sample_count));
+        }
+
+        let recorder = self.recording_path.map(InputRecorder::create).transpose()?;
+        let playback = self.playback_path.map(InputPlayback::open).transpose()?;
+
+        let (event_loop, window) = if self.window {
+            let event_loop = EventLoop::new();
+            let window = Window::new(&event_loop, &self.window_config)?;
+            (Some(event_loop), window)
+        } else {
+            (None, Window::headless())
+        };
+
+        let renderer = match (self.renderer, &event_loop) {
+            (true, Some(_)) => Renderer::new(&window, &self.renderer_config).await?,
+            (true, None) => {
+                warn!("Renderer requested without a window");
+                Renderer::null()
+            },
+            (false, _) => Renderer::null(),
+        };
+
+        let audio = if self.audio { Audio::new()? } else { Audio::null() };
+
+        // Missing gamepad support shouldn't keep the game from starting
+        let gamepad_backend = match self.gamepad_backend {
+            Some(backend) => Some(backend),
+            None if event_loop.is_some() => match GilrsBackend::new() {
+                Ok(backend) => Some(Box::new(backend) as Box<dyn GamepadBackend>),
+                Err(e) => {
+                    warn!("Gamepad input unavailable: {}", e);
+                    None
+                },
+            },
+            None => None,
+        };
+
+        Ok(Engine::from_subsystems(event_loop, window, renderer, audio, gamepad_backend, recorder, playback))
+    }
+}
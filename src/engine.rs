@@ -1,27 +1,57 @@
 // Copyright 2021 Chay Nabors.
 
+use std::thread::sleep;
 use std::time::Duration;
 use std::time::Instant;
 
+use log::error;
 use log::info;
+use winit::event::DeviceEvent;
 use winit::event::Event as WinitEvent;
+use winit::event::Ime;
+use winit::event::MouseScrollDelta;
+use winit::event::TouchPhase as WinitTouchPhase;
 use winit::event::WindowEvent as WinitWindowEvent;
 use winit::event_loop::ControlFlow;
 use winit::event_loop::EventLoop;
 
 use crate::audio::Audio;
+use crate::builder::EngineBuilder;
+use crate::gamepad::GamepadBackend;
+use crate::input::ImeEvent;
 use crate::input::Input;
 use crate::input::InputEvent;
+use crate::input::KeyState;
 use crate::input::MouseEvent;
+use crate::input::ScrollDelta;
+use crate::input::TouchEvent;
+use crate::input::TouchPhase;
 use crate::network::Network;
 use crate::network::NetworkEvent;
+use crate::recording::InputPlayback;
+use crate::recording::InputRecorder;
 use crate::renderer::Renderer;
+use crate::result::Result;
 use crate::window::Window;
 use crate::window::WindowEvent;
 
+const DEFAULT_FIXED_TIME_STEP: Duration = Duration::from_nanos(1_000_000_000 / 60);
+const DEFAULT_MAX_FIXED_STEPS: u32 = 8;
+
 #[derive(Clone, Debug)]
 pub enum Event {
-    UpdateEvent { delta_time: Duration },
+    /// Emitted once per frame. `alpha` is how far the simulation has progressed
+    /// towards the next fixed step, in the range `[0, 1)`, and can be used to
+    /// interpolate between the previous and current simulation states.
+    UpdateEvent {
+        delta_time: Duration,
+        alpha: f32,
+    },
+    /// Emitted zero or more times per frame, always with the same `delta_time`.
+    /// Input presses and releases are reported since the previous fixed step.
+    FixedUpdateEvent {
+        delta_time: Duration,
+    },
     TerminateEvent,
     WindowEvent(WindowEvent),
     InputEvent(InputEvent),
@@ -36,18 +66,70 @@ pub struct Engine {
     pub renderer: Renderer,
     pub audio: Audio,
     pub network: Network,
+    fixed_time_step: Duration,
+    max_fixed_steps: u32,
+    accumulator: Duration,
+    running: bool,
+    ime_allowed: bool,
+    recorder: Option<InputRecorder>,
+    playback: Option<InputPlayback>,
 }
 
 impl Engine {
-    pub async fn new() -> Engine {
-        let event_loop = EventLoop::new();
-        let window = Window::new(&event_loop);
-        let input = Input::new();
-        let renderer = Renderer::new(&window).await.unwrap();
-        let audio = Audio::new();
-        let network = Network::new();
+    pub async fn new() -> Result<Engine> {
+        EngineBuilder::new().build().await
+    }
+
+    pub fn builder() -> EngineBuilder {
+        EngineBuilder::new()
+    }
+
+    pub(crate) fn from_subsystems(
+        event_loop: Option<EventLoop<()>>,
+        window: Window,
+        renderer: Renderer,
+        audio: Audio,
+        gamepad_backend: Option<Box<dyn GamepadBackend>>,
+        recorder: Option<InputRecorder>,
+        playback: Option<InputPlayback>,
+    ) -> Engine {
+        Engine {
+            event_loop,
+            window,
+            input: Input::new(gamepad_backend),
+            renderer,
+            audio,
+            network: Network::new(),
+            fixed_time_step: DEFAULT_FIXED_TIME_STEP,
+            max_fixed_steps: DEFAULT_MAX_FIXED_STEPS,
+            accumulator: Duration::ZERO,
+            running: true,
+            ime_allowed: false,
+            recorder,
+            playback,
+        }
+    }
+
+    /// Sets the interval between `FixedUpdateEvent`s. Panics if `fixed_time_step` is zero.
+    pub fn set_fixed_time_step(&mut self, fixed_time_step: Duration) -> &mut Self {
+        assert!(fixed_time_step > Duration::ZERO, "fixed time step must be non-zero");
+        self.fixed_time_step = fixed_time_step;
+        self
+    }
+
+    /// Sets how many `FixedUpdateEvent`s may be emitted in a single frame. Any
+    /// further whole steps are dropped so a slow frame can't snowball.
+    pub fn set_max_fixed_steps(&mut self, max_fixed_steps: u32) -> &mut Self {
+        self.max_fixed_steps = max_fixed_steps;
+        self
+    }
 
-        Engine { event_loop: Some(event_loop), window, input, renderer, audio, network }
+    pub fn fixed_time_step(&self) -> Duration {
+        self.fixed_time_step
+    }
+
+    pub fn is_headless(&self) -> bool {
+        self.event_loop.is_none()
     }
 
     pub fn run<F: 'static + FnMut(&mut Engine, Event)>(mut self, mut event_handler: F) {
@@ -55,7 +137,11 @@ impl Engine {
 
         let mut prev_now = Instant::now();
 
-        let event_loop = self.event_loop.take().unwrap();
+        let event_loop = match self.event_loop.take() {
+            Some(event_loop) => event_loop,
+            None => return self.run_headless(event_handler),
+        };
+
         event_loop.run(move |event, _, control_flow| {
             *control_flow = ControlFlow::Poll;
 
@@ -72,43 +158,212 @@ impl Engine {
                             let position = [new_position.x, new_position.y];
                             event_handler(&mut self, Event::WindowEvent(WindowEvent::Moved(position)));
                         },
+                        WinitWindowEvent::ReceivedCharacter(character) if !character.is_control() => {
+                            self.dispatch_input(InputEvent::Text(character), &mut event_handler)
+                        },
+                        WinitWindowEvent::Ime(ime) => {
+                            let event = match ime {
+                                Ime::Enabled => ImeEvent::Enabled,
+                                Ime::Preedit(text, cursor) => ImeEvent::Preedit(text, cursor),
+                                Ime::Commit(text) => ImeEvent::Commit(text),
+                                Ime::Disabled => ImeEvent::Disabled,
+                            };
+                            self.dispatch_input(InputEvent::ImeEvent(event), &mut event_handler)
+                        },
                         WinitWindowEvent::KeyboardInput { input, .. } => {
-                            event_handler(&mut self, Event::InputEvent(InputEvent::KeyboardEvent(input)))
+                            self.dispatch_input(InputEvent::KeyboardEvent(input), &mut event_handler)
                         },
                         WinitWindowEvent::CursorMoved { position, .. } => {
-                            event_handler(
-                                &mut self,
-                                Event::InputEvent(InputEvent::MouseEvent(MouseEvent::CursorMoved([
-                                    position.x - size[0] as f64 / 2.,
-                                    position.y - size[1] as f64 / 2.,
-                                ]))),
+                            let position = [position.x - size[0] as f64 / 2., position.y - size[1] as f64 / 2.];
+                            self.dispatch_input(
+                                InputEvent::MouseEvent(MouseEvent::CursorMoved(position)),
+                                &mut event_handler,
                             );
                         },
+                        WinitWindowEvent::CursorEntered { .. } => {
+                            self.dispatch_input(InputEvent::MouseEvent(MouseEvent::CursorEntered), &mut event_handler)
+                        },
+                        WinitWindowEvent::CursorLeft { .. } => {
+                            self.dispatch_input(InputEvent::MouseEvent(MouseEvent::CursorLeft), &mut event_handler)
+                        },
+                        WinitWindowEvent::MouseInput { state, button, .. } => {
+                            let event = match state {
+                                KeyState::Pressed => MouseEvent::ButtonPressed(button),
+                                KeyState::Released => MouseEvent::ButtonReleased(button),
+                            };
+                            self.dispatch_input(InputEvent::MouseEvent(event), &mut event_handler);
+                        },
+                        WinitWindowEvent::MouseWheel { delta, .. } => {
+                            let delta = match delta {
+                                MouseScrollDelta::LineDelta(x, y) => ScrollDelta::Lines([x, y]),
+                                MouseScrollDelta::PixelDelta(position) => ScrollDelta::Pixels([position.x, position.y]),
+                            };
+                            self.dispatch_input(
+                                InputEvent::MouseEvent(MouseEvent::Scrolled(delta)),
+                                &mut event_handler,
+                            );
+                        },
+                        WinitWindowEvent::Touch(touch) => {
+                            let phase = match touch.phase {
+                                WinitTouchPhase::Started => TouchPhase::Started,
+                                WinitTouchPhase::Moved => TouchPhase::Moved,
+                                WinitTouchPhase::Ended => TouchPhase::Ended,
+                                WinitTouchPhase::Cancelled => TouchPhase::Cancelled,
+                            };
+                            let position =
+                                [touch.location.x - size[0] as f64 / 2., touch.location.y - size[1] as f64 / 2.];
+                            let event = InputEvent::TouchEvent(TouchEvent { id: touch.id, phase, position });
+                            self.dispatch_input(event, &mut event_handler);
+                        },
+                        WinitWindowEvent::Focused(false) => {
+                            self.dispatch_input(InputEvent::FocusLost, &mut event_handler)
+                        },
                         WinitWindowEvent::Destroyed => *control_flow = ControlFlow::Exit,
                         _ => (),
                     };
                 },
+                WinitEvent::DeviceEvent { event: DeviceEvent::MouseMotion { delta }, .. } => {
+                    let event = InputEvent::MouseEvent(MouseEvent::RawMotion([delta.0, delta.1]));
+                    self.dispatch_input(event, &mut event_handler);
+                },
                 WinitEvent::MainEventsCleared => self.window.request_redraw(),
                 WinitEvent::RedrawRequested(_) => {
-                    while let Some(event) = self.network.get_event() {
-                        event_handler(&mut self, Event::NetworkEvent(event));
-                    }
-
                     let now = Instant::now();
                     let delta_time = now - prev_now;
                     prev_now = now;
-                    event_handler(&mut self, Event::UpdateEvent { delta_time });
+                    self.update(delta_time, &mut event_handler);
                 },
                 WinitEvent::LoopDestroyed => {
                     info!("Terminating game");
                     event_handler(&mut self, Event::TerminateEvent); // terminate event
+                    self.finish_recording();
                 },
                 _ => (),
             }
         })
     }
 
+    fn run_headless<F: FnMut(&mut Engine, Event)>(mut self, mut event_handler: F) {
+        let mut prev_now = Instant::now();
+
+        while self.running {
+            let now = Instant::now();
+            let delta_time = now - prev_now;
+            prev_now = now;
+            self.update(delta_time, &mut event_handler);
+
+            // There is no vsync to pace a headless loop, so idle until the next fixed step is due.
+            // Playback uses recorded frame times, so it can run as fast as possible.
+            if self.running && self.playback.is_none() {
+                sleep(self.fixed_time_step - self.accumulator);
+            }
+        }
+
+        info!("Terminating game");
+        event_handler(&mut self, Event::TerminateEvent);
+        self.finish_recording();
+    }
+
+    /// Whether input is currently being replayed from a recording instead of
+    /// read from the window and gamepads.
+    pub fn is_replaying(&self) -> bool {
+        self.playback.is_some()
+    }
+
+    fn dispatch_input<F: FnMut(&mut Engine, Event)>(&mut self, event: InputEvent, event_handler: &mut F) {
+        // Live input is ignored while a recording is replayed in its place
+        if self.playback.is_none() {
+            self.handle_input(event, event_handler);
+        }
+    }
+
+    fn handle_input<F: FnMut(&mut Engine, Event)>(&mut self, event: InputEvent, event_handler: &mut F) {
+        if let Some(recorder) = &mut self.recorder {
+            recorder.record_event(&event);
+        }
+
+        self.input.handle_event(&event);
+        event_handler(self, Event::InputEvent(event));
+        self.dispatch_gestures(event_handler);
+    }
+
+    fn dispatch_gestures<F: FnMut(&mut Engine, Event)>(&mut self, event_handler: &mut F) {
+        while let Some(gesture) = self.input.poll_gesture() {
+            event_handler(self, Event::InputEvent(InputEvent::GestureEvent(gesture)));
+        }
+    }
+
+    fn finish_recording(&mut self) {
+        if let Some(mut recorder) = self.recorder.take() {
+            if let Err(e) = recorder.flush() {
+                error!("Failed to write input recording: {}", e);
+            }
+        }
+    }
+
+    fn update<F: FnMut(&mut Engine, Event)>(&mut self, delta_time: Duration, event_handler: &mut F) {
+        while let Some(event) = self.network.get_event() {
+            event_handler(self, Event::NetworkEvent(event));
+        }
+
+        while let Some(event) = self.input.poll_gamepad() {
+            self.dispatch_input(InputEvent::JoystickEvent(event), event_handler);
+        }
+
+        let delta_time = match self.playback.as_mut().map(|playback| playback.next_frame()) {
+            Some(Some(frame)) => {
+                for event in frame.events {
+                    self.handle_input(event, event_handler);
+                }
+                frame.delta_time
+            },
+            Some(None) => {
+                info!("Input playback finished");
+                self.playback = None;
+                delta_time
+            },
+            None => delta_time,
+        };
+
+        self.input.update_gestures(delta_time);
+        self.dispatch_gestures(event_handler);
+
+        self.accumulator += delta_time;
+        let mut steps = 0;
+        while self.accumulator >= self.fixed_time_step {
+            if steps == self.max_fixed_steps {
+                let step = self.fixed_time_step.as_nanos();
+                self.accumulator = Duration::from_nanos((self.accumulator.as_nanos() % step) as u64);
+                break;
+            }
+
+            self.accumulator -= self.fixed_time_step;
+            steps += 1;
+            self.input.begin_fixed_step();
+            event_handler(self, Event::FixedUpdateEvent { delta_time: self.fixed_time_step });
+            self.input.end_fixed_step();
+        }
+
+        let alpha = self.accumulator.as_secs_f32() / self.fixed_time_step.as_secs_f32();
+        event_handler(self, Event::UpdateEvent { delta_time, alpha });
+        self.input.end_frame();
+
+        // Input methods compose text only while a text field has focus
+        if self.input.is_text_input() != self.ime_allowed {
+            self.ime_allowed = self.input.is_text_input();
+            self.window.set_ime_allowed(self.ime_allowed);
+        }
+
+        if let Some(recorder) = &mut self.recorder {
+            if let Err(e) = recorder.end_frame(delta_time) {
+                error!("Failed to write input recording, stopping: {}", e);
+                self.recorder = None;
+            }
+        }
+    }
+
     pub fn terminate(&mut self) {
+        self.running = false;
         self.window.close();
     }
 }
@@ -0,0 +1,469 @@
+// Copyright 2021 Chay Nabors.
+
+use std::borrow::Cow;
+use std::num::NonZeroU32;
+
+use bytemuck::Pod;
+use bytemuck::Zeroable;
+use wgpu::util::BufferInitDescriptor;
+use wgpu::util::DeviceExt;
+use wgpu::AddressMode;
+use wgpu::BindGroup;
+use wgpu::BindGroupDescriptor;
+use wgpu::BindGroupEntry;
+use wgpu::BindGroupLayout;
+use wgpu::BindGroupLayoutDescriptor;
+use wgpu::BindGroupLayoutEntry;
+use wgpu::BindingResource;
+use wgpu::BindingType;
+use wgpu::Buffer;
+use wgpu::BufferBinding;
+use wgpu::BufferBindingType;
+use wgpu::BufferUsage;
+use wgpu::Color;
+use wgpu::ColorTargetState;
+use wgpu::ColorWrite;
+use wgpu::CommandEncoder;
+use wgpu::CommandEncoderDescriptor;
+use wgpu::Device;
+use wgpu::Extent3d;
+use wgpu::FilterMode;
+use wgpu::FragmentState;
+use wgpu::LoadOp;
+use wgpu::MultisampleState;
+use wgpu::Operations;
+use wgpu::PipelineLayout;
+use wgpu::PipelineLayoutDescriptor;
+use wgpu::PrimitiveState;
+use wgpu::Queue;
+use wgpu::RenderPassColorAttachment;
+use wgpu::RenderPassDescriptor;
+use wgpu::RenderPipeline;
+use wgpu::RenderPipelineDescriptor;
+use wgpu::Sampler;
+use wgpu::SamplerDescriptor;
+use wgpu::ShaderFlags;
+use wgpu::ShaderModule;
+use wgpu::ShaderModuleDescriptor;
+use wgpu::ShaderSource;
+use wgpu::ShaderStage;
+use wgpu::Texture;
+use wgpu::TextureDescriptor;
+use wgpu::TextureDimension;
+use wgpu::TextureFormat;
+use wgpu::TextureSampleType;
+use wgpu::TextureUsage;
+use wgpu::TextureView;
+use wgpu::TextureViewDescriptor;
+use wgpu::TextureViewDimension;
+use wgpu::VertexState;
+use wgpu::BIND_BUFFER_ALIGNMENT;
+
+const CUBE_FORMAT: TextureFormat = TextureFormat::Rgba16Float;
+const BRDF_LUT_FORMAT: TextureFormat = TextureFormat::Rg16Float;
+const SOURCE_SIZE: u32 = 256;
+const IRRADIANCE_SIZE: u32 = 32;
+const PREFILTERED_SIZE: u32 = 128;
+/// Mip levels of the prefiltered map, from smooth to fully rough. The PBR shader samples up to the last level.
+const PREFILTERED_MIP_COUNT: u32 = 5;
+const BRDF_LUT_SIZE: u32 = 256;
+
+/// The cube face and roughness a pass renders.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+struct FaceParams {
+    face: u32,
+    roughness: f32,
+}
+
+/// Precomputed image based lighting: diffuse irradiance and prefiltered specular cube maps convolved from an
+/// environment cube map, and the BRDF lookup table for the split sum approximation.
+#[derive(Debug)]
+pub(crate) struct EnvironmentMaps {
+    _source: Texture,
+    source_view: TextureView,
+    _irradiance: Texture,
+    irradiance_view: TextureView,
+    _prefiltered: Texture,
+    prefiltered_view: TextureView,
+    _brdf_lut: Texture,
+    brdf_lut_view: TextureView,
+    source: CubeTargets,
+    irradiance: CubeTargets,
+    prefiltered: CubeTargets,
+    sampler: Sampler,
+
+    _shader_module: ShaderModule,
+    _params_buffer: Buffer,
+    params_bind_group: BindGroup,
+    face_bind_group_layout: BindGroupLayout,
+    cube_bind_group_layout: BindGroupLayout,
+    _face_pipeline_layout: PipelineLayout,
+    _cube_pipeline_layout: PipelineLayout,
+    face_pipeline: RenderPipeline,
+    irradiance_pipeline: RenderPipeline,
+    prefilter_pipeline: RenderPipeline,
+}
+
+/// A render target view per face and mip level of a cube map.
+#[derive(Debug)]
+struct CubeTargets {
+    views: Vec<Vec<TextureView>>,
+}
+
+impl EnvironmentMaps {
+    /// Generates the BRDF lookup table and fills the cube maps with a uniform white environment, so the ambient light
+    /// color alone lights the scene until an environment is set.
+    pub fn new(device: &Device, queue: &Queue) -> EnvironmentMaps {
+        let shader_module = device.create_shader_module(&ShaderModuleDescriptor {
+            label: Some("environment_shader"),
+            source: ShaderSource::Wgsl(Cow::Borrowed(include_str!("environment.wgsl"))),
+            flags: ShaderFlags::VALIDATION,
+        });
+
+        let (source, source_view) = create_cube(device, "environment_source", SOURCE_SIZE, 1);
+        let (irradiance, irradiance_view) = create_cube(device, "environment_irradiance", IRRADIANCE_SIZE, 1);
+        let (prefiltered, prefiltered_view) =
+            create_cube(device, "environment_prefiltered", PREFILTERED_SIZE, PREFILTERED_MIP_COUNT);
+
+        let brdf_lut = device.create_texture(&TextureDescriptor {
+            label: Some("brdf_lut"),
+            size: Extent3d { width: BRDF_LUT_SIZE, height: BRDF_LUT_SIZE, depth_or_array_layers: 1 },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: BRDF_LUT_FORMAT,
+            usage: TextureUsage::RENDER_ATTACHMENT | TextureUsage::SAMPLED,
+        });
+        let brdf_lut_view = brdf_lut.create_view(&TextureViewDescriptor::default());
+
+        let sampler = device.create_sampler(&SamplerDescriptor {
+            label: Some("environment_sampler"),
+            address_mode_u: AddressMode::ClampToEdge,
+            address_mode_v: AddressMode::ClampToEdge,
+            address_mode_w: AddressMode::ClampToEdge,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            mipmap_filter: FilterMode::Linear,
+            ..Default::default()
+        });
+
+        // Every pass reads its face and roughness at its own dynamic offset, one binding alignment apart
+        let stride = BIND_BUFFER_ALIGNMENT as usize;
+        let mut params = vec![0u8; PREFILTERED_MIP_COUNT as usize * 6 * stride];
+        for level in 0..PREFILTERED_MIP_COUNT {
+            let roughness = level as f32 / (PREFILTERED_MIP_COUNT - 1) as f32;
+            for face in 0..6 {
+                let offset = (level * 6 + face) as usize * stride;
+                let face_params = FaceParams { face, roughness };
+                params[offset..offset + std::mem::size_of::<FaceParams>()]
+                    .copy_from_slice(bytemuck::bytes_of(&face_params));
+            }
+        }
+        let params_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("environment_params_buffer"),
+            contents: &params,
+            usage: BufferUsage::UNIFORM,
+        });
+
+        let params_bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("environment_params_bind_group_layout"),
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStage::FRAGMENT,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: true,
+                    min_binding_size: wgpu::BufferSize::new(std::mem::size_of::<FaceParams>() as _),
+                },
+                count: None,
+            }],
+        });
+
+        let params_bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("environment_params_bind_group"),
+            layout: &params_bind_group_layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: BindingResource::Buffer(BufferBinding {
+                    buffer: &params_buffer,
+                    offset: 0,
+                    size: wgpu::BufferSize::new(std::mem::size_of::<FaceParams>() as _),
+                }),
+            }],
+        });
+
+        // Faces are 2D textures while the convolutions read a cube map, so they use separate bindings
+        let face_bind_group_layout = create_source_bind_group_layout(device, 0, TextureViewDimension::D2);
+        let cube_bind_group_layout = create_source_bind_group_layout(device, 1, TextureViewDimension::Cube);
+
+        let face_pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("environment_face_pipeline_layout"),
+            bind_group_layouts: &[&params_bind_group_layout, &face_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let cube_pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("environment_cube_pipeline_layout"),
+            bind_group_layouts: &[&params_bind_group_layout, &cube_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let brdf_pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("brdf_lut_pipeline_layout"),
+            bind_group_layouts: &[],
+            push_constant_ranges: &[],
+        });
+
+        let face_pipeline = create_pipeline(device, &face_pipeline_layout, &shader_module, "copy_face", CUBE_FORMAT);
+        let irradiance_pipeline =
+            create_pipeline(device, &cube_pipeline_layout, &shader_module, "irradiance", CUBE_FORMAT);
+        let prefilter_pipeline =
+            create_pipeline(device, &cube_pipeline_layout, &shader_module, "prefilter", CUBE_FORMAT);
+        let brdf_pipeline = create_pipeline(device, &brdf_pipeline_layout, &shader_module, "brdf_lut", BRDF_LUT_FORMAT);
+
+        let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor { label: Some("brdf_lut_encoder") });
+        {
+            let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+                label: Some("brdf_lut_pass"),
+                color_attachments: &[RenderPassColorAttachment {
+                    view: &brdf_lut_view,
+                    resolve_target: None,
+                    ops: Operations { load: LoadOp::Clear(Color::BLACK), store: true },
+                }],
+                depth_stencil_attachment: None,
+            });
+            render_pass.set_pipeline(&brdf_pipeline);
+            render_pass.draw(0..3, 0..1);
+        }
+        queue.submit(Some(encoder.finish()));
+
+        let environment_maps = EnvironmentMaps {
+            source: CubeTargets::new(&source, 1),
+            irradiance: CubeTargets::new(&irradiance, 1),
+            prefiltered: CubeTargets::new(&prefiltered, PREFILTERED_MIP_COUNT),
+            _source: source,
+            source_view,
+            _irradiance: irradiance,
+            irradiance_view,
+            _prefiltered: prefiltered,
+            prefiltered_view,
+            _brdf_lut: brdf_lut,
+            brdf_lut_view,
+            sampler,
+
+            _shader_module: shader_module,
+            _params_buffer: params_buffer,
+            params_bind_group,
+            face_bind_group_layout,
+            cube_bind_group_layout,
+            _face_pipeline_layout: face_pipeline_layout,
+            _cube_pipeline_layout: cube_pipeline_layout,
+            face_pipeline,
+            irradiance_pipeline,
+            prefilter_pipeline,
+        };
+
+        let mut encoder =
+            device.create_command_encoder(&CommandEncoderDescriptor { label: Some("environment_encoder") });
+        for targets in &environment_maps.source.views {
+            for view in targets {
+                clear(&mut encoder, view, Color::WHITE);
+            }
+        }
+        environment_maps.convolve(device, &mut encoder);
+        queue.submit(Some(encoder.finish()));
+
+        environment_maps
+    }
+
+    /// Renders the six faces, ordered +X, -X, +Y, -Y, +Z, -Z, into the environment and convolves it.
+    pub fn set_faces(&self, device: &Device, queue: &Queue, faces: &[&TextureView]) {
+        let mut encoder =
+            device.create_command_encoder(&CommandEncoderDescriptor { label: Some("environment_encoder") });
+
+        for (face, &view) in faces.iter().enumerate().take(6) {
+            let bind_group = device.create_bind_group(&BindGroupDescriptor {
+                label: Some("environment_face_bind_group"),
+                layout: &self.face_bind_group_layout,
+                entries: &[
+                    BindGroupEntry { binding: 0, resource: BindingResource::TextureView(view) },
+                    BindGroupEntry { binding: 2, resource: BindingResource::Sampler(&self.sampler) },
+                ],
+            });
+            self.draw_face(&mut encoder, &self.face_pipeline, &bind_group, &self.source.views[0][face], face, 0);
+        }
+
+        self.convolve(device, &mut encoder);
+        queue.submit(Some(encoder.finish()));
+    }
+
+    fn convolve(&self, device: &Device, encoder: &mut CommandEncoder) {
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("environment_cube_bind_group"),
+            layout: &self.cube_bind_group_layout,
+            entries: &[
+                BindGroupEntry { binding: 1, resource: BindingResource::TextureView(&self.source_view) },
+                BindGroupEntry { binding: 2, resource: BindingResource::Sampler(&self.sampler) },
+            ],
+        });
+
+        for face in 0..6 {
+            let target = &self.irradiance.views[0][face];
+            self.draw_face(encoder, &self.irradiance_pipeline, &bind_group, target, face, 0);
+        }
+
+        for (level, targets) in self.prefiltered.views.iter().enumerate() {
+            for (face, target) in targets.iter().enumerate() {
+                self.draw_face(encoder, &self.prefilter_pipeline, &bind_group, target, face, level);
+            }
+        }
+    }
+
+    fn draw_face(
+        &self,
+        encoder: &mut CommandEncoder,
+        pipeline: &RenderPipeline,
+        bind_group: &BindGroup,
+        target: &TextureView,
+        face: usize,
+        level: usize,
+    ) {
+        let offset = ((level * 6 + face) as u64 * BIND_BUFFER_ALIGNMENT) as wgpu::DynamicOffset;
+        let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+            label: Some("environment_pass"),
+            color_attachments: &[RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: Operations { load: LoadOp::Clear(Color::BLACK), store: true },
+            }],
+            depth_stencil_attachment: None,
+        });
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_bind_group(0, &self.params_bind_group, &[offset]);
+        render_pass.set_bind_group(1, bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+
+    pub fn irradiance_view(&self) -> &TextureView {
+        &self.irradiance_view
+    }
+
+    pub fn prefiltered_view(&self) -> &TextureView {
+        &self.prefiltered_view
+    }
+
+    pub fn brdf_lut_view(&self) -> &TextureView {
+        &self.brdf_lut_view
+    }
+
+    pub fn sampler(&self) -> &Sampler {
+        &self.sampler
+    }
+}
+
+impl CubeTargets {
+    fn new(texture: &Texture, mip_level_count: u32) -> CubeTargets {
+        let views = (0..mip_level_count)
+            .map(|level| {
+                (0..6)
+                    .map(|face| {
+                        texture.create_view(&TextureViewDescriptor {
+                            label: Some("cube_face_view"),
+                            dimension: Some(TextureViewDimension::D2),
+                            base_mip_level: level,
+                            mip_level_count: NonZeroU32::new(1),
+                            base_array_layer: face,
+                            array_layer_count: NonZeroU32::new(1),
+                            ..Default::default()
+                        })
+                    })
+                    .collect()
+            })
+            .collect();
+
+        CubeTargets { views }
+    }
+}
+
+fn create_cube(device: &Device, label: &str, size: u32, mip_level_count: u32) -> (Texture, TextureView) {
+    let texture = device.create_texture(&TextureDescriptor {
+        label: Some(label),
+        size: Extent3d { width: size, height: size, depth_or_array_layers: 6 },
+        mip_level_count,
+        sample_count: 1,
+        dimension: TextureDimension::D2,
+        format: CUBE_FORMAT,
+        usage: TextureUsage::RENDER_ATTACHMENT | TextureUsage::SAMPLED,
+    });
+
+    let view = texture.create_view(&TextureViewDescriptor {
+        label: Some(label),
+        dimension: Some(TextureViewDimension::Cube),
+        ..Default::default()
+    });
+
+    (texture, view)
+}
+
+fn create_source_bind_group_layout(
+    device: &Device,
+    texture_binding: u32,
+    view_dimension: TextureViewDimension,
+) -> BindGroupLayout {
+    device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+        label: Some("environment_source_bind_group_layout"),
+        entries: &[
+            BindGroupLayoutEntry {
+                binding: texture_binding,
+                visibility: ShaderStage::FRAGMENT,
+                ty: BindingType::Texture {
+                    sample_type: TextureSampleType::Float { filterable: true },
+                    view_dimension,
+                    multisampled: false,
+                },
+                count: None,
+            },
+            BindGroupLayoutEntry {
+                binding: 2,
+                visibility: ShaderStage::FRAGMENT,
+                ty: BindingType::Sampler { filtering: true, comparison: false },
+                count: None,
+            },
+        ],
+    })
+}
+
+fn create_pipeline(
+    device: &Device,
+    layout: &PipelineLayout,
+    module: &ShaderModule,
+    entry_point: &str,
+    format: TextureFormat,
+) -> RenderPipeline {
+    device.create_render_pipeline(&RenderPipelineDescriptor {
+        label: Some(entry_point),
+        layout: Some(layout),
+        vertex: VertexState { module, entry_point: "main", buffers: &[] },
+        primitive: PrimitiveState::default(),
+        depth_stencil: None,
+        multisample: MultisampleState::default(),
+        fragment: Some(FragmentState {
+            module,
+            entry_point,
+            targets: &[ColorTargetState { format, blend: None, write_mask: ColorWrite::ALL }],
+        }),
+    })
+}
+
+fn clear(encoder: &mut CommandEncoder, view: &TextureView, color: Color) {
+    encoder.begin_render_pass(&RenderPassDescriptor {
+        label: Some("environment_clear_pass"),
+        color_attachments: &[RenderPassColorAttachment {
+            view,
+            resolve_target: None,
+            ops: Operations { load: LoadOp::Clear(color), store: true },
+        }],
+        depth_stencil_attachment: None,
+    });
+}
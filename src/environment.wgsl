@@ -0,0 +1,210 @@
+struct VertexOutput {
+    [[location(0)]] tex_coord: vec2<f32>;
+    [[builtin(position)]] pos: vec4<f32>;
+};
+
+[[block]]
+struct FaceParams {
+    face: u32;
+    roughness: f32;
+};
+
+[[group(0), binding(0)]]
+var<uniform> params: FaceParams;
+
+[[group(1), binding(0)]]
+var face_texture: texture_2d<f32>;
+[[group(1), binding(1)]]
+var source_texture: texture_cube<f32>;
+[[group(1), binding(2)]]
+var source_sampler: sampler;
+
+// A single triangle covering the whole target
+[[stage(vertex)]]
+fn main([[builtin(vertex_index)]] vertex_index: u32) -> VertexOutput {
+    let x: f32 = f32(i32(vertex_index) / 2) * 4.0 - 1.0;
+    let y: f32 = f32(i32(vertex_index) % 2) * 4.0 - 1.0;
+
+    var out: VertexOutput;
+    out.tex_coord = vec2<f32>((x + 1.0) * 0.5, (1.0 - y) * 0.5);
+    out.pos = vec4<f32>(x, y, 0.0, 1.0);
+    return out;
+}
+
+// Direction through a texel of the face being rendered, with faces ordered +X, -X, +Y, -Y, +Z, -Z
+fn cube_direction(tex_coord: vec2<f32>) -> vec3<f32> {
+    let s = tex_coord.x * 2.0 - 1.0;
+    let t = tex_coord.y * 2.0 - 1.0;
+    if (params.face == 0u) {
+        return normalize(vec3<f32>(1.0, -t, -s));
+    }
+    if (params.face == 1u) {
+        return normalize(vec3<f32>(-1.0, -t, s));
+    }
+    if (params.face == 2u) {
+        return normalize(vec3<f32>(s, 1.0, t));
+    }
+    if (params.face == 3u) {
+        return normalize(vec3<f32>(s, -1.0, -t));
+    }
+    if (params.face == 4u) {
+        return normalize(vec3<f32>(s, -t, 1.0));
+    }
+    return normalize(vec3<f32>(-s, -t, -1.0));
+}
+
+fn tangent_to_world(v: vec3<f32>, normal: vec3<f32>) -> vec3<f32> {
+    var up: vec3<f32> = vec3<f32>(0.0, 1.0, 0.0);
+    if (abs(normal.y) > 0.999) {
+        up = vec3<f32>(1.0, 0.0, 0.0);
+    }
+
+    let tangent = normalize(cross(up, normal));
+    let bitangent = cross(normal, tangent);
+    return tangent * v.x + bitangent * v.y + normal * v.z;
+}
+
+// Van der Corput sequence in base 2
+fn radical_inverse(index: u32) -> f32 {
+    var n: u32 = index;
+    var scale: f32 = 0.5;
+    var result: f32 = 0.0;
+    loop {
+        if (n == 0u) {
+            break;
+        }
+
+        if (n % 2u == 1u) {
+            result = result + scale;
+        }
+        n = n / 2u;
+        scale = scale * 0.5;
+    }
+
+    return result;
+}
+
+// Half vector distributed around the normal by the GGX distribution
+fn importance_sample_ggx(xi: vec2<f32>, normal: vec3<f32>, roughness: f32) -> vec3<f32> {
+    let a = roughness * roughness;
+    let phi = 6.2831853 * xi.x;
+    let cos_theta = sqrt((1.0 - xi.y) / (1.0 + (a * a - 1.0) * xi.y));
+    let sin_theta = sqrt(1.0 - cos_theta * cos_theta);
+    return normalize(tangent_to_world(vec3<f32>(cos(phi) * sin_theta, sin(phi) * sin_theta, cos_theta), normal));
+}
+
+fn geometry_schlick_ggx(n_dot: f32, roughness: f32) -> f32 {
+    let k = roughness * roughness / 2.0;
+    return n_dot / (n_dot * (1.0 - k) + k);
+}
+
+[[stage(fragment)]]
+fn copy_face(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    return vec4<f32>(textureSample(face_texture, source_sampler, in.tex_coord).rgb, 1.0);
+}
+
+// Cosine weighted average of the environment over the hemisphere around each direction
+[[stage(fragment)]]
+fn irradiance(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let normal = cube_direction(in.tex_coord);
+
+    var sum: vec3<f32> = vec3<f32>(0.0, 0.0, 0.0);
+    var count: f32 = 0.0;
+    var phi: f32 = 0.0;
+    loop {
+        if (phi >= 6.2831853) {
+            break;
+        }
+
+        var theta: f32 = 0.0;
+        loop {
+            if (theta >= 1.5707963) {
+                break;
+            }
+
+            let dir = vec3<f32>(sin(theta) * cos(phi), sin(theta) * sin(phi), cos(theta));
+            let radiance = textureSampleLevel(source_texture, source_sampler, tangent_to_world(dir, normal), 0.0).rgb;
+            sum = sum + radiance * cos(theta) * sin(theta);
+            count = count + 1.0;
+
+            continuing {
+                theta = theta + 0.05;
+            }
+        }
+
+        continuing {
+            phi = phi + 0.05;
+        }
+    }
+
+    return vec4<f32>(sum * 3.14159265 / count, 1.0);
+}
+
+// The environment convolved with the GGX lobe of this mip level's roughness, viewed along the normal
+[[stage(fragment)]]
+fn prefilter(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let normal = cube_direction(in.tex_coord);
+
+    var sum: vec3<f32> = vec3<f32>(0.0, 0.0, 0.0);
+    var weight: f32 = 0.0;
+    var i: u32 = 0u;
+    loop {
+        if (i >= 256u) {
+            break;
+        }
+
+        let xi = vec2<f32>(f32(i) / 256.0, radical_inverse(i));
+        let half_dir = importance_sample_ggx(xi, normal, params.roughness);
+        let light_dir = normalize(2.0 * dot(normal, half_dir) * half_dir - normal);
+        let n_dot_l = dot(normal, light_dir);
+        if (n_dot_l > 0.0) {
+            sum = sum + textureSampleLevel(source_texture, source_sampler, light_dir, 0.0).rgb * n_dot_l;
+            weight = weight + n_dot_l;
+        }
+
+        continuing {
+            i = i + 1u;
+        }
+    }
+
+    return vec4<f32>(sum / max(weight, 0.0001), 1.0);
+}
+
+// Scale and bias applied to F0 by the specular BRDF, by the cosine between normal and view along u and the
+// roughness along v
+[[stage(fragment)]]
+fn brdf_lut(in: VertexOutput) -> [[location(0)]] vec2<f32> {
+    let n_dot_v = max(in.tex_coord.x, 0.0001);
+    let roughness = in.tex_coord.y;
+    let view_dir = vec3<f32>(sqrt(1.0 - n_dot_v * n_dot_v), 0.0, n_dot_v);
+    let normal = vec3<f32>(0.0, 0.0, 1.0);
+
+    var scale: f32 = 0.0;
+    var bias: f32 = 0.0;
+    var i: u32 = 0u;
+    loop {
+        if (i >= 256u) {
+            break;
+        }
+
+        let xi = vec2<f32>(f32(i) / 256.0, radical_inverse(i));
+        let half_dir = importance_sample_ggx(xi, normal, roughness);
+        let light_dir = normalize(2.0 * dot(view_dir, half_dir) * half_dir - view_dir);
+        let n_dot_l = light_dir.z;
+        if (n_dot_l > 0.0) {
+            let n_dot_h = max(half_dir.z, 0.0);
+            let v_dot_h = max(dot(view_dir, half_dir), 0.0);
+            let geometry = geometry_schlick_ggx(n_dot_v, roughness) * geometry_schlick_ggx(n_dot_l, roughness);
+            let visibility = geometry * v_dot_h / (n_dot_h * n_dot_v + 0.0001);
+            let fresnel = pow(1.0 - v_dot_h, 5.0);
+            scale = scale + (1.0 - fresnel) * visibility;
+            bias = bias + fresnel * visibility;
+        }
+
+        continuing {
+            i = i + 1u;
+        }
+    }
+
+    return vec2<f32>(scale, bias) / 256.0;
+}
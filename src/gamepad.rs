@@ -0,0 +1,248 @@
+// Copyright 2021 Chay Nabors.
+
+use std::collections::VecDeque;
+use std::fmt::Debug;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::input::JoystickEvent;
+use crate::result::GearError;
+use crate::result::Result;
+
+/// Identifies a gamepad for as long as the backend can recognize it, including
+/// across disconnects and reconnects.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct GamepadId(pub usize);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum GamepadButton {
+    South,
+    East,
+    North,
+    West,
+    LeftBumper,
+    RightBumper,
+    LeftTrigger,
+    RightTrigger,
+    Select,
+    Start,
+    Mode,
+    LeftThumb,
+    RightThumb,
+    DPadUp,
+    DPadDown,
+    DPadLeft,
+    DPadRight,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum GamepadAxis {
+    LeftStickX,
+    LeftStickY,
+    RightStickX,
+    RightStickY,
+    LeftTrigger,
+    RightTrigger,
+}
+
+/// A source of raw gamepad events. Axis values are reported unfiltered;
+/// deadzones are applied by `Input`.
+pub trait GamepadBackend: Debug {
+    fn poll_event(&mut self) -> Option<JoystickEvent>;
+}
+
+#[derive(Debug)]
+pub struct GilrsBackend {
+    gilrs: gilrs::Gilrs,
+}
+
+impl GilrsBackend {
+    pub fn new() -> Result<Self> {
+        match gilrs::Gilrs::new() {
+            Ok(gilrs) => Ok(Self { gilrs }),
+            Err(gilrs::Error::NotImplemented(gilrs)) => Ok(Self { gilrs }),
+            Err(gilrs::Error::Other(e)) => Err(GearError::GamepadBackendUnavailable(e)),
+            Err(e) => Err(GearError::GamepadBackendUnavailable(e.to_string().into())),
+        }
+    }
+}
+
+impl GamepadBackend for GilrsBackend {
+    fn poll_event(&mut self) -> Option<JoystickEvent> {
+        while let Some(gilrs::Event { id, event, .. }) = self.gilrs.next_event() {
+            let id = GamepadId(id.into());
+            let event = match event {
+                gilrs::EventType::Connected => JoystickEvent::Connected(id),
+                gilrs::EventType::Disconnected => JoystickEvent::Disconnected(id),
+                gilrs::EventType::ButtonPressed(button, _) => match map_button(button) {
+                    Some(button) => JoystickEvent::ButtonPressed(id, button),
+                    None => continue,
+                },
+                gilrs::EventType::ButtonReleased(button, _) => match map_button(button) {
+                    Some(button) => JoystickEvent::ButtonReleased(id, button),
+                    None => continue,
+                },
+                // Analog triggers are reported as buttons with a value
+                gilrs::EventType::ButtonChanged(gilrs::Button::LeftTrigger2, value, _) => {
+                    JoystickEvent::AxisMoved(id, GamepadAxis::LeftTrigger, value)
+                },
+                gilrs::EventType::ButtonChanged(gilrs::Button::RightTrigger2, value, _) => {
+                    JoystickEvent::AxisMoved(id, GamepadAxis::RightTrigger, value)
+                },
+                gilrs::EventType::AxisChanged(axis, value, _) => match map_axis(axis) {
+                    Some(axis) => JoystickEvent::AxisMoved(id, axis, value),
+                    None => continue,
+                },
+                _ => continue,
+            };
+
+            return Some(event);
+        }
+
+        None
+    }
+}
+
+fn map_button(button: gilrs::Button) -> Option<GamepadButton> {
+    Some(match button {
+        gilrs::Button::South => GamepadButton::South,
+        gilrs::Button::East => GamepadButton::East,
+        gilrs::Button::North => GamepadButton::North,
+        gilrs::Button::West => GamepadButton::West,
+        gilrs::Button::LeftTrigger => GamepadButton::LeftBumper,
+        gilrs::Button::RightTrigger => GamepadButton::RightBumper,
+        gilrs::Button::LeftTrigger2 => GamepadButton::LeftTrigger,
+        gilrs::Button::RightTrigger2 => GamepadButton::RightTrigger,
+        gilrs::Button::Select => GamepadButton::Select,
+        gilrs::Button::Start => GamepadButton::Start,
+        gilrs::Button::Mode => GamepadButton::Mode,
+        gilrs::Button::LeftThumb => GamepadButton::LeftThumb,
+        gilrs::Button::RightThumb => GamepadButton::RightThumb,
+        gilrs::Button::DPadUp => GamepadButton::DPadUp,
+        gilrs::Button::DPadDown => GamepadButton::DPadDown,
+        gilrs::Button::DPadLeft => GamepadButton::DPadLeft,
+        gilrs::Button::DPadRight => GamepadButton::DPadRight,
+        _ => return None,
+    })
+}
+
+fn map_axis(axis: gilrs::Axis) -> Option<GamepadAxis> {
+    Some(match axis {
+        gilrs::Axis::LeftStickX => GamepadAxis::LeftStickX,
+        gilrs::Axis::LeftStickY => GamepadAxis::LeftStickY,
+        gilrs::Axis::RightStickX => GamepadAxis::RightStickX,
+        gilrs::Axis::RightStickY => GamepadAxis::RightStickY,
+        _ => return None,
+    })
+}
+
+/// A backend fed by `VirtualGamepad` handles instead of hardware, for tests
+/// and for machines with no controllers attached.
+#[derive(Debug, Default)]
+pub struct VirtualGamepadBackend {
+    events: Arc<Mutex<VecDeque<JoystickEvent>>>,
+    next_id: usize,
+}
+
+impl VirtualGamepadBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn create_gamepad(&mut self) -> VirtualGamepad {
+        let id = GamepadId(self.next_id);
+        self.next_id += 1;
+        VirtualGamepad { id, events: self.events.clone() }
+    }
+}
+
+impl GamepadBackend for VirtualGamepadBackend {
+    fn poll_event(&mut self) -> Option<JoystickEvent> {
+        self.events.lock().unwrap().pop_front()
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct VirtualGamepad {
+    id: GamepadId,
+    events: Arc<Mutex<VecDeque<JoystickEvent>>>,
+}
+
+impl VirtualGamepad {
+    pub fn id(&self) -> GamepadId {
+        self.id
+    }
+
+    pub fn connect(&self) -> &Self {
+        self.push(JoystickEvent::Connected(self.id))
+    }
+
+    pub fn disconnect(&self) -> &Self {
+        self.push(JoystickEvent::Disconnected(self.id))
+    }
+
+    pub fn press(&self, button: GamepadButton) -> &Self {
+        self.push(JoystickEvent::ButtonPressed(self.id, button))
+    }
+
+    pub fn release(&self, button: GamepadButton) -> &Self {
+        self.push(JoystickEvent::ButtonReleased(self.id, button))
+    }
+
+    pub fn move_axis(&self, axis: GamepadAxis, value: f32) -> &Self {
+        self.push(JoystickEvent::AxisMoved(self.id, axis, value))
+    }
+
+    fn push(&self, event: JoystickEvent) -> &Self {
+        self.events.lock().unwrap().push_back(event);
+        self
+    }
+}
+
+/// Maps values inside `deadzone` to zero and rescales the rest so the output
+/// still covers the full range.
+pub(crate) fn apply_deadzone(value: f32, deadzone: f32) -> f32 {
+    if value.abs() <= deadzone {
+        0.
+    } else {
+        value.signum() * ((value.abs() - deadzone) / (1. - deadzone)).min(1.)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deadzone_zeroes_small_values() {
+        assert_eq!(apply_deadzone(0.1, 0.2), 0.);
+        assert_eq!(apply_deadzone(-0.2, 0.2), 0.);
+    }
+
+    #[test]
+    fn deadzone_rescales_to_full_range() {
+        assert!((apply_deadzone(0.6, 0.2) - 0.5).abs() < 1e-6);
+        assert!((apply_deadzone(-0.6, 0.2) + 0.5).abs() < 1e-6);
+        assert_eq!(apply_deadzone(1., 0.2), 1.);
+        assert_eq!(apply_deadzone(-1.5, 0.2), -1.);
+    }
+
+    #[test]
+    fn virtual_gamepad_events_arrive_in_order() {
+        let mut backend = VirtualGamepadBackend::new();
+        let gamepad = backend.create_gamepad();
+        gamepad.connect().press(GamepadButton::South).move_axis(GamepadAxis::LeftStickX, 0.5);
+
+        let id = gamepad.id();
+        assert!(matches!(backend.poll_event(), Some(JoystickEvent::Connected(i)) if i == id));
+        assert!(matches!(backend.poll_event(), Some(JoystickEvent::ButtonPressed(i, GamepadButton::South)) if i == id));
+        assert!(matches!(
+            backend.poll_event(),
+            Some(JoystickEvent::AxisMoved(i, GamepadAxis::LeftStickX, value)) if i == id && value == 0.5
+        ));
+        assert!(backend.poll_event().is_none());
+    }
+}
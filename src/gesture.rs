@@ -0,0 +1,267 @@
+// Copyright 2021 Chay Nabors.
+
+use std::collections::BTreeMap;
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::input::TouchEvent;
+use crate::input::TouchPhase;
+
+const TAP_MAX_DURATION: Duration = Duration::from_millis(300);
+const LONG_PRESS_DURATION: Duration = Duration::from_millis(500);
+const TOUCH_SLOP: f64 = 10.;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Gesture {
+    Tap {
+        position: [f64; 2],
+    },
+    LongPress {
+        position: [f64; 2],
+    },
+    /// A single finger dragged across the screen.
+    Pan {
+        position: [f64; 2],
+        delta: [f64; 2],
+    },
+    /// Two fingers moved apart or together. `scale` is relative to the
+    /// previous pinch event, so values above 1 zoom in.
+    Pinch {
+        center: [f64; 2],
+        scale: f64,
+    },
+    /// Two fingers twisted around their center, by `angle` radians since the
+    /// previous rotate event. Positive angles are clockwise on screen.
+    Rotate {
+        center: [f64; 2],
+        angle: f64,
+    },
+}
+
+#[derive(Debug)]
+struct TrackedTouch {
+    start: [f64; 2],
+    position: [f64; 2],
+    held: Duration,
+    moved: bool,
+    long_pressed: bool,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct GestureRecognizer {
+    touches: BTreeMap<u64, TrackedTouch>,
+    // Whether the current touch sequence has involved more than one finger,
+    // which rules out taps and long presses until every finger is lifted
+    multi_touch: bool,
+    gestures: VecDeque<Gesture>,
+}
+
+impl GestureRecognizer {
+    pub fn handle_touch(&mut self, touch: &TouchEvent) {
+        match touch.phase {
+            TouchPhase::Started => {
+                self.touches.insert(
+                    touch.id,
+                    TrackedTouch {
+                        start: touch.position,
+                        position: touch.position,
+                        held: Duration::ZERO,
+                        moved: false,
+                        long_pressed: false,
+                    },
+                );
+                if self.touches.len() > 1 {
+                    self.multi_touch = true;
+                }
+            },
+            TouchPhase::Moved => self.handle_move(touch),
+            TouchPhase::Ended => {
+                if let Some(tracked) = self.touches.remove(&touch.id) {
+                    if !self.multi_touch && !tracked.moved && !tracked.long_pressed && tracked.held <= TAP_MAX_DURATION
+                    {
+                        self.gestures.push_back(Gesture::Tap { position: touch.position });
+                    }
+                }
+                self.reset_if_released();
+            },
+            TouchPhase::Cancelled => {
+                self.touches.remove(&touch.id);
+                self.reset_if_released();
+            },
+        }
+    }
+
+    /// Advances the hold timers, which is how long presses are detected.
+    pub fn update(&mut self, delta_time: Duration) {
+        let single_touch = !self.multi_touch;
+        for tracked in self.touches.values_mut() {
+            tracked.held += delta_time;
+            if single_touch && !tracked.moved && !tracked.long_pressed && tracked.held >= LONG_PRESS_DURATION {
+                tracked.long_pressed = true;
+                self.gestures.push_back(Gesture::LongPress { position: tracked.position });
+            }
+        }
+    }
+
+    pub fn poll_gesture(&mut self) -> Option<Gesture> {
+        self.gestures.pop_front()
+    }
+
+    pub fn touches(&self) -> impl Iterator<Item = (u64, [f64; 2])> + '_ {
+        self.touches.iter().map(|(id, tracked)| (*id, tracked.position))
+    }
+
+    fn handle_move(&mut self, touch: &TouchEvent) {
+        // Remember the two-finger span before this finger moves
+        let previous_pair = self.pair();
+
+        let tracked = match self.touches.get_mut(&touch.id) {
+            Some(tracked) => tracked,
+            None => return,
+        };
+
+        let previous = tracked.position;
+        tracked.position = touch.position;
+        if !tracked.moved && distance(tracked.start, touch.position) > TOUCH_SLOP {
+            tracked.moved = true;
+        }
+
+        if self.touches.len() == 1 {
+            let tracked = &self.touches[&touch.id];
+            if tracked.moved && !tracked.long_pressed {
+                let delta = [touch.position[0] - previous[0], touch.position[1] - previous[1]];
+                self.gestures.push_back(Gesture::Pan { position: touch.position, delta });
+            }
+        } else if let (Some((a0, b0)), Some((a1, b1))) = (previous_pair, self.pair()) {
+            let center = [(a1[0] + b1[0]) / 2., (a1[1] + b1[1]) / 2.];
+
+            let (previous_distance, current_distance) = (distance(a0, b0), distance(a1, b1));
+            if previous_distance > 0. && current_distance != previous_distance {
+                self.gestures.push_back(Gesture::Pinch { center, scale: current_distance / previous_distance });
+            }
+
+            let mut angle = angle(a1, b1) - angle(a0, b0);
+            if angle > std::f64::consts::PI {
+                angle -= 2. * std::f64::consts::PI;
+            } else if angle < -std::f64::consts::PI {
+                angle += 2. * std::f64::consts::PI;
+            }
+            if angle != 0. {
+                self.gestures.push_back(Gesture::Rotate { center, angle });
+            }
+        }
+    }
+
+    // The two fingers with the lowest ids, which drive pinch and rotate
+    fn pair(&self) -> Option<([f64; 2], [f64; 2])> {
+        let mut touches = self.touches.values();
+        match (touches.next(), touches.next()) {
+            (Some(a), Some(b)) => Some((a.position, b.position)),
+            _ => None,
+        }
+    }
+
+    fn reset_if_released(&mut self) {
+        if self.touches.is_empty() {
+            self.multi_touch = false;
+        }
+    }
+}
+
+fn distance(a: [f64; 2], b: [f64; 2]) -> f64 {
+    ((b[0] - a[0]).powi(2) + (b[1] - a[1]).powi(2)).sqrt()
+}
+
+fn angle(a: [f64; 2], b: [f64; 2]) -> f64 {
+    (b[1] - a[1]).atan2(b[0] - a[0])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn touch(recognizer: &mut GestureRecognizer, id: u64, phase: TouchPhase, position: [f64; 2]) {
+        recognizer.handle_touch(&TouchEvent { id, phase, position });
+    }
+
+    fn gestures(recognizer: &mut GestureRecognizer) -> Vec<Gesture> {
+        std::iter::from_fn(|| recognizer.poll_gesture()).collect()
+    }
+
+    #[test]
+    fn quick_release_is_a_tap() {
+        let mut recognizer = GestureRecognizer::default();
+        touch(&mut recognizer, 0, TouchPhase::Started, [5., 5.]);
+        recognizer.update(Duration::from_millis(100));
+        touch(&mut recognizer, 0, TouchPhase::Ended, [6., 5.]);
+
+        assert_eq!(gestures(&mut recognizer), [Gesture::Tap { position: [6., 5.] }]);
+    }
+
+    #[test]
+    fn holding_still_is_a_long_press() {
+        let mut recognizer = GestureRecognizer::default();
+        touch(&mut recognizer, 0, TouchPhase::Started, [5., 5.]);
+        recognizer.update(Duration::from_millis(300));
+        assert!(gestures(&mut recognizer).is_empty());
+
+        recognizer.update(Duration::from_millis(300));
+        touch(&mut recognizer, 0, TouchPhase::Ended, [5., 5.]);
+        assert_eq!(gestures(&mut recognizer), [Gesture::LongPress { position: [5., 5.] }]);
+    }
+
+    #[test]
+    fn dragging_past_the_slop_pans() {
+        let mut recognizer = GestureRecognizer::default();
+        touch(&mut recognizer, 0, TouchPhase::Started, [0., 0.]);
+        touch(&mut recognizer, 0, TouchPhase::Moved, [4., 0.]);
+        assert!(gestures(&mut recognizer).is_empty());
+
+        touch(&mut recognizer, 0, TouchPhase::Moved, [20., 0.]);
+        touch(&mut recognizer, 0, TouchPhase::Ended, [20., 0.]);
+        assert_eq!(gestures(&mut recognizer), [Gesture::Pan { position: [20., 0.], delta: [16., 0.] }]);
+    }
+
+    #[test]
+    fn spreading_two_fingers_pinches() {
+        let mut recognizer = GestureRecognizer::default();
+        touch(&mut recognizer, 0, TouchPhase::Started, [-10., 0.]);
+        touch(&mut recognizer, 1, TouchPhase::Started, [10., 0.]);
+        touch(&mut recognizer, 1, TouchPhase::Moved, [30., 0.]);
+
+        assert_eq!(gestures(&mut recognizer), [Gesture::Pinch { center: [10., 0.], scale: 2. }]);
+    }
+
+    #[test]
+    fn twisting_two_fingers_rotates() {
+        let mut recognizer = GestureRecognizer::default();
+        touch(&mut recognizer, 0, TouchPhase::Started, [0., 0.]);
+        touch(&mut recognizer, 1, TouchPhase::Started, [10., 0.]);
+        touch(&mut recognizer, 1, TouchPhase::Moved, [0., 10.]);
+
+        match gestures(&mut recognizer)[..] {
+            [Gesture::Rotate { center, angle }] => {
+                assert_eq!(center, [0., 5.]);
+                assert!((angle - std::f64::consts::FRAC_PI_2).abs() < 1e-9);
+            },
+            ref gestures => panic!("expected a single rotation, got {:?}", gestures),
+        }
+    }
+
+    #[test]
+    fn multi_touch_sequences_never_tap() {
+        let mut recognizer = GestureRecognizer::default();
+        touch(&mut recognizer, 0, TouchPhase::Started, [0., 0.]);
+        touch(&mut recognizer, 1, TouchPhase::Started, [50., 0.]);
+        touch(&mut recognizer, 1, TouchPhase::Ended, [50., 0.]);
+        touch(&mut recognizer, 0, TouchPhase::Ended, [0., 0.]);
+        assert!(gestures(&mut recognizer).is_empty());
+
+        touch(&mut recognizer, 2, TouchPhase::Started, [0., 0.]);
+        touch(&mut recognizer, 2, TouchPhase::Ended, [0., 0.]);
+        assert_eq!(gestures(&mut recognizer), [Gesture::Tap { position: [0., 0.] }]);
+    }
+}
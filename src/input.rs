@@ -1,29 +1,646 @@
 // Copyright 2021 Chay Nabors.
 
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::time::Duration;
+
+use serde::Deserialize;
+use serde::Serialize;
+
 pub use winit::event::ElementState as KeyState;
 pub use winit::event::KeyboardInput as KeyboardEvent;
+pub use winit::event::MouseButton;
 pub use winit::event::VirtualKeyCode as KeyCode;
 
-#[derive(Clone, Copy, Debug)]
-pub enum JoystickEvent {}
+use crate::action::ActionMap;
+use crate::action::AxisBinding;
+use crate::action::Binding;
+use crate::action::InputSource;
+use crate::gamepad::apply_deadzone;
+use crate::gamepad::GamepadAxis;
+use crate::gamepad::GamepadBackend;
+use crate::gamepad::GamepadButton;
+use crate::gamepad::GamepadId;
+use crate::gesture::Gesture;
+use crate::gesture::GestureRecognizer;
+
+const DEFAULT_GAMEPAD_DEADZONE: f32 = 0.1;
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum JoystickEvent {
+    Connected(GamepadId),
+    Disconnected(GamepadId),
+    ButtonPressed(GamepadId, GamepadButton),
+    ButtonReleased(GamepadId, GamepadButton),
+    AxisMoved(GamepadId, GamepadAxis, f32),
+}
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum ScrollDelta {
+    Lines([f32; 2]),
+    Pixels([f64; 2]),
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub enum MouseEvent {
     CursorMoved([f64; 2]),
+    CursorEntered,
+    CursorLeft,
+    ButtonPressed(MouseButton),
+    ButtonReleased(MouseButton),
+    Scrolled(ScrollDelta),
+    /// Unaccelerated device motion, reported even while the cursor is grabbed.
+    RawMotion([f64; 2]),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TouchPhase {
+    Started,
+    Moved,
+    Ended,
+    Cancelled,
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct TouchEvent {
+    /// Unique for as long as the finger stays down.
+    pub id: u64,
+    pub phase: TouchPhase,
+    /// The touch position relative to the center of the window.
+    pub position: [f64; 2],
+}
+
+/// Text composition by an input method, only sent while text input is enabled.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ImeEvent {
+    /// The input method became active, composition events may follow.
+    Enabled,
+    /// The text being composed, to be shown in place of the cursor until it's committed. The range is the byte
+    /// offsets of the composition cursor, `None` hides it. An empty string clears the preedit text.
+    Preedit(String, Option<(usize, usize)>),
+    /// Composed text to insert, replacing the preedit text.
+    Commit(String),
+    /// The input method became inactive, any preedit text should be cleared.
+    Disabled,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum InputEvent {
     JoystickEvent(JoystickEvent),
     KeyboardEvent(KeyboardEvent),
     MouseEvent(MouseEvent),
+    TouchEvent(TouchEvent),
+    /// Recognized from `TouchEvent`s by `Input`. These are derived rather than
+    /// raw input, so they aren't written to input recordings.
+    GestureEvent(Gesture),
+    /// A character typed by the user, after the keyboard layout has been
+    /// applied. Control characters are not reported. Text composed with an
+    /// input method arrives as `ImeEvent`s instead.
+    Text(char),
+    /// The window lost focus, so everything held is considered released.
+    FocusLost,
+    ImeEvent(ImeEvent),
 }
 
+#[derive(Debug, Default)]
+struct GamepadState {
+    buttons_down: HashSet<GamepadButton>,
+    axes: HashMap<GamepadAxis, f32>,
+}
+
+/// The buttons that went down or up since the edges were last cleared.
+#[derive(Debug, Default)]
+struct Edges {
+    keys_pressed: HashSet<KeyCode>,
+    keys_released: HashSet<KeyCode>,
+    mouse_buttons_pressed: HashSet<MouseButton>,
+    mouse_buttons_released: HashSet<MouseButton>,
+    gamepad_buttons_pressed: HashSet<(GamepadId, GamepadButton)>,
+    gamepad_buttons_released: HashSet<(GamepadId, GamepadButton)>,
+}
+
+impl Edges {
+    fn clear(&mut self) {
+        self.keys_pressed.clear();
+        self.keys_released.clear();
+        self.mouse_buttons_pressed.clear();
+        self.mouse_buttons_released.clear();
+        self.gamepad_buttons_pressed.clear();
+        self.gamepad_buttons_released.clear();
+    }
+}
+
+/// Presses and releases are tracked per frame, and separately per fixed step. Within a `FixedUpdateEvent` the
+/// `was_*` queries report everything since the previous fixed step, so presses made during frames that ran no
+/// fixed step aren't lost.
 #[derive(Debug)]
-pub struct Input;
+pub struct Input {
+    keys_down: HashSet<KeyCode>,
+    mouse_buttons_down: HashSet<MouseButton>,
+    frame_edges: Edges,
+    fixed_step_edges: Edges,
+    in_fixed_step: bool,
+    cursor_position: [f64; 2],
+    mouse_delta: [f64; 2],
+    gamepad_backend: Option<Box<dyn GamepadBackend>>,
+    gamepad_deadzone: f32,
+    gamepads: HashMap<GamepadId, GamepadState>,
+    actions: ActionMap,
+    text_input: bool,
+    gesture_recognizer: GestureRecognizer,
+    gestures: Vec<Gesture>,
+}
 
 impl Input {
-    pub(crate) fn new() -> Self {
-        Self
+    pub(crate) fn new(gamepad_backend: Option<Box<dyn GamepadBackend>>) -> Self {
+        Self {
+            keys_down: HashSet::new(),
+            mouse_buttons_down: HashSet::new(),
+            frame_edges: Edges::default(),
+            fixed_step_edges: Edges::default(),
+            in_fixed_step: false,
+            cursor_position: [0., 0.],
+            mouse_delta: [0., 0.],
+            gamepad_backend,
+            gamepad_deadzone: DEFAULT_GAMEPAD_DEADZONE,
+            gamepads: HashMap::new(),
+            actions: ActionMap::new(),
+            text_input: false,
+            gesture_recognizer: GestureRecognizer::default(),
+            gestures: vec![],
+        }
+    }
+
+    /// Returns the next gamepad event from the backend with the deadzone
+    /// applied, skipping axis motion that doesn't leave the deadzone.
+    pub(crate) fn poll_gamepad(&mut self) -> Option<JoystickEvent> {
+        let backend = self.gamepad_backend.as_mut()?;
+        while let Some(event) = backend.poll_event() {
+            if let JoystickEvent::AxisMoved(id, axis, value) = event {
+                let value = apply_deadzone(value, self.gamepad_deadzone);
+                let previous = self.gamepads.get(&id).and_then(|gamepad| gamepad.axes.get(&axis).copied());
+                if previous.unwrap_or(0.) == value {
+                    continue;
+                }
+                return Some(JoystickEvent::AxisMoved(id, axis, value));
+            }
+
+            return Some(event);
+        }
+
+        None
+    }
+
+    pub(crate) fn handle_event(&mut self, event: &InputEvent) {
+        match event {
+            InputEvent::KeyboardEvent(KeyboardEvent { virtual_keycode: Some(key), state, .. }) => match state {
+                KeyState::Pressed => {
+                    // Held keys repeat, but only the first press counts
+                    if self.keys_down.insert(*key) {
+                        self.record_edge(|edges| edges.keys_pressed.insert(*key));
+                    }
+                },
+                KeyState::Released => {
+                    if self.keys_down.remove(key) {
+                        self.record_edge(|edges| edges.keys_released.insert(*key));
+                    }
+                },
+            },
+            InputEvent::MouseEvent(MouseEvent::CursorMoved(position)) => self.cursor_position = *position,
+            InputEvent::MouseEvent(MouseEvent::ButtonPressed(button)) => {
+                if self.mouse_buttons_down.insert(*button) {
+                    self.record_edge(|edges| edges.mouse_buttons_pressed.insert(*button));
+                }
+            },
+            InputEvent::MouseEvent(MouseEvent::ButtonReleased(button)) => {
+                if self.mouse_buttons_down.remove(button) {
+                    self.record_edge(|edges| edges.mouse_buttons_released.insert(*button));
+                }
+            },
+            InputEvent::MouseEvent(MouseEvent::RawMotion(delta)) => {
+                self.mouse_delta[0] += delta[0];
+                self.mouse_delta[1] += delta[1];
+            },
+            InputEvent::JoystickEvent(JoystickEvent::Connected(id)) => {
+                self.gamepads.insert(*id, GamepadState::default());
+            },
+            InputEvent::JoystickEvent(JoystickEvent::Disconnected(id)) => {
+                self.gamepads.remove(id);
+            },
+            InputEvent::JoystickEvent(JoystickEvent::ButtonPressed(id, button)) => {
+                if self.gamepads.entry(*id).or_default().buttons_down.insert(*button) {
+                    self.record_edge(|edges| edges.gamepad_buttons_pressed.insert((*id, *button)));
+                }
+            },
+            InputEvent::JoystickEvent(JoystickEvent::ButtonReleased(id, button)) => {
+                if self.gamepads.entry(*id).or_default().buttons_down.remove(button) {
+                    self.record_edge(|edges| edges.gamepad_buttons_released.insert((*id, *button)));
+                }
+            },
+            InputEvent::JoystickEvent(JoystickEvent::AxisMoved(id, axis, value)) => {
+                self.gamepads.entry(*id).or_default().axes.insert(*axis, *value);
+            },
+            InputEvent::TouchEvent(touch) => self.gesture_recognizer.handle_touch(touch),
+            // The matching release events will never arrive, so release everything now
+            InputEvent::FocusLost => {
+                let keys = self.keys_down.drain().collect::<Vec<_>>();
+                let buttons = self.mouse_buttons_down.drain().collect::<Vec<_>>();
+                self.record_edge(|edges| {
+                    edges.keys_released.extend(&keys);
+                    edges.mouse_buttons_released.extend(&buttons);
+                });
+            },
+            _ => (),
+        }
+    }
+
+    pub fn set_gamepad_deadzone(&mut self, deadzone: f32) -> &mut Self {
+        self.gamepad_deadzone = deadzone.clamp(0., 0.99);
+        self
+    }
+
+    pub(crate) fn update_gestures(&mut self, delta_time: Duration) {
+        self.gesture_recognizer.update(delta_time);
+    }
+
+    pub(crate) fn poll_gesture(&mut self) -> Option<Gesture> {
+        let gesture = self.gesture_recognizer.poll_gesture()?;
+        self.gestures.push(gesture);
+        Some(gesture)
+    }
+
+    fn record_edge<R, F: Fn(&mut Edges) -> R>(&mut self, record: F) {
+        record(&mut self.frame_edges);
+        record(&mut self.fixed_step_edges);
+    }
+
+    /// The edges the `was_*` queries currently report.
+    fn edges(&self) -> &Edges {
+        if self.in_fixed_step {
+            &self.fixed_step_edges
+        } else {
+            &self.frame_edges
+        }
+    }
+
+    pub(crate) fn begin_fixed_step(&mut self) {
+        self.in_fixed_step = true;
+    }
+
+    pub(crate) fn end_fixed_step(&mut self) {
+        self.in_fixed_step = false;
+        self.fixed_step_edges.clear();
+    }
+
+    pub(crate) fn end_frame(&mut self) {
+        self.gestures.clear();
+        self.frame_edges.clear();
+        self.mouse_delta = [0., 0.];
+    }
+
+    pub fn is_key_down(&self, key: KeyCode) -> bool {
+        self.keys_down.contains(&key)
+    }
+
+    /// Whether `key` went down since the previous frame, or fixed step within a `FixedUpdateEvent`.
+    pub fn was_key_pressed(&self, key: KeyCode) -> bool {
+        self.edges().keys_pressed.contains(&key)
+    }
+
+    /// Whether `key` went up since the previous frame, or fixed step within a `FixedUpdateEvent`.
+    pub fn was_key_released(&self, key: KeyCode) -> bool {
+        self.edges().keys_released.contains(&key)
+    }
+
+    pub fn is_mouse_button_down(&self, button: MouseButton) -> bool {
+        self.mouse_buttons_down.contains(&button)
+    }
+
+    pub fn was_mouse_button_pressed(&self, button: MouseButton) -> bool {
+        self.edges().mouse_buttons_pressed.contains(&button)
+    }
+
+    pub fn was_mouse_button_released(&self, button: MouseButton) -> bool {
+        self.edges().mouse_buttons_released.contains(&button)
+    }
+
+    /// The cursor position relative to the center of the window.
+    pub fn cursor_position(&self) -> [f64; 2] {
+        self.cursor_position
+    }
+
+    /// The raw mouse motion accumulated since the previous frame. Unlike the
+    /// cursor position this keeps updating while the cursor is grabbed.
+    pub fn mouse_delta(&self) -> [f64; 2] {
+        self.mouse_delta
+    }
+
+    /// The id and position of every finger currently on the screen.
+    pub fn touches(&self) -> impl Iterator<Item = (u64, [f64; 2])> + '_ {
+        self.gesture_recognizer.touches()
+    }
+
+    /// The gestures recognized since the previous frame.
+    pub fn gestures(&self) -> &[Gesture] {
+        &self.gestures
+    }
+
+    pub fn gamepads(&self) -> impl Iterator<Item = GamepadId> + '_ {
+        self.gamepads.keys().copied()
+    }
+
+    pub fn is_gamepad_connected(&self, id: GamepadId) -> bool {
+        self.gamepads.contains_key(&id)
+    }
+
+    pub fn is_gamepad_button_down(&self, id: GamepadId, button: GamepadButton) -> bool {
+        self.gamepads.get(&id).map_or(false, |gamepad| gamepad.buttons_down.contains(&button))
+    }
+
+    pub fn was_gamepad_button_pressed(&self, id: GamepadId, button: GamepadButton) -> bool {
+        self.edges().gamepad_buttons_pressed.contains(&(id, button))
+    }
+
+    pub fn was_gamepad_button_released(&self, id: GamepadId, button: GamepadButton) -> bool {
+        self.edges().gamepad_buttons_released.contains(&(id, button))
+    }
+
+    /// The deadzone-filtered axis value, in `[-1, 1]` for sticks and `[0, 1]` for triggers.
+    pub fn gamepad_axis(&self, id: GamepadId, axis: GamepadAxis) -> f32 {
+        self.gamepads.get(&id).and_then(|gamepad| gamepad.axes.get(&axis).copied()).unwrap_or(0.)
+    }
+
+    /// While text input is enabled, actions bound to keys are suppressed so
+    /// typing into a text field doesn't also move the player, and input
+    /// methods may compose text, reported as `ImeEvent`s.
+    pub fn set_text_input(&mut self, enabled: bool) -> &mut Self {
+        self.text_input = enabled;
+        self
+    }
+
+    pub fn is_text_input(&self) -> bool {
+        self.text_input
+    }
+
+    pub fn actions(&self) -> &ActionMap {
+        &self.actions
+    }
+
+    pub fn actions_mut(&mut self) -> &mut ActionMap {
+        &mut self.actions
+    }
+
+    pub fn is_action_down(&self, action: &str) -> bool {
+        self.action_bindings(action)
+            .iter()
+            .any(|binding| self.modifiers_down(binding) && self.is_source_down(binding.source))
+    }
+
+    pub fn was_action_pressed(&self, action: &str) -> bool {
+        self.action_bindings(action)
+            .iter()
+            .any(|binding| self.modifiers_down(binding) && self.was_source_pressed(binding.source))
+    }
+
+    pub fn was_action_released(&self, action: &str) -> bool {
+        self.action_bindings(action)
+            .iter()
+            .any(|binding| self.modifiers_down(binding) && self.was_source_released(binding.source))
+    }
+
+    /// The combined value of every binding for `axis`, clamped to `[-1, 1]`.
+    pub fn action_axis(&self, axis: &str) -> f32 {
+        let bindings = match self.actions.active_context() {
+            Some(context) => context.axis_bindings(axis),
+            None => return 0.,
+        };
+
+        bindings.iter().map(|binding| self.axis_binding_value(binding)).sum::<f32>().clamp(-1., 1.)
+    }
+
+    /// The combined value of every binding for `axis`, clamped to the unit circle.
+    pub fn action_axis_2d(&self, axis: &str) -> [f32; 2] {
+        let bindings = match self.actions.active_context() {
+            Some(context) => context.axis_2d_bindings(axis),
+            None => return [0., 0.],
+        };
+
+        let mut value = [0., 0.];
+        for binding in bindings {
+            value[0] += self.axis_binding_value(&binding.x);
+            value[1] += self.axis_binding_value(&binding.y);
+        }
+
+        let length = (value[0] * value[0] + value[1] * value[1]).sqrt();
+        if length > 1. {
+            value = [value[0] / length, value[1] / length];
+        }
+        value
+    }
+
+    fn action_bindings(&self, action: &str) -> &[Binding] {
+        self.actions.active_context().map(|context| context.action_bindings(action)).unwrap_or(&[])
+    }
+
+    fn axis_binding_value(&self, binding: &AxisBinding) -> f32 {
+        match binding {
+            AxisBinding::Digital { negative, positive } => {
+                let down = |binding: &Binding| self.modifiers_down(binding) && self.is_source_down(binding.source);
+                down(positive) as i32 as f32 - down(negative) as i32 as f32
+            },
+            AxisBinding::Gamepad(axis) => self
+                .gamepads
+                .values()
+                .filter_map(|gamepad| gamepad.axes.get(axis).copied())
+                .fold(0., |furthest, value| if value.abs() > furthest.abs() { value } else { furthest }),
+        }
+    }
+
+    fn modifiers_down(&self, binding: &Binding) -> bool {
+        binding.modifiers.iter().all(|key| self.is_key_down(*key))
+    }
+
+    fn is_source_down(&self, source: InputSource) -> bool {
+        match source {
+            InputSource::Key(_) if self.text_input => false,
+            InputSource::Key(key) => self.is_key_down(key),
+            InputSource::MouseButton(button) => self.is_mouse_button_down(button),
+            InputSource::GamepadButton(button) => {
+                self.gamepads.values().any(|gamepad| gamepad.buttons_down.contains(&button))
+            },
+        }
+    }
+
+    fn was_source_pressed(&self, source: InputSource) -> bool {
+        match source {
+            InputSource::Key(_) if self.text_input => false,
+            InputSource::Key(key) => self.was_key_pressed(key),
+            InputSource::MouseButton(button) => self.was_mouse_button_pressed(button),
+            InputSource::GamepadButton(button) => {
+                self.edges().gamepad_buttons_pressed.iter().any(|&(_, pressed)| pressed == button)
+            },
+        }
+    }
+
+    fn was_source_released(&self, source: InputSource) -> bool {
+        match source {
+            InputSource::Key(_) if self.text_input => false,
+            InputSource::Key(key) => self.was_key_released(key),
+            InputSource::MouseButton(button) => self.was_mouse_button_released(button),
+            InputSource::GamepadButton(button) => {
+                self.edges().gamepad_buttons_released.iter().any(|&(_, released)| released == button)
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::action::Axis2dBinding;
+
+    #[allow(deprecated)]
+    fn key(key: KeyCode, state: KeyState) -> InputEvent {
+        InputEvent::KeyboardEvent(KeyboardEvent {
+            scancode: 0,
+            state,
+            virtual_keycode: Some(key),
+            modifiers: Default::default(),
+        })
+    }
+
+    fn digital(negative: KeyCode, positive: KeyCode) -> AxisBinding {
+        AxisBinding::Digital { negative: Binding::key(negative), positive: Binding::key(positive) }
+    }
+
+    #[test]
+    fn actions_resolve_against_the_top_context() {
+        let mut input = Input::new(None);
+        input.actions_mut().context_mut("gameplay").bind_action("jump", Binding::key(KeyCode::Space));
+        input.actions_mut().context_mut("menu").bind_action("confirm", Binding::key(KeyCode::Space));
+        input.handle_event(&key(KeyCode::Space, KeyState::Pressed));
+        assert!(!input.is_action_down("jump"));
+
+        input.actions_mut().push_context("gameplay");
+        assert!(input.is_action_down("jump"));
+        assert!(input.was_action_pressed("jump"));
+        assert!(!input.is_action_down("confirm"));
+
+        input.actions_mut().push_context("menu");
+        assert!(!input.is_action_down("jump"));
+        assert!(input.is_action_down("confirm"));
+
+        input.actions_mut().pop_context();
+        assert!(input.is_action_down("jump"));
+    }
+
+    #[test]
+    fn chorded_bindings_require_their_modifiers() {
+        let mut input = Input::new(None);
+        input
+            .actions_mut()
+            .context_mut("editor")
+            .bind_action("save", Binding::key(KeyCode::S).with_modifier(KeyCode::LControl));
+        input.actions_mut().push_context("editor");
+
+        input.handle_event(&key(KeyCode::S, KeyState::Pressed));
+        assert!(!input.was_action_pressed("save"));
+
+        input.end_frame();
+        input.handle_event(&key(KeyCode::S, KeyState::Released));
+        input.handle_event(&key(KeyCode::LControl, KeyState::Pressed));
+        input.handle_event(&key(KeyCode::S, KeyState::Pressed));
+        assert!(input.was_action_pressed("save"));
+    }
+
+    #[test]
+    fn axes_combine_and_clamp() {
+        let mut input = Input::new(None);
+        input
+            .actions_mut()
+            .context_mut("gameplay")
+            .bind_axis("strafe", digital(KeyCode::A, KeyCode::D))
+            .bind_axis("strafe", digital(KeyCode::Left, KeyCode::Right))
+            .bind_axis_2d(
+                "move",
+                Axis2dBinding { x: digital(KeyCode::A, KeyCode::D), y: digital(KeyCode::S, KeyCode::W) },
+            );
+        input.actions_mut().push_context("gameplay");
+
+        input.handle_event(&key(KeyCode::D, KeyState::Pressed));
+        input.handle_event(&key(KeyCode::Right, KeyState::Pressed));
+        assert_eq!(input.action_axis("strafe"), 1.);
+
+        input.handle_event(&key(KeyCode::W, KeyState::Pressed));
+        let [x, y] = input.action_axis_2d("move");
+        assert!((x - std::f32::consts::FRAC_1_SQRT_2).abs() < 1e-6);
+        assert!((y - std::f32::consts::FRAC_1_SQRT_2).abs() < 1e-6);
+    }
+
+    #[test]
+    fn text_input_suppresses_key_bindings() {
+        let mut input = Input::new(None);
+        input
+            .actions_mut()
+            .context_mut("gameplay")
+            .bind_action("jump", Binding::key(KeyCode::Space))
+            .bind_action("fire", Binding::mouse_button(MouseButton::Left));
+        input.actions_mut().push_context("gameplay");
+        input.set_text_input(true);
+
+        input.handle_event(&key(KeyCode::Space, KeyState::Pressed));
+        input.handle_event(&InputEvent::MouseEvent(MouseEvent::ButtonPressed(MouseButton::Left)));
+        assert!(input.is_key_down(KeyCode::Space));
+        assert!(!input.is_action_down("jump"));
+        assert!(input.is_action_down("fire"));
+
+        input.set_text_input(false);
+        assert!(input.is_action_down("jump"));
+    }
+
+    #[test]
+    fn gamepad_axes_use_the_furthest_deflection() {
+        let mut input = Input::new(None);
+        input.actions_mut().context_mut("gameplay").bind_axis("steer", AxisBinding::Gamepad(GamepadAxis::LeftStickX));
+        input.actions_mut().push_context("gameplay");
+
+        input.handle_event(&InputEvent::JoystickEvent(JoystickEvent::AxisMoved(
+            GamepadId(0),
+            GamepadAxis::LeftStickX,
+            0.25,
+        )));
+        input.handle_event(&InputEvent::JoystickEvent(JoystickEvent::AxisMoved(
+            GamepadId(1),
+            GamepadAxis::LeftStickX,
+            -0.75,
+        )));
+        assert_eq!(input.action_axis("steer"), -0.75);
+    }
+
+    #[test]
+    fn presses_are_latched_until_a_fixed_step_consumes_them() {
+        let mut input = Input::new(None);
+        input.handle_event(&key(KeyCode::Space, KeyState::Pressed));
+        input.end_frame();
+        assert!(!input.was_key_pressed(KeyCode::Space));
+
+        // The next frame runs two fixed steps, only the first sees the press
+        input.begin_fixed_step();
+        assert!(input.was_key_pressed(KeyCode::Space));
+        input.end_fixed_step();
+        input.begin_fixed_step();
+        assert!(!input.was_key_pressed(KeyCode::Space));
+        input.end_fixed_step();
+    }
+
+    #[test]
+    fn fixed_steps_leave_frame_edges_alone() {
+        let mut input = Input::new(None);
+        input.handle_event(&key(KeyCode::Space, KeyState::Pressed));
+        input.begin_fixed_step();
+        input.end_fixed_step();
+        assert!(input.was_key_pressed(KeyCode::Space));
+        input.end_frame();
+        assert!(!input.was_key_pressed(KeyCode::Space));
     }
 }
@@ -1,25 +1,62 @@
 // Copyright 2021 Chay Nabors.
 
+mod action;
 mod audio;
+mod bcn;
+mod buffer;
+mod builder;
 mod engine;
+mod environment;
+mod gamepad;
+mod gesture;
 mod input;
+mod light;
 mod loadable;
 mod model;
 mod network;
+mod recording;
 mod renderer;
 mod result;
+mod shadow;
 mod sound;
+#[cfg(test)]
+mod test_util;
 mod texture;
 mod window;
 
+pub use action::ActionMap;
+pub use action::Axis2dBinding;
+pub use action::AxisBinding;
+pub use action::Binding;
+pub use action::InputContext;
+pub use action::InputSource;
 pub use audio::Audio;
 pub use audio::AudioSource;
+pub use builder::EngineBuilder;
 pub use engine::Engine;
+pub use gamepad::GamepadAxis;
+pub use gamepad::GamepadBackend;
+pub use gamepad::GamepadButton;
+pub use gamepad::GamepadId;
+pub use gamepad::GilrsBackend;
+pub use gamepad::VirtualGamepad;
+pub use gamepad::VirtualGamepadBackend;
 pub use input::Input;
 pub use input::KeyCode;
 pub use input::KeyState;
+pub use input::MouseButton;
+pub use light::DirectionalLight;
+pub use light::PointLight;
+pub use light::SpotLight;
 pub use loadable::Loadable;
+pub use model::Animation;
+pub use model::Material;
 pub use model::Model;
+pub use model::ModelConfig;
+pub use model::ModelHandle;
+pub use model::Node;
+pub use model::NormalMode;
+pub use model::Skin;
 pub use nalgebra as math;
 pub use nalgebra_glm as math_ext;
 pub use network::Network;
@@ -27,15 +64,35 @@ pub use network::NetworkConfig;
 pub use network::Packet;
 pub use network::Socket;
 pub use renderer::Renderer;
+pub use renderer::ShadingModel;
+pub use result::GearError;
 pub use result::Result;
 pub use sound::Sound;
+pub use texture::SamplerConfig;
 pub use texture::Texture;
+pub use wgpu::AddressMode;
+pub use wgpu::BackendBit;
+pub use wgpu::FilterMode;
+pub use wgpu::PowerPreference;
+pub use wgpu::PresentMode;
 pub use window::Window;
 
+pub mod animation {
+    pub use crate::model::Channel;
+    pub use crate::model::ChannelOutputs;
+    pub use crate::model::Interpolation;
+}
+
 pub mod event {
     pub use crate::engine::Event;
+    pub use crate::gesture::Gesture;
+    pub use crate::input::ImeEvent;
     pub use crate::input::InputEvent;
+    pub use crate::input::JoystickEvent;
     pub use crate::input::MouseEvent;
+    pub use crate::input::ScrollDelta;
+    pub use crate::input::TouchEvent;
+    pub use crate::input::TouchPhase;
     pub use crate::network::NetworkEvent;
     pub use crate::window::WindowEvent;
 }
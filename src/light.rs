@@ -0,0 +1,141 @@
+// Copyright 2021 Chay Nabors.
+
+use bytemuck::Pod;
+use bytemuck::Zeroable;
+use nalgebra::Point3;
+use nalgebra::Vector3;
+
+const DIRECTIONAL: f32 = 0.;
+const POINT: f32 = 1.;
+const SPOT: f32 = 2.;
+const NO_SHADOW: f32 = -1.;
+
+/// A light infinitely far away, like the sun.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DirectionalLight {
+    /// The direction the light travels in.
+    pub direction: Vector3<f32>,
+    pub color: [f32; 3],
+    pub intensity: f32,
+    /// Renders cascaded shadow maps covering the view up to `Renderer::set_shadow_distance`.
+    pub cast_shadows: bool,
+}
+
+impl Default for DirectionalLight {
+    fn default() -> Self {
+        Self { direction: -Vector3::y(), color: [1., 1., 1.], intensity: 1., cast_shadows: false }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PointLight {
+    pub position: Point3<f32>,
+    pub color: [f32; 3],
+    pub intensity: f32,
+    /// Distance at which the light has faded out completely.
+    pub range: f32,
+}
+
+impl Default for PointLight {
+    fn default() -> Self {
+        Self { position: Point3::origin(), color: [1., 1., 1.], intensity: 1., range: 10. }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SpotLight {
+    pub position: Point3<f32>,
+    /// The direction the cone points in.
+    pub direction: Vector3<f32>,
+    pub color: [f32; 3],
+    pub intensity: f32,
+    /// Distance at which the light has faded out completely.
+    pub range: f32,
+    /// Angle from the center in radians within which the light is at full strength.
+    pub inner_angle: f32,
+    /// Angle from the center in radians beyond which nothing is lit.
+    pub outer_angle: f32,
+    pub cast_shadows: bool,
+}
+
+impl Default for SpotLight {
+    fn default() -> Self {
+        Self {
+            position: Point3::origin(),
+            direction: -Vector3::y(),
+            color: [1., 1., 1.],
+            intensity: 1.,
+            range: 10.,
+            inner_angle: std::f32::consts::FRAC_PI_8,
+            outer_angle: std::f32::consts::FRAC_PI_6,
+            cast_shadows: false,
+        }
+    }
+}
+
+/// Header of the light storage buffer, followed by `count` lights.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+pub(crate) struct LightsHeader {
+    pub ambient: [f32; 4],
+    pub count: u32,
+    pub _padding: [u32; 3],
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+pub(crate) struct LightData {
+    /// Kind in `w`.
+    position: [f32; 4],
+    /// Range in `w`.
+    direction: [f32; 4],
+    /// Color premultiplied by intensity.
+    color: [f32; 4],
+    /// Cosines of the inner and outer spot angles, then the first shadow map layer or -1.
+    cone: [f32; 4],
+}
+
+impl LightData {
+    pub fn set_shadow_layer(&mut self, layer: usize) {
+        self.cone[2] = layer as f32;
+    }
+}
+
+impl From<DirectionalLight> for LightData {
+    fn from(light: DirectionalLight) -> Self {
+        let direction = light.direction.try_normalize(0.).unwrap_or_else(|| -Vector3::y());
+        Self {
+            position: [0., 0., 0., DIRECTIONAL],
+            direction: [direction.x, direction.y, direction.z, 0.],
+            color: premultiply(light.color, light.intensity),
+            cone: [0., 0., NO_SHADOW, 0.],
+        }
+    }
+}
+
+impl From<PointLight> for LightData {
+    fn from(light: PointLight) -> Self {
+        Self {
+            position: [light.position.x, light.position.y, light.position.z, POINT],
+            direction: [0., 0., 0., light.range],
+            color: premultiply(light.color, light.intensity),
+            cone: [0., 0., NO_SHADOW, 0.],
+        }
+    }
+}
+
+impl From<SpotLight> for LightData {
+    fn from(light: SpotLight) -> Self {
+        let direction = light.direction.try_normalize(0.).unwrap_or_else(|| -Vector3::y());
+        Self {
+            position: [light.position.x, light.position.y, light.position.z, SPOT],
+            direction: [direction.x, direction.y, direction.z, light.range],
+            color: premultiply(light.color, light.intensity),
+            cone: [light.inner_angle.cos(), light.outer_angle.cos(), NO_SHADOW, 0.],
+        }
+    }
+}
+
+fn premultiply(color: [f32; 3], intensity: f32) -> [f32; 4] {
+    [color[0] * intensity, color[1] * intensity, color[2] * intensity, 1.]
+}
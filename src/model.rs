@@ -1,13 +1,42 @@
 // Copyright 2021 Chay Nabors.
 
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::ffi::OsStr;
 use std::path::Path;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::Weak;
 
 use bytemuck::Pod;
 use bytemuck::Zeroable;
+use gltf::animation::util::ReadOutputs;
+use gltf::image::Format;
+use gltf::mesh::Mode;
+use gltf::texture::MagFilter;
+use gltf::texture::MinFilter;
+use gltf::texture::WrappingMode;
+use log::warn;
+use nalgebra::Matrix4;
+use nalgebra::Point3;
+use nalgebra::Vector3;
 use tobj::LoadOptions;
+use wgpu::util::BufferInitDescriptor;
+use wgpu::util::DeviceExt;
+use wgpu::AddressMode;
+use wgpu::Buffer;
+use wgpu::BufferUsage;
+use wgpu::Device;
+use wgpu::FilterMode;
 
 use crate::result::Result;
+use crate::result::WithPath;
 use crate::Loadable;
+use crate::SamplerConfig;
+use crate::Texture;
+
+static NEXT_MODEL_ID: AtomicU64 = AtomicU64::new(0);
 
 #[repr(C)]
 #[derive(Copy, Clone, Debug, Pod, Zeroable)]
@@ -15,41 +44,1065 @@ pub struct Vertex {
     pub position: [f32; 3],
     pub tex_coords: [f32; 2],
     pub normal: [f32; 3],
+    /// Tangent with the bitangent's handedness in `w`, zero where the vertex can't be normal mapped.
+    pub tangent: [f32; 4],
+}
+
+#[derive(Clone, Debug)]
+pub struct Material {
+    pub name: String,
+    pub ambient: [f32; 3],
+    pub diffuse: [f32; 3],
+    pub specular: [f32; 3],
+    pub shininess: f32,
+    /// Opacity, where 1 is fully opaque.
+    pub dissolve: f32,
+    pub metallic: f32,
+    pub roughness: f32,
+    pub emissive: [f32; 3],
+    /// Base color for glTF materials.
+    pub diffuse_texture: Option<Texture>,
+    pub normal_texture: Option<Texture>,
+    pub specular_texture: Option<Texture>,
+    /// Roughness in the green channel and metalness in the blue channel.
+    pub metallic_roughness_texture: Option<Texture>,
+    pub occlusion_texture: Option<Texture>,
+    pub emissive_texture: Option<Texture>,
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            ambient: [0., 0., 0.],
+            diffuse: [1., 1., 1.],
+            specular: [0., 0., 0.],
+            shininess: 0.,
+            dissolve: 1.,
+            metallic: 0.,
+            roughness: 1.,
+            emissive: [0., 0., 0.],
+            diffuse_texture: None,
+            normal_texture: None,
+            specular_texture: None,
+            metallic_roughness_texture: None,
+            occlusion_texture: None,
+            emissive_texture: None,
+        }
+    }
+}
+
+impl Material {
+    /// Diffuse color with dissolve as alpha.
+    pub(crate) fn diffuse_rgba(&self) -> [f32; 4] {
+        [self.diffuse[0], self.diffuse[1], self.diffuse[2], self.dissolve]
+    }
+
+    /// Specular color with shininess as alpha.
+    pub(crate) fn specular_rgba(&self) -> [f32; 4] {
+        [self.specular[0], self.specular[1], self.specular[2], self.shininess]
+    }
+
+    pub(crate) fn emissive_rgba(&self) -> [f32; 4] {
+        [self.emissive[0], self.emissive[1], self.emissive[2], 1.]
+    }
+
+    /// Metalness and roughness factors in the first two components.
+    pub(crate) fn metallic_roughness(&self) -> [f32; 4] {
+        [self.metallic, self.roughness, 0., 0.]
+    }
 }
 
 pub struct Mesh {
+    /// Positions and normals are in model space, except for skinned meshes which stay in their bind pose.
     pub vertices: Vec<Vertex>,
     pub indices: Vec<u32>,
+    /// Index into the owning model's `materials`.
+    pub material: Option<usize>,
+    /// Index into the owning model's `nodes` of the node this mesh was instantiated by.
+    pub node: Option<usize>,
+    /// Per vertex joint indices into the node's skin, empty for meshes without skinning data.
+    pub joints: Vec<[u16; 4]>,
+    pub weights: Vec<[f32; 4]>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Node {
+    pub name: Option<String>,
+    pub parent: Option<usize>,
+    pub children: Vec<usize>,
+    pub translation: [f32; 3],
+    /// Unit quaternion as `[x, y, z, w]`.
+    pub rotation: [f32; 4],
+    pub scale: [f32; 3],
+    /// Index into the owning model's `skins`.
+    pub skin: Option<usize>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Skin {
+    pub name: Option<String>,
+    /// Indices into the owning model's `nodes`.
+    pub joints: Vec<usize>,
+    /// Column major, one per joint.
+    pub inverse_bind_matrices: Vec<[[f32; 4]; 4]>,
+    pub skeleton: Option<usize>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Interpolation {
+    Linear,
+    Step,
+    /// Outputs hold an in tangent, value and out tangent for each keyframe.
+    CubicSpline,
+}
+
+#[derive(Clone, Debug)]
+pub enum ChannelOutputs {
+    Translations(Vec<[f32; 3]>),
+    Rotations(Vec<[f32; 4]>),
+    Scales(Vec<[f32; 3]>),
+    /// Every morph target's weight for each keyframe in turn.
+    MorphWeights(Vec<f32>),
+}
+
+#[derive(Clone, Debug)]
+pub struct Channel {
+    /// Index into the owning model's `nodes`.
+    pub node: usize,
+    pub interpolation: Interpolation,
+    /// Keyframe times in seconds.
+    pub inputs: Vec<f32>,
+    pub outputs: ChannelOutputs,
+}
+
+#[derive(Clone, Debug)]
+pub struct Animation {
+    pub name: Option<String>,
+    pub channels: Vec<Channel>,
+}
+
+/// How normals are generated for meshes that lack them.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum NormalMode {
+    /// Each triangle uses its own face normal.
+    Flat,
+    /// Normals are averaged across faces meeting at less than `max_angle` radians, hard edges are kept beyond it.
+    Smooth { max_angle: f32 },
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ModelConfig {
+    pub normal_mode: NormalMode,
+    /// Discards the file's normals and generates new ones for every mesh.
+    pub recompute_normals: bool,
+    /// Computes tangents for meshes that don't provide them. Meshes without tangents aren't normal mapped.
+    pub generate_tangents: bool,
+}
+
+impl Default for ModelConfig {
+    fn default() -> Self {
+        Self {
+            normal_mode: NormalMode::Smooth { max_angle: std::f32::consts::FRAC_PI_3 },
+            recompute_normals: false,
+            generate_tangents: true,
+        }
+    }
 }
 
 #[derive(Default)]
 pub struct Model {
     pub meshes: Vec<Mesh>,
+    pub materials: Vec<Material>,
+    pub nodes: Vec<Node>,
+    /// Indices into `nodes` of the loaded scene's root nodes.
+    pub roots: Vec<usize>,
+    pub skins: Vec<Skin>,
+    pub animations: Vec<Animation>,
+}
+
+#[derive(Debug)]
+pub(crate) struct UploadedMesh {
+    pub index_count: u32,
+    pub diffuse: [f32; 4],
+    pub specular: [f32; 4],
+    pub emissive: [f32; 4],
+    pub metallic_roughness: [f32; 4],
+    pub diffuse_texture: Option<Texture>,
+    pub normal_texture: Option<Texture>,
+    pub metallic_roughness_texture: Option<Texture>,
+    pub occlusion_texture: Option<Texture>,
+    pub emissive_texture: Option<Texture>,
+}
+
+#[derive(Debug)]
+pub(crate) struct UploadedModel {
+    pub id: u64,
+    pub meshes: Vec<UploadedMesh>,
+}
+
+/// A model uploaded once by `Renderer::upload_model` and drawn by reference. Cloning is cheap;
+/// the GPU buffers are freed by `Renderer::free_model` or once every clone is dropped.
+#[derive(Clone, Debug)]
+pub struct ModelHandle {
+    pub(crate) data: Arc<UploadedModel>,
+}
+
+impl ModelHandle {
+    pub(crate) fn new(model: &Model) -> ModelHandle {
+        // The handle holds on to the material textures so their GPU copies outlive the model
+        let meshes = model
+            .meshes
+            .iter()
+            .map(|mesh| {
+                let material = mesh.material.and_then(|material| model.materials.get(material));
+                UploadedMesh {
+                    index_count: mesh.indices.len() as u32,
+                    diffuse: material.map_or([1., 1., 1., 1.], Material::diffuse_rgba),
+                    specular: material.map_or([0., 0., 0., 0.], Material::specular_rgba),
+                    emissive: material.map_or([0., 0., 0., 1.], Material::emissive_rgba),
+                    metallic_roughness: material.map_or([0., 1., 0., 0.], Material::metallic_roughness),
+                    diffuse_texture: material.and_then(|material| material.diffuse_texture.clone()),
+                    normal_texture: material.and_then(|material| material.normal_texture.clone()),
+                    metallic_roughness_texture: material
+                        .and_then(|material| material.metallic_roughness_texture.clone()),
+                    occlusion_texture: material.and_then(|material| material.occlusion_texture.clone()),
+                    emissive_texture: material.and_then(|material| material.emissive_texture.clone()),
+                }
+            })
+            .collect();
+
+        let id = NEXT_MODEL_ID.fetch_add(1, Ordering::Relaxed);
+        ModelHandle { data: Arc::new(UploadedModel { id, meshes }) }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct GpuMesh {
+    pub vertex_buffer: Buffer,
+    pub index_buffer: Buffer,
+}
+
+#[derive(Debug)]
+pub(crate) struct GpuModel {
+    pub meshes: Vec<GpuMesh>,
+    data: Weak<UploadedModel>,
+}
+
+impl GpuModel {
+    pub fn upload(device: &Device, model: &Model, handle: &ModelHandle) -> GpuModel {
+        let meshes = model
+            .meshes
+            .iter()
+            .map(|mesh| GpuMesh {
+                vertex_buffer: device.create_buffer_init(&BufferInitDescriptor {
+                    label: Some("mesh_vertex_buffer"),
+                    contents: bytemuck::cast_slice(&mesh.vertices),
+                    usage: BufferUsage::VERTEX,
+                }),
+                index_buffer: device.create_buffer_init(&BufferInitDescriptor {
+                    label: Some("mesh_index_buffer"),
+                    contents: bytemuck::cast_slice(&mesh.indices),
+                    usage: BufferUsage::INDEX,
+                }),
+            })
+            .collect();
+
+        GpuModel { meshes, data: Arc::downgrade(&handle.data) }
+    }
+
+    /// Whether every `ModelHandle` referring to this upload has been dropped.
+    pub fn is_orphaned(&self) -> bool {
+        self.data.strong_count() == 0
+    }
+}
+
+impl Model {
+    pub fn load_with_config<P: AsRef<Path>>(path: P, config: ModelConfig) -> Result<Model> {
+        let extension = path.as_ref().extension().and_then(OsStr::to_str).map(str::to_ascii_lowercase);
+        match extension.as_deref() {
+            Some("gltf") | Some("glb") => load_gltf(path.as_ref(), &config).with_path(&path),
+            _ => load_obj(path.as_ref(), &config),
+        }
+    }
 }
 
 impl Loadable for Model {
     fn load<P: AsRef<Path>>(path: P) -> Result<Model> {
-        let (models, _materials) =
-            tobj::load_obj(path.as_ref(), &LoadOptions { triangulate: true, single_index: true, ..Default::default() })?;
-
-        let mut meshes = vec![];
-        for model in models {
-            let mut vertices = vec![];
-            for i in 0..model.mesh.positions.len() / 3 {
-                vertices.push(Vertex {
-                    position: [
-                        model.mesh.positions[i * 3],
-                        model.mesh.positions[i * 3 + 1],
-                        model.mesh.positions[i * 3 + 2],
-                    ],
-                    tex_coords: [model.mesh.texcoords[i * 2], model.mesh.texcoords[i * 2 + 1]],
-                    normal: [model.mesh.normals[i * 3], model.mesh.normals[i * 3 + 1], model.mesh.normals[i * 3 + 2]],
-                });
+        Model::load_with_config(path, ModelConfig::default())
+    }
+}
+
+fn load_obj(path: &Path, config: &ModelConfig) -> Result<Model> {
+    let (models, materials) =
+        tobj::load_obj(path, &LoadOptions { triangulate: true, single_index: true, ..Default::default() })
+            .with_path(path)?;
+
+    let mut meshes = vec![];
+    for model in models {
+        let vertex_count = model.mesh.positions.len() / 3;
+        let has_tex_coords = model.mesh.texcoords.len() == vertex_count * 2;
+        let has_normals = model.mesh.normals.len() == vertex_count * 3;
+
+        let mut vertices = vec![];
+        for i in 0..vertex_count {
+            vertices.push(Vertex {
+                position: [
+                    model.mesh.positions[i * 3],
+                    model.mesh.positions[i * 3 + 1],
+                    model.mesh.positions[i * 3 + 2],
+                ],
+                // OBJ puts the texture origin at the bottom left, wgpu at the top left
+                tex_coords: match has_tex_coords {
+                    true => [model.mesh.texcoords[i * 2], 1. - model.mesh.texcoords[i * 2 + 1]],
+                    false => [0., 0.],
+                },
+                normal: match has_normals {
+                    true => [model.mesh.normals[i * 3], model.mesh.normals[i * 3 + 1], model.mesh.normals[i * 3 + 2]],
+                    false => [0., 0., 0.],
+                },
+                tangent: [0., 0., 0., 0.],
+            });
+        }
+
+        let mut mesh = Mesh {
+            vertices,
+            indices: model.mesh.indices,
+            material: model.mesh.material_id,
+            node: None,
+            joints: vec![],
+            weights: vec![],
+        };
+        if !has_normals || config.recompute_normals {
+            generate_normals(&mut mesh, config.normal_mode);
+        }
+        if has_tex_coords && config.generate_tangents {
+            generate_tangents(&mut mesh);
+        }
+
+        meshes.push(mesh);
+    }
+
+    let materials = match materials {
+        Ok(materials) => materials,
+        Err(e) => {
+            warn!("Failed to load materials for {}: {}", path.display(), e);
+            vec![]
+        },
+    };
+
+    // Texture paths in MTL files are relative to the OBJ, and often shared between materials
+    let directory = path.parent().unwrap_or_else(|| Path::new(""));
+    let mut textures = HashMap::new();
+    let mut load_texture = |name: &str| -> Option<Texture> {
+        if name.is_empty() {
+            return None;
+        }
+
+        let texture = textures.entry(name.to_owned()).or_insert_with(|| {
+            match Texture::load(directory.join(name.replace('\\', "/"))) {
+                Ok(texture) => Some(texture),
+                Err(e) => {
+                    warn!("{}", e);
+                    None
+                },
+            }
+        });
+
+        texture.clone()
+    };
+
+    let materials = materials
+        .into_iter()
+        .map(|material| Material {
+            diffuse_texture: load_texture(&material.diffuse_texture),
+            normal_texture: load_texture(&material.normal_texture).map(Texture::into_linear),
+            specular_texture: load_texture(&material.specular_texture),
+            name: material.name,
+            ambient: material.ambient,
+            diffuse: material.diffuse,
+            specular: material.specular,
+            shininess: material.shininess,
+            dissolve: material.dissolve,
+            ..Default::default()
+        })
+        .collect();
+
+    Ok(Model { meshes, materials, ..Default::default() })
+}
+
+fn load_gltf(path: &Path, config: &ModelConfig) -> Result<Model> {
+    let (document, buffers, images) = gltf::import(path)?;
+    let mut model = Model::default();
+
+    // Materials often share images, only the sampler differs between uses. Only color textures are sRGB encoded, the
+    // linear conversion is cached too so packed images used by several slots are uploaded once
+    let mut textures = HashMap::new();
+    let mut load_texture = |texture: gltf::Texture, linear: bool| -> Option<Texture> {
+        let image = texture.source().index();
+        let loaded = textures.entry((image, linear)).or_insert_with(|| match texture_from_gltf(&images[image]) {
+            Ok(texture) if linear => Some(texture.into_linear()),
+            Ok(texture) => Some(texture),
+            Err(e) => {
+                warn!("Failed to load image {} of {}: {}", image, path.display(), e);
+                None
+            },
+        });
+
+        loaded.clone().map(|loaded| loaded.with_sampler(sampler_from_gltf(&texture.sampler())))
+    };
+
+    for material in document.materials() {
+        let pbr = material.pbr_metallic_roughness();
+        let [red, green, blue, alpha] = pbr.base_color_factor();
+        model.materials.push(Material {
+            name: material.name().unwrap_or_default().to_owned(),
+            diffuse: [red, green, blue],
+            dissolve: alpha,
+            metallic: pbr.metallic_factor(),
+            roughness: pbr.roughness_factor(),
+            emissive: material.emissive_factor(),
+            diffuse_texture: pbr.base_color_texture().and_then(|info| load_texture(info.texture(), false)),
+            normal_texture: material.normal_texture().and_then(|info| load_texture(info.texture(), true)),
+            metallic_roughness_texture: pbr
+                .metallic_roughness_texture()
+                .and_then(|info| load_texture(info.texture(), true)),
+            occlusion_texture: material.occlusion_texture().and_then(|info| load_texture(info.texture(), true)),
+            emissive_texture: material.emissive_texture().and_then(|info| load_texture(info.texture(), false)),
+            ..Default::default()
+        });
+    }
+
+    for node in document.nodes() {
+        let (translation, rotation, scale) = node.transform().decomposed();
+        model.nodes.push(Node {
+            name: node.name().map(str::to_owned),
+            parent: None,
+            children: node.children().map(|child| child.index()).collect(),
+            translation,
+            rotation,
+            scale,
+            skin: node.skin().map(|skin| skin.index()),
+        });
+    }
+
+    for node in document.nodes() {
+        for child in node.children() {
+            model.nodes[child.index()].parent = Some(node.index());
+        }
+    }
+
+    let scene = document.default_scene().or_else(|| document.scenes().next());
+    let mut stack: Vec<_> =
+        scene.iter().flat_map(|scene| scene.nodes()).map(|node| (node, Matrix4::<f32>::identity())).collect();
+    model.roots = stack.iter().map(|(node, _)| node.index()).collect();
+    while let Some((node, parent_transform)) = stack.pop() {
+        let transform = parent_transform * Matrix4::from(node.transform().matrix());
+        if let Some(mesh) = node.mesh() {
+            // Skinned vertices are placed by their joints, which already carry the node hierarchy
+            let mesh_transform = if node.skin().is_some() { Matrix4::identity() } else { transform };
+            for primitive in mesh.primitives() {
+                if let Some(mut mesh) = load_primitive(&primitive, &buffers, &mesh_transform, config) {
+                    mesh.node = Some(node.index());
+                    model.meshes.push(mesh);
+                }
+            }
+        }
+
+        stack.extend(node.children().map(|child| (child, transform)));
+    }
+
+    for skin in document.skins() {
+        let joints: Vec<usize> = skin.joints().map(|joint| joint.index()).collect();
+        let reader = skin.reader(|buffer| Some(&*buffers[buffer.index()]));
+        let inverse_bind_matrices = match reader.read_inverse_bind_matrices() {
+            Some(matrices) => matrices.collect(),
+            None => vec![Matrix4::<f32>::identity().into(); joints.len()],
+        };
+
+        model.skins.push(Skin {
+            name: skin.name().map(str::to_owned),
+            joints,
+            inverse_bind_matrices,
+            skeleton: skin.skeleton().map(|skeleton| skeleton.index()),
+        });
+    }
+
+    for animation in document.animations() {
+        let mut channels = vec![];
+        for channel in animation.channels() {
+            let reader = channel.reader(|buffer| Some(&*buffers[buffer.index()]));
+            let inputs = match reader.read_inputs() {
+                Some(inputs) => inputs.collect(),
+                None => continue,
+            };
+            let outputs = match reader.read_outputs() {
+                Some(ReadOutputs::Translations(translations)) => ChannelOutputs::Translations(translations.collect()),
+                Some(ReadOutputs::Rotations(rotations)) => ChannelOutputs::Rotations(rotations.into_f32().collect()),
+                Some(ReadOutputs::Scales(scales)) => ChannelOutputs::Scales(scales.collect()),
+                Some(ReadOutputs::MorphTargetWeights(weights)) => {
+                    ChannelOutputs::MorphWeights(weights.into_f32().collect())
+                },
+                None => continue,
+            };
+            let interpolation = match channel.sampler().interpolation() {
+                gltf::animation::Interpolation::Linear => Interpolation::Linear,
+                gltf::animation::Interpolation::Step => Interpolation::Step,
+                gltf::animation::Interpolation::CubicSpline => Interpolation::CubicSpline,
+            };
+
+            channels.push(Channel { node: channel.target().node().index(), interpolation, inputs, outputs });
+        }
+
+        model.animations.push(Animation { name: animation.name().map(str::to_owned), channels });
+    }
+
+    Ok(model)
+}
+
+/// Reads a primitive as a triangle list, baking `transform` into its positions and normals.
+fn load_primitive(
+    primitive: &gltf::Primitive,
+    buffers: &[gltf::buffer::Data],
+    transform: &Matrix4<f32>,
+    config: &ModelConfig,
+) -> Option<Mesh> {
+    let reader = primitive.reader(|buffer| Some(&*buffers[buffer.index()]));
+    let positions: Vec<[f32; 3]> = reader.read_positions()?.collect();
+    let has_normals = reader.read_normals().is_some() && !config.recompute_normals;
+    let normals = match reader.read_normals() {
+        Some(normals) => normals.collect(),
+        None => vec![[0., 0., 0.]; positions.len()],
+    };
+    // Tangents are only meaningful alongside the normals they were authored with
+    let tangents: Option<Vec<[f32; 4]>> = match has_normals {
+        true => reader.read_tangents().map(|tangents| tangents.collect()),
+        false => None,
+    };
+    let has_tex_coords = reader.read_tex_coords(0).is_some();
+    let tex_coords = match reader.read_tex_coords(0) {
+        Some(tex_coords) => tex_coords.into_f32().collect(),
+        None => vec![[0., 0.]; positions.len()],
+    };
+    let mut indices: Vec<u32> = match reader.read_indices() {
+        Some(indices) => indices.into_u32().collect(),
+        None => (0..positions.len() as u32).collect(),
+    };
+
+    indices = match primitive.mode() {
+        Mode::Triangles => indices,
+        // Every other strip triangle is wound the opposite way
+        Mode::TriangleStrip => (2..indices.len())
+            .flat_map(|i| match i % 2 {
+                0 => [indices[i - 2], indices[i - 1], indices[i]],
+                _ => [indices[i - 1], indices[i - 2], indices[i]],
+            })
+            .collect(),
+        Mode::TriangleFan => (2..indices.len()).flat_map(|i| [indices[0], indices[i - 1], indices[i]]).collect(),
+        mode => {
+            warn!("Skipping glTF primitive with unsupported mode {:?}", mode);
+            return None;
+        },
+    };
+
+    // A mirroring transform flips the winding order and the bitangent
+    let mirrored = transform.determinant() < 0.;
+    if mirrored {
+        for triangle in indices.chunks_exact_mut(3) {
+            triangle.swap(1, 2);
+        }
+    }
+
+    // A degenerate transform leaves nothing to draw
+    let linear = transform.fixed_slice::<3, 3>(0, 0).into_owned();
+    let normal_matrix = linear.try_inverse()?.transpose();
+    let mut vertices: Vec<Vertex> = positions
+        .into_iter()
+        .zip(normals)
+        .zip(tex_coords)
+        .map(|((position, normal), tex_coords)| Vertex {
+            position: transform.transform_point(&Point3::from(position)).coords.into(),
+            tex_coords,
+            normal: (normal_matrix * Vector3::from(normal)).try_normalize(0.).unwrap_or_else(Vector3::zeros).into(),
+            tangent: [0., 0., 0., 0.],
+        })
+        .collect();
+
+    let has_tangents = tangents.is_some();
+    for (vertex, [x, y, z, w]) in vertices.iter_mut().zip(tangents.unwrap_or_default()) {
+        let tangent = (linear * Vector3::new(x, y, z)).try_normalize(0.).unwrap_or_else(Vector3::zeros);
+        vertex.tangent = [tangent.x, tangent.y, tangent.z, if mirrored { -w } else { w }];
+    }
+
+    let mut mesh = Mesh {
+        vertices,
+        indices,
+        material: primitive.material().index(),
+        node: None,
+        joints: reader.read_joints(0).map(|joints| joints.into_u16().collect()).unwrap_or_default(),
+        weights: reader.read_weights(0).map(|weights| weights.into_f32().collect()).unwrap_or_default(),
+    };
+    if !has_normals {
+        generate_normals(&mut mesh, config.normal_mode);
+    }
+    if !has_tangents && has_tex_coords && config.generate_tangents {
+        generate_tangents(&mut mesh);
+    }
+
+    Some(mesh)
+}
+
+/// Replaces every normal of a triangle list, splitting vertices where faces meet at a hard edge.
+fn generate_normals(mesh: &mut Mesh, mode: NormalMode) {
+    let min_cos = match mode {
+        NormalMode::Flat => 1.,
+        NormalMode::Smooth { max_angle } => max_angle.cos(),
+    };
+
+    let vertices = &mesh.vertices;
+    let position = |index: u32| Vector3::from(vertices[index as usize].position);
+    // Vertices are matched by position so seams in the texture or material don't split the surface
+    let position_key = |index: u32| {
+        let [x, y, z] = vertices[index as usize].position;
+        [(x + 0.).to_bits(), (y + 0.).to_bits(), (z + 0.).to_bits()]
+    };
+
+    // Left unnormalized so larger faces weigh more
+    let face_normals: Vec<Vector3<f32>> = mesh
+        .indices
+        .chunks_exact(3)
+        .map(|triangle| {
+            let origin = position(triangle[0]);
+            (position(triangle[1]) - origin).cross(&(position(triangle[2]) - origin))
+        })
+        .collect();
+
+    let mut faces_at_position: HashMap<[u32; 3], Vec<usize>> = HashMap::new();
+    for (i, &index) in mesh.indices.iter().enumerate() {
+        faces_at_position.entry(position_key(index)).or_default().push(i / 3);
+    }
+
+    let mut new_vertices = vec![];
+    let mut new_joints = vec![];
+    let mut new_weights = vec![];
+    let mut remapped = HashMap::new();
+    let mut indices = Vec::with_capacity(mesh.indices.len());
+    for (i, &index) in mesh.indices.iter().enumerate() {
+        let face = i / 3;
+        let normal = match face_normals[face].try_normalize(0.) {
+            Some(face_normal) => faces_at_position[&position_key(index)]
+                .iter()
+                .filter(|&&other| {
+                    other == face
+                        || face_normals[other]
+                            .try_normalize(0.)
+                            .map_or(false, |other| other.dot(&face_normal) >= min_cos)
+                })
+                .map(|&other| face_normals[other])
+                .sum::<Vector3<f32>>()
+                .try_normalize(0.)
+                .unwrap_or(face_normal),
+            None => Vector3::zeros(),
+        };
+
+        let normal: [f32; 3] = normal.into();
+        let key = (index, [normal[0].to_bits(), normal[1].to_bits(), normal[2].to_bits()]);
+        indices.push(*remapped.entry(key).or_insert_with(|| {
+            new_vertices.push(Vertex { normal, ..vertices[index as usize] });
+            if !mesh.joints.is_empty() {
+                new_joints.push(mesh.joints[index as usize]);
+                new_weights.push(mesh.weights[index as usize]);
             }
 
-            meshes.push(Mesh { vertices, indices: model.mesh.indices });
+            new_vertices.len() as u32 - 1
+        }));
+    }
+
+    mesh.vertices = new_vertices;
+    mesh.indices = indices;
+    if !mesh.joints.is_empty() {
+        mesh.joints = new_joints;
+        mesh.weights = new_weights;
+    }
+}
+
+fn texture_from_gltf(image: &gltf::image::Data) -> Result<Texture> {
+    let (channels, wide) = match image.format {
+        Format::R8 => (1, false),
+        Format::R8G8 => (2, false),
+        Format::R8G8B8 | Format::B8G8R8 => (3, false),
+        Format::R8G8B8A8 | Format::B8G8R8A8 => (4, false),
+        Format::R16 => (1, true),
+        Format::R16G16 => (2, true),
+        Format::R16G16B16 => (3, true),
+        Format::R16G16B16A16 => (4, true),
+    };
+
+    // 16 bit channels are stored in native byte order
+    let pixels: Cow<[u8]> = if wide {
+        Cow::Owned(image.pixels.chunks_exact(2).map(|c| (u16::from_ne_bytes([c[0], c[1]]) >> 8) as u8).collect())
+    } else {
+        Cow::Borrowed(&image.pixels)
+    };
+
+    // One and two channel images are grayscale with optional alpha
+    let mut rgba: Vec<u8> = pixels
+        .chunks_exact(channels)
+        .flat_map(|pixel| match *pixel {
+            [l] => [l, l, l, 255],
+            [l, a] => [l, l, l, a],
+            [r, g, b] => [r, g, b, 255],
+            [r, g, b, a] => [r, g, b, a],
+            _ => unreachable!(),
+        })
+        .collect();
+
+    if let Format::B8G8R8 | Format::B8G8R8A8 = image.format {
+        for pixel in rgba.chunks_exact_mut(4) {
+            pixel.swap(0, 2);
+        }
+    }
+
+    Texture::from_rgba8(image.width, image.height, rgba)
+}
+
+fn sampler_from_gltf(sampler: &gltf::texture::Sampler) -> SamplerConfig {
+    let address_mode = |mode: WrappingMode| match mode {
+        WrappingMode::ClampToEdge => AddressMode::ClampToEdge,
+        WrappingMode::MirroredRepeat => AddressMode::MirrorRepeat,
+        WrappingMode::Repeat => AddressMode::Repeat,
+    };
+    let (min_filter, mipmap_filter) = match sampler.min_filter() {
+        Some(MinFilter::Nearest) | Some(MinFilter::NearestMipmapNearest) => (FilterMode::Nearest, FilterMode::Nearest),
+        Some(MinFilter::Linear) | Some(MinFilter::LinearMipmapNearest) => (FilterMode::Linear, FilterMode::Nearest),
+        Some(MinFilter::NearestMipmapLinear) => (FilterMode::Nearest, FilterMode::Linear),
+        Some(MinFilter::LinearMipmapLinear) | None => (FilterMode::Linear, FilterMode::Linear),
+    };
+
+    SamplerConfig {
+        mag_filter: match sampler.mag_filter() {
+            Some(MagFilter::Nearest) => FilterMode::Nearest,
+            _ => FilterMode::Linear,
+        },
+        min_filter,
+        mipmap_filter,
+        address_mode_u: address_mode(sampler.wrap_s()),
+        address_mode_v: address_mode(sampler.wrap_t()),
+        ..Default::default()
+    }
+}
+
+/// Computes MikkTSpace style tangents from the texture coordinate gradients of each triangle.
+/// Bitangents follow decreasing V, matching glTF's normal map convention.
+fn generate_tangents(mesh: &mut Mesh) {
+    let mut tangents = vec![Vector3::<f32>::zeros(); mesh.vertices.len()];
+    let mut bitangents = vec![Vector3::<f32>::zeros(); mesh.vertices.len()];
+    for triangle in mesh.indices.chunks_exact(3) {
+        let a = &mesh.vertices[triangle[0] as usize];
+        let b = &mesh.vertices[triangle[1] as usize];
+        let c = &mesh.vertices[triangle[2] as usize];
+        let edge1 = Vector3::from(b.position) - Vector3::from(a.position);
+        let edge2 = Vector3::from(c.position) - Vector3::from(a.position);
+        let (du1, dv1) = (b.tex_coords[0] - a.tex_coords[0], a.tex_coords[1] - b.tex_coords[1]);
+        let (du2, dv2) = (c.tex_coords[0] - a.tex_coords[0], a.tex_coords[1] - c.tex_coords[1]);
+
+        let determinant = du1 * dv2 - du2 * dv1;
+        if determinant.abs() <= f32::EPSILON {
+            continue;
+        }
+
+        let tangent = (edge1 * dv2 - edge2 * dv1) / determinant;
+        let bitangent = (edge2 * du1 - edge1 * du2) / determinant;
+        for &index in triangle {
+            tangents[index as usize] += tangent;
+            bitangents[index as usize] += bitangent;
+        }
+    }
+
+    for ((vertex, tangent), bitangent) in mesh.vertices.iter_mut().zip(tangents).zip(bitangents) {
+        let normal = Vector3::from(vertex.normal);
+        // Keep the tangent perpendicular to the normal, it was averaged across faces
+        vertex.tangent = match (tangent - normal * normal.dot(&tangent)).try_normalize(0.) {
+            Some(tangent) => {
+                let handedness = if normal.cross(&tangent).dot(&bitangent) < 0. { -1. } else { 1. };
+                [tangent.x, tangent.y, tangent.z, handedness]
+            },
+            None => [0., 0., 0., 0.],
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use wgpu::TextureFormat;
+
+    use super::*;
+    use crate::test_util::TempFile;
+
+    // A quad folded by about 23 degrees along the diagonal from (1, 0, 0) to (0, 1, 0), without normals or UVs
+    const FOLDED_QUAD_OBJ: &str = "v 0 0 0\nv 1 0 0\nv 0 1 0\nv 1 1 0.3\nf 1 2 3\nf 2 4 3\n";
+    // One triangle in the XY plane whose stored normals point away from its front face
+    const INVERTED_TRIANGLE_OBJ: &str = "v 0 0 0\nv 1 0 0\nv 0 1 0\nvn 0 0 -1\nf 1//1 2//1 3//1\n";
+    // The same triangle followed by the inverted normals, base64 encoded
+    const TRIANGLE_BUFFER: &str =
+        "AAAAAAAAAAAAAAAAAACAPwAAAAAAAAAAAAAAAAAAgD8AAAAAAAAAAAAAAAAAAIC/AAAAAAAAAAAAAIC/AAAAAAAAAAAAAIC/";
+    // A unit quad's positions, normals facing +Z, fan indices, then two keyframe times and translations
+    const QUAD_BUFFER: &str =
+        "AAAAAAAAAAAAAAAAAACAPwAAAAAAAAAAAAAAAAAAgD8AAAAAAACAPwAAgD8AAAAAAAAAAAAAAAAAAIA/AAAAAAAAAAAAAIA/AAAAAAAAAAAAAIA/AAAAAAAAAAAAAIA/AAABAAMAAgAAAAAAAACAPwAAAAAAAAAAAAAAAAAAAAAAAAAAAACgQA==";
+
+    fn load(name: &str, contents: &str, config: ModelConfig) -> Model {
+        let file = TempFile::with_contents(name, contents);
+        Model::load_with_config(file.path(), config).unwrap()
+    }
+
+    fn triangle_gltf(with_normals: bool) -> String {
+        let normal_attribute = if with_normals { r#", "NORMAL": 1"# } else { "" };
+        format!(
+            r#"{{
+                "asset": {{ "version": "2.0" }},
+                "scene": 0,
+                "scenes": [{{ "nodes": [0] }}],
+                "nodes": [{{ "mesh": 0 }}],
+                "meshes": [{{ "primitives": [{{ "attributes": {{ "POSITION": 0{} }} }}] }}],
+                "buffers": [{{ "byteLength": 72, "uri": "data:application/octet-stream;base64,{}" }}],
+                "bufferViews": [
+                    {{ "buffer": 0, "byteOffset": 0, "byteLength": 36 }},
+                    {{ "buffer": 0, "byteOffset": 36, "byteLength": 36 }}
+                ],
+                "accessors": [
+                    {{ "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3", "min": [0, 0, 0], "max": [1, 1, 0] }},
+                    {{ "bufferView": 1, "componentType": 5126, "count": 3, "type": "VEC3" }}
+                ]
+            }}"#,
+            normal_attribute, TRIANGLE_BUFFER
+        )
+    }
+
+    // A translated parent scaling a child drawn as a triangle strip, next to a mirrored root drawn as an indexed fan
+    fn hierarchy_gltf() -> String {
+        format!(
+            r#"{{
+                "asset": {{ "version": "2.0" }},
+                "scene": 0,
+                "scenes": [{{ "nodes": [0, 2] }}],
+                "nodes": [
+                    {{ "translation": [1, 0, 0], "children": [1] }},
+                    {{ "scale": [2, 2, 2], "mesh": 0 }},
+                    {{ "scale": [-1, 1, 1], "mesh": 1 }}
+                ],
+                "meshes": [
+                    {{ "primitives": [{{ "attributes": {{ "POSITION": 0, "NORMAL": 1 }}, "mode": 5 }}] }},
+                    {{ "primitives": [{{ "attributes": {{ "POSITION": 0, "NORMAL": 1 }}, "indices": 2, "mode": 6 }}] }}
+                ],
+                "animations": [{{
+                    "channels": [{{ "sampler": 0, "target": {{ "node": 1, "path": "translation" }} }}],
+                    "samplers": [{{ "input": 3, "output": 4, "interpolation": "STEP" }}]
+                }}],
+                "buffers": [{{ "byteLength": 136, "uri": "data:application/octet-stream;base64,{}" }}],
+                "bufferViews": [
+                    {{ "buffer": 0, "byteOffset": 0, "byteLength": 48 }},
+                    {{ "buffer": 0, "byteOffset": 48, "byteLength": 48 }},
+                    {{ "buffer": 0, "byteOffset": 96, "byteLength": 8 }},
+                    {{ "buffer": 0, "byteOffset": 104, "byteLength": 8 }},
+                    {{ "buffer": 0, "byteOffset": 112, "byteLength": 24 }}
+                ],
+                "accessors": [
+                    {{ "bufferView": 0, "componentType": 5126, "count": 4, "type": "VEC3", "min": [0, 0, 0], "max": [1, 1, 0] }},
+                    {{ "bufferView": 1, "componentType": 5126, "count": 4, "type": "VEC3" }},
+                    {{ "bufferView": 2, "componentType": 5123, "count": 4, "type": "SCALAR" }},
+                    {{ "bufferView": 3, "componentType": 5126, "count": 2, "type": "SCALAR", "min": [0], "max": [1] }},
+                    {{ "bufferView": 4, "componentType": 5126, "count": 2, "type": "VEC3" }}
+                ]
+            }}"#,
+            QUAD_BUFFER
+        )
+    }
+
+    fn mesh_of_node(model: &Model, node: usize) -> &Mesh {
+        model.meshes.iter().find(|mesh| mesh.node == Some(node)).unwrap()
+    }
+
+    fn assert_front_facing(mesh: &Mesh) {
+        for triangle in mesh.indices.chunks_exact(3) {
+            let position = |index: u32| Vector3::from(mesh.vertices[index as usize].position);
+            let origin = position(triangle[0]);
+            let normal = (position(triangle[1]) - origin).cross(&(position(triangle[2]) - origin));
+            assert!(normal.z > 0., "triangle {:?} faces away", triangle);
+        }
+    }
+
+    fn normals_at(mesh: &Mesh, position: [f32; 3]) -> Vec<Vector3<f32>> {
+        mesh.vertices.iter().filter(|vertex| vertex.position == position).map(|vertex| vertex.normal.into()).collect()
+    }
+
+    fn assert_close(actual: Vector3<f32>, expected: Vector3<f32>) {
+        assert!((actual - expected).norm() < 1e-5, "expected {:?}, got {:?}", expected, actual);
+    }
+
+    #[test]
+    fn obj_without_normals_is_smoothed_across_soft_edges() {
+        let model = load("folded_quad.obj", FOLDED_QUAD_OBJ, ModelConfig::default());
+        let mesh = &model.meshes[0];
+
+        assert_eq!(mesh.vertices.len(), 4);
+        assert!(mesh.vertices.iter().all(|vertex| vertex.tex_coords == [0., 0.]));
+        assert_close(normals_at(mesh, [0., 0., 0.])[0], Vector3::z());
+        assert_close(normals_at(mesh, [1., 1., 0.3])[0], Vector3::new(-0.3, -0.3, 1.).normalize());
+        // Shared vertices average the face normals, weighted by area
+        assert_close(normals_at(mesh, [1., 0., 0.])[0], Vector3::new(-0.3, -0.3, 2.).normalize());
+    }
+
+    #[test]
+    fn hard_edges_split_vertices() {
+        let config = ModelConfig { normal_mode: NormalMode::Smooth { max_angle: 0.1 }, ..Default::default() };
+        let smooth = load("hard_quad.obj", FOLDED_QUAD_OBJ, config);
+        let config = ModelConfig { normal_mode: NormalMode::Flat, ..Default::default() };
+        let flat = load("flat_quad.obj", FOLDED_QUAD_OBJ, config);
+
+        for mesh in &[&smooth.meshes[0], &flat.meshes[0]] {
+            assert_eq!(mesh.vertices.len(), 6);
+            let normals = normals_at(mesh, [1., 0., 0.]);
+            assert_eq!(normals.len(), 2);
+            assert_close(normals[0], Vector3::z());
+            assert_close(normals[1], Vector3::new(-0.3, -0.3, 1.).normalize());
         }
+    }
+
+    #[test]
+    fn obj_normals_are_kept_unless_recomputed() {
+        let model = load("inverted.obj", INVERTED_TRIANGLE_OBJ, ModelConfig::default());
+        assert!(model.meshes[0].vertices.iter().all(|vertex| vertex.normal == [0., 0., -1.]));
+
+        let config = ModelConfig { recompute_normals: true, ..Default::default() };
+        let model = load("recomputed.obj", INVERTED_TRIANGLE_OBJ, config);
+        assert!(model.meshes[0].vertices.iter().all(|vertex| vertex.normal == [0., 0., 1.]));
+    }
+
+    #[test]
+    fn gltf_without_normals_or_uvs_gets_generated_normals() {
+        let model = load("triangle.gltf", &triangle_gltf(false), ModelConfig::default());
+        let mesh = &model.meshes[0];
 
-        Ok(Model { meshes })
+        assert_eq!(mesh.indices, [0, 1, 2]);
+        assert!(mesh.vertices.iter().all(|vertex| vertex.tex_coords == [0., 0.]));
+        assert!(mesh.vertices.iter().all(|vertex| vertex.normal == [0., 0., 1.]));
+    }
+
+    #[test]
+    fn gltf_normals_are_kept_unless_recomputed() {
+        let model = load("inverted.gltf", &triangle_gltf(true), ModelConfig::default());
+        assert!(model.meshes[0].vertices.iter().all(|vertex| vertex.normal == [0., 0., -1.]));
+
+        let config = ModelConfig { recompute_normals: true, ..Default::default() };
+        let model = load("recomputed.gltf", &triangle_gltf(true), config);
+        assert!(model.meshes[0].vertices.iter().all(|vertex| vertex.normal == [0., 0., 1.]));
+    }
+
+    #[test]
+    fn gltf_node_transforms_are_baked_down_the_hierarchy() {
+        let model = load("hierarchy.gltf", &hierarchy_gltf(), ModelConfig::default());
+
+        assert_eq!(model.roots, [0, 2]);
+        assert_eq!(model.nodes[0].children, [1]);
+        assert_eq!(model.nodes[1].parent, Some(0));
+        assert_eq!(model.nodes[1].scale, [2., 2., 2.]);
+        let positions: Vec<[f32; 3]> = mesh_of_node(&model, 1).vertices.iter().map(|vertex| vertex.position).collect();
+        assert_eq!(positions, [[1., 0., 0.], [3., 0., 0.], [1., 2., 0.], [3., 2., 0.]]);
+    }
+
+    #[test]
+    fn gltf_strips_become_triangle_lists() {
+        let model = load("strip_and_fan.gltf", &hierarchy_gltf(), ModelConfig::default());
+        let strip = mesh_of_node(&model, 1);
+
+        // The second strip triangle swaps its first two vertices to keep the winding
+        assert_eq!(strip.indices, [0, 1, 2, 2, 1, 3]);
+        assert_front_facing(strip);
+    }
+
+    #[test]
+    fn gltf_mirrored_nodes_flip_winding() {
+        let model = load("mirrored.gltf", &hierarchy_gltf(), ModelConfig::default());
+        let fan = mesh_of_node(&model, 2);
+
+        // The fan [0, 1, 3], [0, 3, 2] with the last two vertices of each triangle swapped
+        assert_eq!(fan.indices, [0, 3, 1, 0, 2, 3]);
+        assert_eq!(fan.vertices[1].position, [-1., 0., 0.]);
+        assert_front_facing(fan);
+        assert!(fan.vertices.iter().all(|vertex| vertex.normal == [0., 0., 1.]));
+    }
+
+    #[test]
+    fn gltf_animation_channels_are_read() {
+        let model = load("animated.gltf", &hierarchy_gltf(), ModelConfig::default());
+        let channel = &model.animations[0].channels[0];
+
+        assert_eq!(channel.node, 1);
+        assert_eq!(channel.interpolation, Interpolation::Step);
+        assert_eq!(channel.inputs, [0., 1.]);
+        match &channel.outputs {
+            ChannelOutputs::Translations(translations) => assert_eq!(translations, &[[0., 0., 0.], [0., 0., 5.]]),
+            outputs => panic!("expected translations, got {:?}", outputs),
+        }
+    }
+
+    #[test]
+    fn gltf_images_shared_by_linear_slots_are_converted_once() {
+        // A packed occlusion, roughness and metalness image used by two slots, and as a base color
+        let gltf = r#"{
+            "asset": { "version": "2.0" },
+            "materials": [{
+                "pbrMetallicRoughness": { "baseColorTexture": { "index": 0 }, "metallicRoughnessTexture": { "index": 0 } },
+                "occlusionTexture": { "index": 0 }
+            }],
+            "textures": [{ "source": 0 }],
+            "images": [{ "uri": "data:image/png;base64,iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAIAAACQd1PeAAAADElEQVR4nGP438AAAAQBAYDFKhhdAAAAAElFTkSuQmCC" }]
+        }"#;
+        let model = load("packed.gltf", gltf, ModelConfig::default());
+        let material = &model.materials[0];
+        let occlusion = material.occlusion_texture.as_ref().unwrap();
+        let metallic_roughness = material.metallic_roughness_texture.as_ref().unwrap();
+
+        assert!(Arc::ptr_eq(&occlusion.data, &metallic_roughness.data));
+        assert_eq!(occlusion.data.format, TextureFormat::Rgba8Unorm);
+        assert_eq!(occlusion.data.levels[0], [255, 128, 0, 255]);
+        assert_eq!(material.diffuse_texture.as_ref().unwrap().data.format, TextureFormat::Rgba8UnormSrgb);
+    }
+
+    #[test]
+    fn gltf_images_are_converted_to_rgba8() {
+        let bgr = gltf::image::Data { pixels: vec![1, 2, 3], format: Format::B8G8R8, width: 1, height: 1 };
+        assert_eq!(texture_from_gltf(&bgr).unwrap().data.levels[0], [3, 2, 1, 255]);
+
+        // Only the high byte of each 16 bit channel is kept
+        let pixels = [0x1234u16, 0xabcd].iter().flat_map(|channel| channel.to_ne_bytes().to_vec()).collect();
+        let gray_alpha = gltf::image::Data { pixels, format: Format::R16G16, width: 1, height: 1 };
+        assert_eq!(texture_from_gltf(&gray_alpha).unwrap().data.levels[0], [0x12, 0x12, 0x12, 0xab]);
+    }
+
+    #[test]
+    fn split_vertices_keep_their_skinning() {
+        let vertex = |position: [f32; 3]| Vertex {
+            position,
+            tex_coords: [0., 0.],
+            normal: [0., 0., 0.],
+            tangent: [0., 0., 0., 0.],
+        };
+        let mut mesh = Mesh {
+            vertices: vec![vertex([0., 0., 0.]), vertex([1., 0., 0.]), vertex([0., 1., 0.]), vertex([1., 1., 0.3])],
+            indices: vec![0, 1, 2, 1, 3, 2],
+            material: None,
+            node: None,
+            joints: vec![[0, 0, 0, 0], [1, 0, 0, 0], [2, 0, 0, 0], [3, 0, 0, 0]],
+            weights: vec![[1., 0., 0., 0.]; 4],
+        };
+        generate_normals(&mut mesh, NormalMode::Flat);
+
+        assert_eq!(mesh.vertices.len(), 6);
+        assert_eq!(mesh.joints.len(), 6);
+        assert_eq!(mesh.weights.len(), 6);
+        for (&index, original) in mesh.indices.iter().zip(&[0, 1, 2, 1, 3, 2]) {
+            assert_eq!(mesh.joints[index as usize][0], *original);
+        }
     }
 }
@@ -18,6 +18,7 @@ pub use laminar::Config as NetworkConfig;
 pub use laminar::Packet;
 use laminar::SocketEvent;
 
+use crate::result::GearError;
 use crate::Result;
 
 #[derive(Clone, Debug)]
@@ -35,9 +36,9 @@ pub struct Socket {
 }
 
 impl Socket {
-    pub fn send(&self, packet: Packet) -> &Self {
-        self.sender.send(packet).unwrap();
-        self
+    pub fn send(&self, packet: Packet) -> Result<&Self> {
+        self.sender.send(packet).map_err(|_| GearError::SocketClosed)?;
+        Ok(self)
     }
 }
 
@@ -90,7 +91,9 @@ impl Network {
     }
 
     pub fn bind_with_config<A: ToSocketAddrs>(&mut self, addresses: A, config: NetworkConfig) -> Result<Socket> {
-        let mut socket = laminar::Socket::bind_with_config(addresses, config)?;
+        let addresses: Vec<SocketAddr> = addresses.to_socket_addrs()?.collect();
+        let mut socket = laminar::Socket::bind_with_config(&addresses[..], config)
+            .map_err(|e| GearError::NetworkBindFailed { addresses: addresses.clone(), source: Box::new(e.into()) })?;
         let sender = socket.get_packet_sender();
         let receiver = socket.get_event_receiver();
         let stop_signal = Arc::new(AtomicBool::new(false));
@@ -0,0 +1,75 @@
+// Appended to shader.wgsl
+
+fn distribution_ggx(n_dot_h: f32, roughness: f32) -> f32 {
+    let a = roughness * roughness;
+    let a2 = a * a;
+    let d = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+    return a2 / (3.14159265 * d * d);
+}
+
+fn geometry_smith(n_dot_v: f32, n_dot_l: f32, roughness: f32) -> f32 {
+    let r = roughness + 1.0;
+    let k = r * r / 8.0;
+    return n_dot_v / (n_dot_v * (1.0 - k) + k) * n_dot_l / (n_dot_l * (1.0 - k) + k);
+}
+
+fn fresnel_schlick(cos_theta: f32, f0: vec3<f32>, roughness: f32) -> vec3<f32> {
+    let smoothness = 1.0 - roughness;
+    let f90 = max(vec3<f32>(smoothness, smoothness, smoothness), f0);
+    return f0 + (f90 - f0) * pow(clamp(1.0 - cos_theta, 0.0, 1.0), 5.0);
+}
+
+[[stage(fragment)]]
+fn main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let base_color = textureSample(diffuse_texture, diffuse_sampler, in.tex_coord) * uniforms.diffuse;
+    let metallic_roughness = textureSample(metallic_roughness_texture, metallic_roughness_sampler, in.tex_coord);
+    let metallic = clamp(uniforms.metallic_roughness.x * metallic_roughness.b, 0.0, 1.0);
+    let roughness = clamp(uniforms.metallic_roughness.y * metallic_roughness.g, 0.04, 1.0);
+    let occlusion = textureSample(occlusion_texture, occlusion_sampler, in.tex_coord).r;
+    let emissive = textureSample(emissive_texture, emissive_sampler, in.tex_coord).rgb * uniforms.emissive.rgb;
+
+    let normal = surface_normal(in);
+    let view_dir = normalize(uniforms.camera_position.xyz - in.world_pos);
+    let n_dot_v = max(dot(normal, view_dir), 0.0001);
+    // Dielectrics reflect about 4% head on, metals tint reflections with their base color
+    let f0 = mix(vec3<f32>(0.04, 0.04, 0.04), base_color.rgb, vec3<f32>(metallic, metallic, metallic));
+    let diffuse_color = base_color.rgb * (1.0 - metallic);
+
+    var color: vec3<f32> = vec3<f32>(0.0, 0.0, 0.0);
+    var i: u32 = 0u;
+    loop {
+        if (i >= lights.count) {
+            break;
+        }
+
+        let incidence = light_incidence(i, in.world_pos);
+        let light_dir = incidence.xyz;
+        let n_dot_l = max(dot(normal, light_dir), 0.0);
+        if (n_dot_l > 0.0) {
+            let half_dir = normalize(light_dir + view_dir);
+            let n_dot_h = max(dot(normal, half_dir), 0.0);
+            let fresnel = fresnel_schlick(max(dot(half_dir, view_dir), 0.0), f0, 0.0);
+            let specular = fresnel * distribution_ggx(n_dot_h, roughness) * geometry_smith(n_dot_v, n_dot_l, roughness)
+                / (4.0 * n_dot_v * n_dot_l);
+            let diffuse = (vec3<f32>(1.0, 1.0, 1.0) - fresnel) * diffuse_color / 3.14159265;
+            color = color + (diffuse + specular) * lights.lights[i].color.rgb * incidence.w * n_dot_l;
+        }
+
+        continuing {
+            i = i + 1u;
+        }
+    }
+
+    // Image based lighting with the split sum approximation, scaled by the ambient light. The maximum level is
+    // `PREFILTERED_MIP_COUNT - 1`
+    let fresnel = fresnel_schlick(n_dot_v, f0, roughness);
+    let irradiance = textureSample(irradiance_map, environment_sampler, normal).rgb;
+    let reflected = reflect(-view_dir, normal);
+    let prefiltered = textureSampleLevel(prefiltered_map, environment_sampler, reflected, roughness * 4.0).rgb;
+    let brdf = textureSample(brdf_lut, environment_sampler, vec2<f32>(n_dot_v, roughness)).rg;
+    let diffuse = (vec3<f32>(1.0, 1.0, 1.0) - fresnel) * diffuse_color * irradiance;
+    let specular = prefiltered * (fresnel * brdf.x + vec3<f32>(brdf.y, brdf.y, brdf.y));
+    color = color + (diffuse + specular) * lights.ambient.rgb * occlusion + emissive;
+
+    return vec4<f32>(color, base_color.a);
+}
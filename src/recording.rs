@@ -0,0 +1,147 @@
+// Copyright 2021 Chay Nabors.
+
+use std::fs::File;
+use std::io::BufReader;
+use std::io::BufWriter;
+use std::io::ErrorKind;
+use std::io::Write;
+use std::path::Path;
+use std::time::Duration;
+
+use log::error;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::input::InputEvent;
+use crate::result::GearError;
+use crate::result::Result;
+use crate::result::WithPath;
+
+const MAGIC: [u8; 4] = *b"GREC";
+const VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize)]
+struct Header {
+    magic: [u8; 4],
+    version: u32,
+}
+
+/// Every input event received during a single frame, followed by the frame's
+/// `delta_time`.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct RecordedFrame {
+    pub events: Vec<InputEvent>,
+    pub delta_time: Duration,
+}
+
+#[derive(Debug)]
+pub(crate) struct InputRecorder {
+    writer: BufWriter<File>,
+    events: Vec<InputEvent>,
+}
+
+impl InputRecorder {
+    pub fn create<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let mut writer = BufWriter::new(File::create(&path).with_path(&path)?);
+        bincode::serialize_into(&mut writer, &Header { magic: MAGIC, version: VERSION }).with_path(&path)?;
+
+        Ok(Self { writer, events: vec![] })
+    }
+
+    pub fn record_event(&mut self, event: &InputEvent) {
+        self.events.push(event.clone());
+    }
+
+    pub fn end_frame(&mut self, delta_time: Duration) -> Result<()> {
+        let frame = RecordedFrame { events: std::mem::take(&mut self.events), delta_time };
+        Ok(bincode::serialize_into(&mut self.writer, &frame)?)
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        Ok(self.writer.flush()?)
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct InputPlayback {
+    reader: BufReader<File>,
+}
+
+impl InputPlayback {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let mut reader = BufReader::new(File::open(&path).with_path(&path)?);
+        let header: Header = bincode::deserialize_from(&mut reader).with_path(&path)?;
+        if header.magic != MAGIC || header.version != VERSION {
+            return Err(GearError::UnsupportedFormat).with_path(&path);
+        }
+
+        Ok(Self { reader })
+    }
+
+    /// Returns `None` once the recording is exhausted.
+    pub fn next_frame(&mut self) -> Option<RecordedFrame> {
+        match bincode::deserialize_from(&mut self.reader) {
+            Ok(frame) => Some(frame),
+            Err(e) => {
+                if !matches!(e.as_ref(), bincode::ErrorKind::Io(e) if e.kind() == ErrorKind::UnexpectedEof) {
+                    error!("Failed to read input recording: {}", e);
+                }
+                None
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::ImeEvent;
+    use crate::input::MouseEvent;
+    use crate::test_util::TempFile;
+
+    #[test]
+    fn frames_round_trip() {
+        let file = TempFile::new("round_trip.rec");
+        let mut recorder = InputRecorder::create(file.path()).unwrap();
+        recorder.record_event(&InputEvent::MouseEvent(MouseEvent::CursorMoved([12., -3.5])));
+        recorder.record_event(&InputEvent::Text('x'));
+        recorder.end_frame(Duration::from_millis(16)).unwrap();
+        recorder.end_frame(Duration::from_millis(17)).unwrap();
+        recorder.flush().unwrap();
+
+        let mut playback = InputPlayback::open(file.path()).unwrap();
+        let frame = playback.next_frame().unwrap();
+        assert_eq!(frame.delta_time, Duration::from_millis(16));
+        assert!(matches!(
+            frame.events[..],
+            [InputEvent::MouseEvent(MouseEvent::CursorMoved([x, y])), InputEvent::Text('x')] if x == 12. && y == -3.5
+        ));
+        let frame = playback.next_frame().unwrap();
+        assert_eq!(frame.delta_time, Duration::from_millis(17));
+        assert!(frame.events.is_empty());
+        assert!(playback.next_frame().is_none());
+    }
+
+    #[test]
+    fn composition_events_round_trip() {
+        let file = TempFile::new("composition.rec");
+        let mut recorder = InputRecorder::create(file.path()).unwrap();
+        recorder.record_event(&InputEvent::ImeEvent(ImeEvent::Preedit("a b".to_string(), Some((3, 3)))));
+        recorder.record_event(&InputEvent::ImeEvent(ImeEvent::Commit("啊不".to_string())));
+        recorder.end_frame(Duration::from_millis(16)).unwrap();
+        recorder.flush().unwrap();
+
+        let frame = InputPlayback::open(file.path()).unwrap().next_frame().unwrap();
+        assert!(matches!(
+            &frame.events[..],
+            [InputEvent::ImeEvent(ImeEvent::Preedit(preedit, Some((3, 3)))), InputEvent::ImeEvent(ImeEvent::Commit(commit))]
+                if preedit == "a b" && commit == "啊不"
+        ));
+    }
+
+    #[test]
+    fn foreign_files_are_rejected() {
+        let file = TempFile::with_contents("foreign.rec", b"not a recording");
+        assert!(InputPlayback::open(file.path()).is_err());
+    }
+}
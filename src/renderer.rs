@@ -1,9 +1,10 @@
 // Copyright 2021 Chay Nabors.
 
 use std::borrow::Cow;
+use std::collections::HashMap;
 use std::ops::Range;
-use std::sync::Weak;
 
+use bytemuck::Pod;
 use bytemuck::Zeroable;
 use log::error;
 use log::info;
@@ -12,6 +13,7 @@ use nalgebra::Matrix4;
 use nalgebra::Point3;
 use nalgebra::Translation3;
 use nalgebra::UnitQuaternion;
+use nalgebra::Vector3;
 use wgpu::vertex_attr_array;
 use wgpu::Adapter;
 use wgpu::BackendBit;
@@ -25,10 +27,8 @@ use wgpu::BindingResource;
 use wgpu::BindingType;
 use wgpu::BlendState;
 use wgpu::Buffer;
-use wgpu::BufferAddress;
 use wgpu::BufferBinding;
 use wgpu::BufferBindingType;
-use wgpu::BufferDescriptor;
 use wgpu::BufferUsage;
 use wgpu::Color;
 use wgpu::ColorTargetState;
@@ -60,12 +60,14 @@ use wgpu::PresentMode;
 use wgpu::PrimitiveState;
 use wgpu::PrimitiveTopology;
 use wgpu::Queue;
+use wgpu::RenderPass;
 use wgpu::RenderPassColorAttachment;
 use wgpu::RenderPassDepthStencilAttachment;
 use wgpu::RenderPassDescriptor;
 use wgpu::RenderPipeline;
 use wgpu::RenderPipelineDescriptor;
 use wgpu::RequestAdapterOptions;
+use wgpu::Sampler;
 use wgpu::ShaderFlags;
 use wgpu::ShaderModule;
 use wgpu::ShaderModuleDescriptor;
@@ -79,36 +81,146 @@ use wgpu::Texture;
 use wgpu::TextureDescriptor;
 use wgpu::TextureDimension;
 use wgpu::TextureFormat;
+use wgpu::TextureSampleType;
 use wgpu::TextureUsage;
 use wgpu::TextureView;
 use wgpu::TextureViewDescriptor;
+use wgpu::TextureViewDimension;
 use wgpu::VertexBufferLayout;
 use wgpu::VertexState;
 use wgpu::BIND_BUFFER_ALIGNMENT;
 
+use crate::buffer::GrowableBuffer;
+use crate::buffer::MAX_BUFFER_SIZE;
+use crate::environment::EnvironmentMaps;
+use crate::light::DirectionalLight;
+use crate::light::LightData;
+use crate::light::LightsHeader;
+use crate::light::PointLight;
+use crate::light::SpotLight;
+use crate::model::GpuModel;
+use crate::model::ModelHandle;
 use crate::model::Vertex;
+use crate::result::GearError;
+use crate::result::Result;
+use crate::shadow::cascade_splits;
+use crate::shadow::ShadowCaster;
+use crate::shadow::ShadowMaps;
+use crate::shadow::ShadowUniforms;
+use crate::shadow::ViewFrustum;
+use crate::shadow::MAX_SHADOW_MAPS;
+use crate::texture::create_sampler;
+use crate::texture::create_texture_bind_group;
+use crate::texture::GpuTexture;
+use crate::texture::MipGenerator;
+use crate::texture::SamplerConfig;
 use crate::Window;
 
-const VERTEX_BUFFER_SIZE: u64 = 32000000;
-const INDEX_BUFFER_SIZE: u64 = 32000000;
-const MAX_UNIFORM_COUNT: u64 = 1 << 20;
 const TEXTURE_FORMAT: TextureFormat = TextureFormat::Bgra8UnormSrgb;
 const DEPTH_TEXTURE_FORMAT: TextureFormat = TextureFormat::Depth32Float;
+const INITIAL_LIGHT_CAPACITY: usize = 64;
+/// MSAA sample counts every adapter supports for the swap chain and depth formats.
+pub(crate) const SUPPORTED_SAMPLE_COUNTS: [u32; 2] = [1, 4];
 
-#[repr(C, align(256))]
-#[derive(Copy, Clone, Debug, Zeroable)]
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
 struct Uniforms {
-    mvp: [[f32; 4]; 4],
+    view_projection: [[f32; 4]; 4],
+    /// Diffuse color with the material's dissolve as alpha.
+    diffuse: [f32; 4],
+    /// Specular color with the material's shininess as `w`.
+    specular: [f32; 4],
+    camera_position: [f32; 4],
+    emissive: [f32; 4],
+    /// Metalness and roughness factors in the first two components.
+    metallic_roughness: [f32; 4],
+    receive_shadows: u32,
+    /// Rounds the size up to the shader's 16 byte struct alignment.
+    _padding: [u32; 3],
+}
+
+/// How lit surfaces are shaded.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ShadingModel {
+    /// Diffuse and specular colors with shininess. Frames without lights are drawn unlit.
+    BlinnPhong,
+    /// The glTF metallic-roughness model, lit by the scene's lights and image based lighting from the environment.
+    Pbr,
+}
+
+impl Default for ShadingModel {
+    fn default() -> Self {
+        ShadingModel::BlinnPhong
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+pub(crate) struct InstanceTransform {
+    model: [[f32; 4]; 4],
 }
 
 #[derive(Debug)]
 struct DrawCall {
     base_vertex: i32,
     indices: Range<u32>,
+    texture: Option<(u64, SamplerConfig)>,
+    maps: MaterialMaps,
+    /// Model id and mesh index of an uploaded mesh, otherwise the draw reads the per frame buffers.
+    mesh: Option<(u64, usize)>,
+    uniform_index: usize,
+    instances: Range<u32>,
+    casts_shadows: bool,
+}
+
+/// The textures bound alongside the diffuse map, missing ones fall back to defaults.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+struct MaterialMaps {
+    normal: Option<(u64, SamplerConfig)>,
+    metallic_roughness: Option<(u64, SamplerConfig)>,
+    occlusion: Option<(u64, SamplerConfig)>,
+    emissive: Option<(u64, SamplerConfig)>,
+}
+
+impl MaterialMaps {
+    fn maps(&self) -> [Option<(u64, SamplerConfig)>; 4] {
+        [self.normal, self.metallic_roughness, self.occlusion, self.emissive]
+    }
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct RendererConfig {
+    pub backends: BackendBit,
+    pub power_preference: PowerPreference,
+    pub present_mode: PresentMode,
+    pub sample_count: u32,
+    /// Initial buffer sizes in bytes, each buffer doubles whenever a frame needs more.
+    pub vertex_buffer_size: u64,
+    pub index_buffer_size: u64,
+    pub instance_buffer_size: u64,
+    pub uniform_buffer_size: u64,
+    /// Width and height of each shadow map layer. Layers are allocated once shadow casting lights need them.
+    pub shadow_map_size: u32,
+}
+
+impl Default for RendererConfig {
+    fn default() -> Self {
+        Self {
+            backends: BackendBit::all(),
+            power_preference: PowerPreference::HighPerformance,
+            present_mode: PresentMode::Fifo,
+            sample_count: 1,
+            vertex_buffer_size: 1 << 22,
+            index_buffer_size: 1 << 21,
+            instance_buffer_size: 1 << 20,
+            uniform_buffer_size: 1 << 20,
+            shadow_map_size: 2048,
+        }
+    }
 }
 
 #[derive(Debug)]
-pub struct Renderer {
+struct Backend {
     _instance: Instance,
     surface: Surface,
     _adapter: Adapter,
@@ -116,40 +228,166 @@ pub struct Renderer {
     queue: Queue,
     swap_chain_descriptor: SwapChainDescriptor,
     swap_chain: SwapChain,
+    present_mode: PresentMode,
+    sample_count: u32,
+    texture_compression_bc: bool,
 
-    vertex_buffer: Buffer,
-    index_buffer: Buffer,
-    uniform_buffer: Buffer,
-    _uniform_bind_group_layout: BindGroupLayout,
+    vertex_buffer: GrowableBuffer,
+    index_buffer: GrowableBuffer,
+    instance_buffer: GrowableBuffer,
+    uniform_buffer: GrowableBuffer,
+    uniform_bind_group_layout: BindGroupLayout,
     uniform_bind_group: BindGroup,
+    light_buffer: GrowableBuffer,
+    light_bind_group_layout: BindGroupLayout,
+    light_bind_group: BindGroup,
+    shadow_maps: ShadowMaps,
+    environment: EnvironmentMaps,
+    texture_bind_group_layout: BindGroupLayout,
+    material_bind_group_layout: BindGroupLayout,
+    samplers: HashMap<SamplerConfig, Sampler>,
+    mip_generator: MipGenerator,
+    default_texture: GpuTexture,
+    default_texture_bind_group: BindGroup,
+    default_normal_texture: GpuTexture,
+    textures: HashMap<u64, GpuTexture>,
+    material_bind_groups: HashMap<MaterialMaps, BindGroup>,
+    models: HashMap<u64, GpuModel>,
 
     depth_texture: Texture,
     depth_texture_view: TextureView,
+    msaa_texture: Option<Texture>,
+    msaa_texture_view: Option<TextureView>,
     _shader_module: ShaderModule,
+    _pbr_shader_module: ShaderModule,
     _pipeline_layout: PipelineLayout,
     pipeline: RenderPipeline,
+    pbr_pipeline: RenderPipeline,
+}
+
+#[derive(Debug)]
+pub struct Renderer {
+    backend: Option<Backend>,
 
     clear_color: [f64; 4],
     view: Isometry3<f32>,
     projection: Matrix4<f32>,
-    _bound_texture: Option<Weak<crate::Texture>>,
+    bound_texture: Option<(u64, SamplerConfig)>,
     draw_calls: Vec<Vec<DrawCall>>,
     vertex_data: Vec<Vertex>,
     index_data: Vec<u32>,
+    instance_data: Vec<InstanceTransform>,
     uniform_data: Vec<Uniforms>,
+    ambient_light: [f32; 3],
+    lights: Vec<LightData>,
+    shadow_casters: Vec<(usize, ShadowCaster)>,
+    shadow_distance: f32,
+    casts_shadows: bool,
+    receives_shadows: bool,
+    shading_model: ShadingModel,
+}
+
+impl Backend {
+    /// Grows the per frame buffers and the shadow maps to fit this frame's data.
+    fn reserve_buffers(
+        &mut self,
+        vertices: &[u8],
+        indices: &[u8],
+        instances: &[u8],
+        uniforms: &[u8],
+        lights: &[u8],
+        shadow_layers: usize,
+    ) -> Result<()> {
+        self.vertex_buffer.reserve(&self.device, vertices.len() as u64)?;
+        self.index_buffer.reserve(&self.device, indices.len() as u64)?;
+        self.instance_buffer.reserve(&self.device, instances.len() as u64)?;
+        if self.uniform_buffer.reserve(&self.device, uniforms.len() as u64)? {
+            self.uniform_bind_group =
+                create_uniform_bind_group(&self.device, &self.uniform_bind_group_layout, self.uniform_buffer.buffer());
+        }
+        let lights_grown = self.light_buffer.reserve(&self.device, lights.len() as u64)?;
+        let shadow_maps_grown = self.shadow_maps.reserve(&self.device, shadow_layers);
+        if lights_grown || shadow_maps_grown {
+            self.light_bind_group = create_light_bind_group(
+                &self.device,
+                &self.light_bind_group_layout,
+                self.light_buffer.buffer(),
+                &self.shadow_maps,
+                &self.environment,
+            );
+        }
+
+        Ok(())
+    }
+
+    /// The view and sampler a material map is bound with, falling back to `default` for missing maps.
+    fn map_binding<'a>(
+        &'a self,
+        map: Option<(u64, SamplerConfig)>,
+        default: &'a GpuTexture,
+    ) -> (&'a TextureView, &'a Sampler) {
+        map.and_then(|(id, sampler)| Some((&self.textures.get(&id)?.view, self.samplers.get(&sampler)?)))
+            .unwrap_or_else(|| (&default.view, &self.samplers[&SamplerConfig::default()]))
+    }
+
+    /// Creates the bind group for a combination of material maps if it doesn't exist yet.
+    fn prepare_material_bind_group(&mut self, maps: MaterialMaps) {
+        if self.material_bind_groups.contains_key(&maps) {
+            return;
+        }
+
+        let bind_group = create_material_bind_group(
+            &self.device,
+            &self.material_bind_group_layout,
+            &[
+                self.map_binding(maps.normal, &self.default_normal_texture),
+                self.map_binding(maps.metallic_roughness, &self.default_texture),
+                self.map_binding(maps.occlusion, &self.default_texture),
+                self.map_binding(maps.emissive, &self.default_texture),
+            ],
+        );
+        self.material_bind_groups.insert(maps, bind_group);
+    }
+
+    /// Binds the buffers `mesh` is drawn from, returning false if its model was freed by `free_model` after the
+    /// draw was recorded.
+    fn bind_mesh<'a>(
+        &'a self,
+        render_pass: &mut RenderPass<'a>,
+        mesh: Option<(u64, usize)>,
+        vertex_size: u64,
+        index_size: u64,
+    ) -> bool {
+        match mesh {
+            Some((id, index)) => match self.models.get(&id) {
+                Some(model) => {
+                    let gpu_mesh = &model.meshes[index];
+                    render_pass.set_vertex_buffer(0, gpu_mesh.vertex_buffer.slice(..));
+                    render_pass.set_index_buffer(gpu_mesh.index_buffer.slice(..), IndexFormat::Uint32);
+                },
+                None => return false,
+            },
+            None => {
+                render_pass.set_vertex_buffer(0, self.vertex_buffer.buffer().slice(0..vertex_size));
+                render_pass.set_index_buffer(self.index_buffer.buffer().slice(0..index_size), IndexFormat::Uint32);
+            },
+        }
+
+        true
+    }
 }
 
 impl Renderer {
-    pub(crate) async fn new(window: &Window) -> Option<Renderer> {
+    pub(crate) async fn new(window: &Window, config: &RendererConfig) -> Result<Renderer> {
         info!("Initializing rendering backend");
 
-        let instance = Instance::new(BackendBit::all());
+        let instance = Instance::new(config.backends);
 
         let surface = unsafe { instance.create_surface(window) };
 
         let adapter = match instance
             .request_adapter(&RequestAdapterOptions {
-                power_preference: PowerPreference::HighPerformance,
+                power_preference: config.power_preference,
                 compatible_surface: Some(&surface),
             })
             .await
@@ -157,54 +395,62 @@ impl Renderer {
             Some(adapter) => adapter,
             None => {
                 error!("Failed to find any suitable graphics adapter");
-                return None;
+                return Err(GearError::AdapterUnavailable);
             },
         };
 
+        // Compressed textures are decoded on the CPU when the adapter can't sample them
+        let features = adapter.features() & Features::TEXTURE_COMPRESSION_BC;
+        if features.is_empty() {
+            info!("BC texture compression is unsupported, compressed textures will be decoded on upload");
+        }
+
         let (device, queue) = match adapter
-            .request_device(
-                &DeviceDescriptor { label: Some("device"), features: Features::empty(), limits: Limits::default() },
-                None,
-            )
+            .request_device(&DeviceDescriptor { label: Some("device"), features, limits: Limits::default() }, None)
             .await
         {
             Ok(dq) => dq,
             Err(e) => {
                 error!("Failed to acquire a graphics device: {}", e);
-                return None;
+                return Err(e.into());
             },
         };
 
         let window_size = window.size();
 
-        let (swap_chain_descriptor, swap_chain) = create_swap_chain(&device, &surface, window_size);
+        let (swap_chain_descriptor, swap_chain) =
+            create_swap_chain(&device, &surface, window_size, config.present_mode);
 
-        let vertex_buffer = device.create_buffer(&BufferDescriptor {
-            label: Some("vertex_buffer"),
-            size: VERTEX_BUFFER_SIZE,
-            usage: BufferUsage::VERTEX | BufferUsage::COPY_DST,
-            mapped_at_creation: false,
-        });
-
-        let index_buffer = device.create_buffer(&BufferDescriptor {
-            label: Some("index_buffer"),
-            size: INDEX_BUFFER_SIZE,
-            usage: BufferUsage::INDEX | BufferUsage::COPY_DST,
-            mapped_at_creation: false,
-        });
-
-        let uniform_buffer = device.create_buffer(&BufferDescriptor {
-            label: Some("uniform_buffer"),
-            size: MAX_UNIFORM_COUNT as BufferAddress * BIND_BUFFER_ALIGNMENT,
-            usage: BufferUsage::UNIFORM | BufferUsage::COPY_DST,
-            mapped_at_creation: false,
-        });
+        let vertex_buffer = GrowableBuffer::new(
+            &device,
+            "vertex_buffer",
+            BufferUsage::VERTEX,
+            config.vertex_buffer_size,
+            MAX_BUFFER_SIZE,
+        );
+        let index_buffer =
+            GrowableBuffer::new(&device, "index_buffer", BufferUsage::INDEX, config.index_buffer_size, MAX_BUFFER_SIZE);
+        let instance_buffer = GrowableBuffer::new(
+            &device,
+            "instance_buffer",
+            BufferUsage::VERTEX,
+            config.instance_buffer_size,
+            MAX_BUFFER_SIZE,
+        );
+        // Each draw's uniforms sit at their own dynamic offset
+        let uniform_buffer = GrowableBuffer::new(
+            &device,
+            "uniform_buffer",
+            BufferUsage::UNIFORM,
+            config.uniform_buffer_size.max(BIND_BUFFER_ALIGNMENT),
+            MAX_BUFFER_SIZE,
+        );
 
         let uniform_bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
             label: None,
             entries: &[BindGroupLayoutEntry {
                 binding: 0,
-                visibility: ShaderStage::VERTEX,
+                visibility: ShaderStage::VERTEX | ShaderStage::FRAGMENT,
                 ty: BindingType::Buffer {
                     ty: BufferBindingType::Uniform,
                     has_dynamic_offset: true,
@@ -214,78 +460,226 @@ impl Renderer {
             }],
         });
 
-        let uniform_bind_group = device.create_bind_group(&BindGroupDescriptor {
-            layout: &uniform_bind_group_layout,
-            entries: &[BindGroupEntry {
-                binding: 0,
-                resource: BindingResource::Buffer(BufferBinding {
-                    buffer: &uniform_buffer,
-                    offset: 0,
-                    size: wgpu::BufferSize::new(std::mem::size_of::<Uniforms>() as _),
-                }),
-            }],
-            label: None,
+        let uniform_bind_group =
+            create_uniform_bind_group(&device, &uniform_bind_group_layout, uniform_buffer.buffer());
+
+        let light_buffer = GrowableBuffer::new(
+            &device,
+            "light_buffer",
+            BufferUsage::STORAGE,
+            (std::mem::size_of::<LightsHeader>() + INITIAL_LIGHT_CAPACITY * std::mem::size_of::<LightData>()) as u64,
+            // Bound whole, so it can't outgrow the binding limit
+            device.limits().max_storage_buffer_binding_size as wgpu::BufferAddress,
+        );
+
+        let shadow_maps = ShadowMaps::new(&device, config.shadow_map_size);
+        let environment = EnvironmentMaps::new(&device, &queue);
+
+        // Lights along with the shadow maps they cast and the environment
+        let light_bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("light_bind_group_layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStage::FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: wgpu::BufferSize::new(std::mem::size_of::<LightsHeader>() as _),
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStage::FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: wgpu::BufferSize::new(std::mem::size_of::<ShadowUniforms>() as _),
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStage::FRAGMENT,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Depth,
+                        view_dimension: TextureViewDimension::D2Array,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: ShaderStage::FRAGMENT,
+                    ty: BindingType::Sampler { filtering: true, comparison: true },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 4,
+                    visibility: ShaderStage::FRAGMENT,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: true },
+                        view_dimension: TextureViewDimension::Cube,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 5,
+                    visibility: ShaderStage::FRAGMENT,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: true },
+                        view_dimension: TextureViewDimension::Cube,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 6,
+                    visibility: ShaderStage::FRAGMENT,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: true },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 7,
+                    visibility: ShaderStage::FRAGMENT,
+                    ty: BindingType::Sampler { filtering: true, comparison: false },
+                    count: None,
+                },
+            ],
         });
 
-        let (depth_texture, depth_texture_view) = create_depth_texture(&device, window_size);
+        let light_bind_group = create_light_bind_group(
+            &device,
+            &light_bind_group_layout,
+            light_buffer.buffer(),
+            &shadow_maps,
+            &environment,
+        );
 
+        let texture_bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("texture_bind_group_layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStage::FRAGMENT,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: true },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStage::FRAGMENT,
+                    ty: BindingType::Sampler { filtering: true, comparison: false },
+                    count: None,
+                },
+            ],
+        });
+
+        // Normal, metallic-roughness, occlusion and emissive maps, each followed by its sampler
+        let material_bind_group_layout_entries = (0..4)
+            .flat_map(|map| {
+                vec![
+                    BindGroupLayoutEntry {
+                        binding: map * 2,
+                        visibility: ShaderStage::FRAGMENT,
+                        ty: BindingType::Texture {
+                            sample_type: TextureSampleType::Float { filterable: true },
+                            view_dimension: TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    BindGroupLayoutEntry {
+                        binding: map * 2 + 1,
+                        visibility: ShaderStage::FRAGMENT,
+                        ty: BindingType::Sampler { filtering: true, comparison: false },
+                        count: None,
+                    },
+                ]
+            })
+            .collect::<Vec<_>>();
+        let material_bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("material_bind_group_layout"),
+            entries: &material_bind_group_layout_entries,
+        });
+
+        let mip_generator = MipGenerator::new(&device);
+
+        let mut samplers = HashMap::new();
+        let default_sampler = samplers
+            .entry(SamplerConfig::default())
+            .or_insert_with(|| create_sampler(&device, &SamplerConfig::default()));
+
+        // Untextured draws sample plain white
+        let white = crate::Texture::from_rgba8(1, 1, vec![255; 4])?;
+        let default_texture = GpuTexture::upload(&device, &queue, &mip_generator, false, &white.data);
+        let default_texture_bind_group =
+            create_texture_bind_group(&device, &texture_bind_group_layout, &default_texture.view, default_sampler);
+
+        // Meshes without a normal map sample a flat one, other missing maps sample white
+        let flat = crate::Texture::from_rgba8(1, 1, vec![128, 128, 255, 255])?.into_linear();
+        let default_normal_texture = GpuTexture::upload(&device, &queue, &mip_generator, false, &flat.data);
+
+        let (depth_texture, depth_texture_view) = create_depth_texture(&device, window_size, config.sample_count);
+        let (msaa_texture, msaa_texture_view) = create_msaa_texture(&device, window_size, config.sample_count);
+
+        // Both shading models share the vertex stage and lighting helpers in `shader.wgsl`
         let shader_module = device.create_shader_module(&ShaderModuleDescriptor {
             label: Some("shader"),
-            source: ShaderSource::Wgsl(Cow::Borrowed(include_str!("shader.wgsl"))),
+            source: ShaderSource::Wgsl(Cow::Borrowed(concat!(
+                include_str!("shader.wgsl"),
+                "\n",
+                include_str!("blinn_phong.wgsl")
+            ))),
+            flags: ShaderFlags::VALIDATION,
+        });
+
+        let pbr_shader_module = device.create_shader_module(&ShaderModuleDescriptor {
+            label: Some("pbr_shader"),
+            source: ShaderSource::Wgsl(Cow::Borrowed(concat!(
+                include_str!("shader.wgsl"),
+                "\n",
+                include_str!("pbr.wgsl")
+            ))),
             flags: ShaderFlags::VALIDATION,
         });
 
         let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
             label: None,
-            bind_group_layouts: &[&uniform_bind_group_layout],
+            bind_group_layouts: &[
+                &uniform_bind_group_layout,
+                &texture_bind_group_layout,
+                &material_bind_group_layout,
+                &light_bind_group_layout,
+            ],
             push_constant_ranges: &[],
         });
 
-        let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
-            label: None,
-            layout: Some(&pipeline_layout),
-            vertex: VertexState {
-                module: &shader_module,
-                entry_point: "main",
-                buffers: &[VertexBufferLayout {
-                    array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
-                    step_mode: InputStepMode::Vertex,
-                    attributes: &vertex_attr_array![
-                        0 => Float32x3,
-                        1 => Float32x2,
-                        2 => Float32x3,
-                    ],
-                }],
-            },
-            primitive: PrimitiveState {
-                topology: PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                front_face: FrontFace::Ccw,
-                cull_mode: Some(Face::Back),
-                clamp_depth: false,
-                polygon_mode: PolygonMode::Fill,
-                conservative: false,
-            },
-            depth_stencil: Some(DepthStencilState {
-                format: DEPTH_TEXTURE_FORMAT,
-                depth_write_enabled: true,
-                depth_compare: CompareFunction::GreaterEqual,
-                stencil: StencilState::default(),
-                bias: DepthBiasState::default(),
-            }),
-            multisample: MultisampleState { count: 1, mask: !0, alpha_to_coverage_enabled: false },
-            fragment: Some(FragmentState {
-                module: &shader_module,
-                entry_point: "main",
-                targets: &[ColorTargetState {
-                    format: swap_chain_descriptor.format,
-                    blend: Some(BlendState::REPLACE),
-                    write_mask: ColorWrite::ALL,
-                }],
-            }),
-        });
+        let pipeline = create_pipeline(
+            &device,
+            &pipeline_layout,
+            &shader_module,
+            swap_chain_descriptor.format,
+            config.sample_count,
+        );
+        let pbr_pipeline = create_pipeline(
+            &device,
+            &pipeline_layout,
+            &pbr_shader_module,
+            swap_chain_descriptor.format,
+            config.sample_count,
+        );
 
-        Some(Renderer {
+        let mut backend = Backend {
             _instance: instance,
             surface,
             _adapter: adapter,
@@ -293,38 +687,94 @@ impl Renderer {
             queue,
             swap_chain_descriptor,
             swap_chain,
+            present_mode: config.present_mode,
+            sample_count: config.sample_count,
+            texture_compression_bc: features.contains(Features::TEXTURE_COMPRESSION_BC),
 
             vertex_buffer,
             index_buffer,
+            instance_buffer,
             uniform_buffer,
-            _uniform_bind_group_layout: uniform_bind_group_layout,
+            uniform_bind_group_layout,
             uniform_bind_group,
+            light_buffer,
+            light_bind_group_layout,
+            light_bind_group,
+            shadow_maps,
+            environment,
+            texture_bind_group_layout,
+            material_bind_group_layout,
+            samplers,
+            mip_generator,
+            default_texture,
+            default_texture_bind_group,
+            default_normal_texture,
+            textures: HashMap::new(),
+            material_bind_groups: HashMap::new(),
+            models: HashMap::new(),
 
             depth_texture,
             depth_texture_view,
+            msaa_texture,
+            msaa_texture_view,
             _shader_module: shader_module,
+            _pbr_shader_module: pbr_shader_module,
             _pipeline_layout: pipeline_layout,
             pipeline,
+            pbr_pipeline,
+        };
+        backend.prepare_material_bind_group(MaterialMaps::default());
+
+        Ok(Renderer::with_backend(Some(backend)))
+    }
+
+    pub(crate) fn null() -> Renderer {
+        info!("Initializing null rendering backend");
+
+        Renderer::with_backend(None)
+    }
+
+    fn with_backend(backend: Option<Backend>) -> Renderer {
+        Renderer {
+            backend,
 
             clear_color: [0., 0., 0., 1.],
             view: Isometry3::identity(),
             projection: Matrix4::identity(),
-            _bound_texture: None,
+            bound_texture: None,
             vertex_data: vec![],
             index_data: vec![],
+            instance_data: vec![],
             uniform_data: vec![],
             draw_calls: vec![],
-        })
+            ambient_light: [0., 0., 0.],
+            lights: vec![],
+            shadow_casters: vec![],
+            shadow_distance: 50.,
+            casts_shadows: true,
+            receives_shadows: true,
+            shading_model: ShadingModel::default(),
+        }
     }
 
     pub(crate) fn resize(&mut self, size: [u32; 2]) {
-        let (swap_chain_descriptor, swap_chain) = create_swap_chain(&self.device, &self.surface, size);
-        self.swap_chain_descriptor = swap_chain_descriptor;
-        self.swap_chain = swap_chain;
+        let backend = match &mut self.backend {
+            Some(backend) => backend,
+            None => return,
+        };
 
-        let (depth_texture, depth_texture_view) = create_depth_texture(&self.device, size);
-        self.depth_texture = depth_texture;
-        self.depth_texture_view = depth_texture_view;
+        let (swap_chain_descriptor, swap_chain) =
+            create_swap_chain(&backend.device, &backend.surface, size, backend.present_mode);
+        backend.swap_chain_descriptor = swap_chain_descriptor;
+        backend.swap_chain = swap_chain;
+
+        let (depth_texture, depth_texture_view) = create_depth_texture(&backend.device, size, backend.sample_count);
+        backend.depth_texture = depth_texture;
+        backend.depth_texture_view = depth_texture_view;
+
+        let (msaa_texture, msaa_texture_view) = create_msaa_texture(&backend.device, size, backend.sample_count);
+        backend.msaa_texture = msaa_texture;
+        backend.msaa_texture_view = msaa_texture_view;
     }
 
     pub fn set_clear_color(&mut self, clear_color: [f64; 4]) -> &mut Self {
@@ -342,35 +792,396 @@ impl Renderer {
         self
     }
 
-    pub fn bind_texture(&mut self, _texture: &crate::Texture) -> &mut Self {
+    /// Sets the light applied to every surface regardless of the scene's lights, which also scales the environment
+    /// lighting of `ShadingModel::Pbr` draws. Kept across frames.
+    pub fn set_ambient_light(&mut self, color: [f32; 3]) -> &mut Self {
+        self.ambient_light = color;
+        self
+    }
+
+    /// Adds a light to the current frame. Lights are cleared on `submit`.
+    pub fn add_directional_light(&mut self, light: DirectionalLight) -> &mut Self {
+        if light.cast_shadows {
+            self.shadow_casters.push((self.lights.len(), ShadowCaster::Directional(light)));
+        }
+        self.lights.push(light.into());
+        self
+    }
+
+    /// Adds a light to the current frame. Lights are cleared on `submit`.
+    pub fn add_point_light(&mut self, light: PointLight) -> &mut Self {
+        self.lights.push(light.into());
+        self
+    }
+
+    /// Adds a light to the current frame. Lights are cleared on `submit`.
+    pub fn add_spot_light(&mut self, light: SpotLight) -> &mut Self {
+        if light.cast_shadows {
+            self.shadow_casters.push((self.lights.len(), ShadowCaster::Spot(light)));
+        }
+        self.lights.push(light.into());
+        self
+    }
+
+    /// Sets how far from the camera directional lights cast shadows. Cascades are spread over this distance, so
+    /// shorter distances give sharper shadows.
+    pub fn set_shadow_distance(&mut self, distance: f32) -> &mut Self {
+        self.shadow_distance = distance;
+        self
+    }
+
+    /// Sets whether models drawn after this call cast shadows.
+    pub fn set_casts_shadows(&mut self, casts_shadows: bool) -> &mut Self {
+        self.casts_shadows = casts_shadows;
+        self
+    }
+
+    /// Sets whether models drawn after this call are darkened by shadows.
+    pub fn set_receives_shadows(&mut self, receives_shadows: bool) -> &mut Self {
+        self.receives_shadows = receives_shadows;
         self
     }
 
+    /// Sets the shading model of every draw.
+    pub fn set_shading_model(&mut self, shading_model: ShadingModel) -> &mut Self {
+        self.shading_model = shading_model;
+        self
+    }
+
+    /// Sets the environment lighting `ShadingModel::Pbr` draws, scaled by the ambient light. Takes the cube map's faces
+    /// ordered +X, -X, +Y, -Y, +Z, -Z, which are convolved once here. Until set, the environment is uniform white.
+    pub fn set_environment(&mut self, faces: &[crate::Texture; 6]) -> &mut Self {
+        if let Some(backend) = &self.backend {
+            let faces = faces
+                .iter()
+                .map(|face| {
+                    GpuTexture::upload(
+                        &backend.device,
+                        &backend.queue,
+                        &backend.mip_generator,
+                        backend.texture_compression_bc,
+                        &face.data,
+                    )
+                })
+                .collect::<Vec<_>>();
+            let views = faces.iter().map(|face| &face.view).collect::<Vec<_>>();
+            backend.environment.set_faces(&backend.device, &backend.queue, &views);
+        }
+
+        self
+    }
+
+    /// Sets the texture used by subsequent `draw_model` calls, sampled with the texture's own sampler.
+    pub fn bind_texture(&mut self, texture: &crate::Texture) -> &mut Self {
+        self.bind_texture_with_sampler(texture, texture.sampler())
+    }
+
+    /// Sets the texture used by subsequent `draw_model` calls, overriding its sampler.
+    pub fn bind_texture_with_sampler(&mut self, texture: &crate::Texture, sampler: SamplerConfig) -> &mut Self {
+        self.bound_texture = Some(self.prepare_texture(texture, sampler));
+        self
+    }
+
+    pub fn unbind_texture(&mut self) -> &mut Self {
+        self.bound_texture = None;
+        self
+    }
+
+    /// Uploads `texture` and creates its bind group for `sampler` if either doesn't exist yet.
+    fn prepare_texture(&mut self, texture: &crate::Texture, sampler: SamplerConfig) -> (u64, SamplerConfig) {
+        let (id, sampler) = self.upload_texture(texture, sampler);
+        if let Some(backend) = &mut self.backend {
+            if let Some(gpu_texture) = backend.textures.get_mut(&id) {
+                let wgpu_sampler = &backend.samplers[&sampler];
+                gpu_texture.prepare_bind_group(
+                    &backend.device,
+                    &backend.texture_bind_group_layout,
+                    sampler,
+                    wgpu_sampler,
+                );
+            }
+        }
+
+        (id, sampler)
+    }
+
+    /// Uploads `texture` and creates `sampler` if either doesn't exist yet.
+    fn upload_texture(&mut self, texture: &crate::Texture, sampler: SamplerConfig) -> (u64, SamplerConfig) {
+        let data = &texture.data;
+        if let Some(backend) = &mut self.backend {
+            let device = &backend.device;
+            let queue = &backend.queue;
+            let mip_generator = &backend.mip_generator;
+            let texture_compression_bc = backend.texture_compression_bc;
+            backend
+                .textures
+                .entry(data.id)
+                .or_insert_with(|| GpuTexture::upload(device, queue, mip_generator, texture_compression_bc, data));
+            backend.samplers.entry(sampler).or_insert_with(|| create_sampler(device, &sampler));
+        }
+
+        (data.id, sampler)
+    }
+
+    /// Uploads a material's maps and creates the bind group for their combination if it doesn't exist yet.
+    fn prepare_material_maps(
+        &mut self,
+        normal: Option<&crate::Texture>,
+        metallic_roughness: Option<&crate::Texture>,
+        occlusion: Option<&crate::Texture>,
+        emissive: Option<&crate::Texture>,
+    ) -> MaterialMaps {
+        let mut upload =
+            |texture: Option<&crate::Texture>| texture.map(|texture| self.upload_texture(texture, texture.sampler()));
+        let maps = MaterialMaps {
+            normal: upload(normal),
+            metallic_roughness: upload(metallic_roughness),
+            occlusion: upload(occlusion),
+            emissive: upload(emissive),
+        };
+
+        if let Some(backend) = &mut self.backend {
+            backend.prepare_material_bind_group(maps);
+        }
+
+        maps
+    }
+
+    /// Draws each mesh with its material, falling back to the bound texture for meshes without a diffuse map.
     pub fn draw_model(&mut self, model: &crate::Model, position: Point3<f32>, rotation: UnitQuaternion<f32>) -> &mut Self {
+        self.draw_model_instanced(model, &[Isometry3::from_parts(Translation3::from(position), rotation)])
+    }
+
+    /// Draws the model once per transform, copying its vertices only once.
+    pub fn draw_model_instanced(&mut self, model: &crate::Model, transforms: &[Isometry3<f32>]) -> &mut Self {
+        if transforms.is_empty() {
+            return self;
+        }
+
+        let instances = self.push_instances(transforms);
+        let view_projection = (self.projection * self.view.to_homogeneous()).into();
+        let camera_position = self.camera_position();
+        let receive_shadows = self.receives_shadows as u32;
+
         let mut draw_call = vec![];
         for mesh in &model.meshes {
+            let material = mesh.material.and_then(|material| model.materials.get(material));
+            let texture = match material.and_then(|material| material.diffuse_texture.as_ref()) {
+                Some(texture) => Some(self.prepare_texture(texture, texture.sampler())),
+                None => self.bound_texture,
+            };
+            let maps = self.prepare_material_maps(
+                material.and_then(|material| material.normal_texture.as_ref()),
+                material.and_then(|material| material.metallic_roughness_texture.as_ref()),
+                material.and_then(|material| material.occlusion_texture.as_ref()),
+                material.and_then(|material| material.emissive_texture.as_ref()),
+            );
+            let diffuse = material.map_or([1., 1., 1., 1.], crate::Material::diffuse_rgba);
+            let specular = material.map_or([0., 0., 0., 0.], crate::Material::specular_rgba);
+            let emissive = material.map_or([0., 0., 0., 1.], crate::Material::emissive_rgba);
+            let metallic_roughness = material.map_or([0., 1., 0., 0.], crate::Material::metallic_roughness);
+
             draw_call.push(DrawCall {
                 base_vertex: self.vertex_data.len() as i32,
                 indices: self.index_data.len() as u32..(self.index_data.len() + mesh.indices.len()) as u32,
+                texture,
+                maps,
+                mesh: None,
+                uniform_index: self.uniform_data.len(),
+                instances: instances.clone(),
+                casts_shadows: self.casts_shadows,
             });
             self.vertex_data.extend(&mesh.vertices);
             self.index_data.extend(&mesh.indices);
+            self.uniform_data.push(Uniforms {
+                view_projection,
+                diffuse,
+                specular,
+                camera_position,
+                emissive,
+                metallic_roughness,
+                receive_shadows,
+                _padding: [0; 3],
+            });
         }
         self.draw_calls.push(draw_call);
 
-        let model = Translation3::from(position) * rotation;
-        let mvp = self.projection * (self.view * model).to_homogeneous();
-        self.uniform_data.push(Uniforms { mvp: mvp.into() });
+        self
+    }
+
+    /// Uploads the model's meshes to the GPU once so they can be drawn by handle without copying them every frame.
+    pub fn upload_model(&mut self, model: &crate::Model) -> ModelHandle {
+        let handle = ModelHandle::new(model);
+        if let Some(backend) = &mut self.backend {
+            backend.models.insert(handle.data.id, GpuModel::upload(&backend.device, model, &handle));
+        }
+
+        handle
+    }
+
+    /// Frees the model's GPU buffers without waiting for every handle to be dropped.
+    /// Later draws of the handle are skipped.
+    pub fn free_model(&mut self, model: &ModelHandle) {
+        if let Some(backend) = &mut self.backend {
+            backend.models.remove(&model.data.id);
+        }
+    }
+
+    /// Draws an uploaded model, falling back to the bound texture for meshes without a diffuse map.
+    pub fn draw_uploaded_model(
+        &mut self,
+        model: &ModelHandle,
+        position: Point3<f32>,
+        rotation: UnitQuaternion<f32>,
+    ) -> &mut Self {
+        self.draw_uploaded_model_instanced(model, &[Isometry3::from_parts(Translation3::from(position), rotation)])
+    }
+
+    /// Draws an uploaded model once per transform with a single instanced draw per mesh.
+    pub fn draw_uploaded_model_instanced(&mut self, model: &ModelHandle, transforms: &[Isometry3<f32>]) -> &mut Self {
+        if transforms.is_empty() {
+            return self;
+        }
+
+        let instances = self.push_instances(transforms);
+        let view_projection = (self.projection * self.view.to_homogeneous()).into();
+        let camera_position = self.camera_position();
+        let receive_shadows = self.receives_shadows as u32;
+
+        let mut draw_call = vec![];
+        for (i, mesh) in model.data.meshes.iter().enumerate() {
+            if mesh.index_count == 0 {
+                continue;
+            }
+
+            let texture = match &mesh.diffuse_texture {
+                Some(texture) => Some(self.prepare_texture(texture, texture.sampler())),
+                None => self.bound_texture,
+            };
+            let maps = self.prepare_material_maps(
+                mesh.normal_texture.as_ref(),
+                mesh.metallic_roughness_texture.as_ref(),
+                mesh.occlusion_texture.as_ref(),
+                mesh.emissive_texture.as_ref(),
+            );
+
+            draw_call.push(DrawCall {
+                base_vertex: 0,
+                indices: 0..mesh.index_count,
+                texture,
+                maps,
+                mesh: Some((model.data.id, i)),
+                uniform_index: self.uniform_data.len(),
+                instances: instances.clone(),
+                casts_shadows: self.casts_shadows,
+            });
+            self.uniform_data.push(Uniforms {
+                view_projection,
+                diffuse: mesh.diffuse,
+                specular: mesh.specular,
+                camera_position,
+                emissive: mesh.emissive,
+                metallic_roughness: mesh.metallic_roughness,
+                receive_shadows,
+                _padding: [0; 3],
+            });
+        }
+        self.draw_calls.push(draw_call);
 
         self
     }
 
+    fn camera_position(&self) -> [f32; 4] {
+        let position = self.view.inverse().translation.vector;
+        [position.x, position.y, position.z, 1.]
+    }
+
+    fn push_instances(&mut self, transforms: &[Isometry3<f32>]) -> Range<u32> {
+        let start = self.instance_data.len() as u32;
+        self.instance_data
+            .extend(transforms.iter().map(|transform| InstanceTransform { model: transform.to_homogeneous().into() }));
+        start..self.instance_data.len() as u32
+    }
+
+    /// The number of model draws recorded since the last `submit`.
+    pub fn draw_call_count(&self) -> usize {
+        self.draw_calls.len()
+    }
+
     pub fn submit(&mut self) {
-        let frame = match self.swap_chain.get_current_frame() {
+        if self.backend.is_some() {
+            self.submit_to_backend();
+        }
+
+        self.vertex_data.clear();
+        self.index_data.clear();
+        self.instance_data.clear();
+        self.uniform_data.clear();
+        self.draw_calls.clear();
+        self.lights.clear();
+        self.shadow_casters.clear();
+    }
+
+    fn submit_to_backend(&mut self) {
+        let backend = self.backend.as_mut().unwrap();
+
+        let vertex_data = bytemuck::cast_slice(&self.vertex_data);
+        let index_data = bytemuck::cast_slice(&self.index_data);
+        let instance_data = bytemuck::cast_slice(&self.instance_data);
+        // Each draw reads its uniforms at its own dynamic offset, one binding alignment apart
+        let stride = BIND_BUFFER_ALIGNMENT as usize;
+        let mut uniform_data = vec![0u8; self.uniform_data.len() * stride];
+        for (data, uniforms) in uniform_data.chunks_exact_mut(stride).zip(&self.uniform_data) {
+            data[..std::mem::size_of::<Uniforms>()].copy_from_slice(bytemuck::bytes_of(uniforms));
+        }
+
+        // Shadow casting lights past the shadow map budget are lit without shadows
+        let shadow_map_size = backend.shadow_maps.size();
+        let frustum = ViewFrustum::new(&self.projection);
+        let mut shadow_uniforms = ShadowUniforms::zeroed();
+        let mut shadow_layers = 0;
+        if !self.draw_calls.is_empty() {
+            for (index, caster) in &self.shadow_casters {
+                let view_projections =
+                    caster.view_projections(&self.view, frustum.as_ref(), self.shadow_distance, shadow_map_size);
+                if view_projections.is_empty() || shadow_layers + view_projections.len() > MAX_SHADOW_MAPS {
+                    continue;
+                }
+
+                self.lights[*index].set_shadow_layer(shadow_layers);
+                for view_projection in view_projections {
+                    shadow_uniforms.view_projections[shadow_layers] = view_projection.into();
+                    shadow_layers += 1;
+                }
+            }
+        }
+        if let Some(frustum) = &frustum {
+            shadow_uniforms.cascade_splits = cascade_splits(frustum.near(), self.shadow_distance);
+        }
+        let camera_forward = self.view.inverse_transform_vector(&-Vector3::z());
+        shadow_uniforms.camera_forward = [camera_forward.x, camera_forward.y, camera_forward.z, 0.];
+        shadow_uniforms.texel_size = 1. / shadow_map_size as f32;
+
+        let header = LightsHeader {
+            ambient: [self.ambient_light[0], self.ambient_light[1], self.ambient_light[2], 1.],
+            count: self.lights.len() as u32,
+            _padding: [0; 3],
+        };
+        let mut light_data = bytemuck::bytes_of(&header).to_vec();
+        light_data.extend_from_slice(bytemuck::cast_slice(&self.lights));
+
+        if let Err(e) =
+            backend.reserve_buffers(vertex_data, index_data, instance_data, &uniform_data, &light_data, shadow_layers)
+        {
+            error!("Skipping frame: {}", e);
+            return;
+        }
+
+        let frame = match backend.swap_chain.get_current_frame() {
             Ok(frame) => frame,
             Err(_) => {
-                self.swap_chain = self.device.create_swap_chain(&self.surface, &self.swap_chain_descriptor);
-                match self.swap_chain.get_current_frame() {
+                backend.swap_chain = backend.device.create_swap_chain(&backend.surface, &backend.swap_chain_descriptor);
+                match backend.swap_chain.get_current_frame() {
                     Ok(frame) => frame,
                     Err(e) => {
                         error!("Failed to acquire swapchain frame: {}", e);
@@ -382,23 +1193,55 @@ impl Renderer {
 
         let render_texture = frame.output;
 
-        let vertex_data = bytemuck::cast_slice(&self.vertex_data);
-        let index_data = bytemuck::cast_slice(&self.index_data);
-        let uniform_data = unsafe {
-            std::slice::from_raw_parts(
-                self.uniform_data.as_ptr() as *const u8,
-                self.uniform_data.len() * BIND_BUFFER_ALIGNMENT as usize,
-            )
+        // When multisampling, render into the MSAA target and resolve into the frame
+        let (color_view, resolve_target) = match &backend.msaa_texture_view {
+            Some(msaa_texture_view) => (msaa_texture_view, Some(&render_texture.view)),
+            None => (&render_texture.view, None),
         };
 
-        let mut encoder = self.device.create_command_encoder(&CommandEncoderDescriptor { label: None });
+        let mut encoder = backend.device.create_command_encoder(&CommandEncoderDescriptor { label: None });
+
+        for layer in 0..shadow_layers {
+            let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+                label: Some("shadow_pass"),
+                color_attachments: &[],
+                depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
+                    view: backend.shadow_maps.layer_view(layer),
+                    depth_ops: Some(Operations { load: LoadOp::Clear(0.0), store: true }),
+                    stencil_ops: None,
+                }),
+            });
+
+            render_pass.set_pipeline(backend.shadow_maps.pipeline());
+            render_pass.set_vertex_buffer(1, backend.instance_buffer.buffer().slice(0..instance_data.len() as u64));
+            render_pass.set_bind_group(
+                0,
+                backend.shadow_maps.view_bind_group(),
+                &[backend.shadow_maps.view_offset(layer)],
+            );
+            let mut bound_mesh = None;
+            for draw_call in self.draw_calls.iter().flatten().filter(|draw_call| draw_call.casts_shadows) {
+                if bound_mesh != Some(draw_call.mesh) {
+                    if !backend.bind_mesh(
+                        &mut render_pass,
+                        draw_call.mesh,
+                        vertex_data.len() as u64,
+                        index_data.len() as u64,
+                    ) {
+                        continue;
+                    }
+                    bound_mesh = Some(draw_call.mesh);
+                }
+                render_pass.draw_indexed(draw_call.indices.clone(), draw_call.base_vertex, draw_call.instances.clone());
+            }
+        }
 
         {
             let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
                 label: Some("render_pass"),
                 color_attachments: &[RenderPassColorAttachment {
-                    view: &render_texture.view,
-                    resolve_target: None,
+                    view: color_view,
+                    resolve_target,
                     ops: Operations {
                         load: LoadOp::Clear(Color {
                             r: self.clear_color[0],
@@ -410,49 +1253,221 @@ impl Renderer {
                     },
                 }],
                 depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
-                    view: &self.depth_texture_view,
+                    view: &backend.depth_texture_view,
                     depth_ops: Some(Operations { load: LoadOp::Clear(0.0), store: true }),
                     stencil_ops: None,
                 }),
             });
 
             if self.draw_calls.len() > 0 {
-                render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(0..vertex_data.len() as u64));
-                render_pass.set_index_buffer(self.index_buffer.slice(0..index_data.len() as u64), IndexFormat::Uint32);
-                render_pass.set_pipeline(&self.pipeline);
+                render_pass.set_pipeline(match self.shading_model {
+                    ShadingModel::BlinnPhong => &backend.pipeline,
+                    ShadingModel::Pbr => &backend.pbr_pipeline,
+                });
+                render_pass.set_vertex_buffer(1, backend.instance_buffer.buffer().slice(0..instance_data.len() as u64));
+                render_pass.set_bind_group(3, &backend.light_bind_group, &[]);
+                // Vertex and index buffers are only rebound when consecutive draws read different meshes
+                let mut bound_mesh = None;
                 for i in 0..self.draw_calls.len() {
-                    let offset = (i as DynamicOffset) * (BIND_BUFFER_ALIGNMENT as DynamicOffset);
-                    render_pass.set_bind_group(0, &self.uniform_bind_group, &[offset]);
                     for k in 0..self.draw_calls[i].len() {
+                        let mesh = self.draw_calls[i][k].mesh;
+                        if bound_mesh != Some(mesh) {
+                            if !backend.bind_mesh(
+                                &mut render_pass,
+                                mesh,
+                                vertex_data.len() as u64,
+                                index_data.len() as u64,
+                            ) {
+                                continue;
+                            }
+                            bound_mesh = Some(mesh);
+                        }
+
+                        let offset = (self.draw_calls[i][k].uniform_index as DynamicOffset)
+                            * (BIND_BUFFER_ALIGNMENT as DynamicOffset);
+                        render_pass.set_bind_group(0, &backend.uniform_bind_group, &[offset]);
+                        let texture_bind_group = self.draw_calls[i][k]
+                            .texture
+                            .and_then(|(id, sampler)| backend.textures.get(&id)?.bind_group(&sampler))
+                            .unwrap_or(&backend.default_texture_bind_group);
+                        render_pass.set_bind_group(1, texture_bind_group, &[]);
+                        let material_bind_group = backend
+                            .material_bind_groups
+                            .get(&self.draw_calls[i][k].maps)
+                            .unwrap_or(&backend.material_bind_groups[&MaterialMaps::default()]);
+                        render_pass.set_bind_group(2, material_bind_group, &[]);
                         render_pass.draw_indexed(
                             self.draw_calls[i][k].indices.clone(),
                             self.draw_calls[i][k].base_vertex,
-                            0..1,
+                            self.draw_calls[i][k].instances.clone(),
                         );
                     }
                 }
             }
         }
 
-        self.queue.write_buffer(&self.vertex_buffer, 0, vertex_data);
-        self.queue.write_buffer(&self.index_buffer, 0, index_data);
-        self.queue.write_buffer(&self.uniform_buffer, 0, uniform_data);
-        self.queue.submit(Some(encoder.finish()));
+        backend.vertex_buffer.write(&backend.queue, vertex_data);
+        backend.index_buffer.write(&backend.queue, index_data);
+        backend.instance_buffer.write(&backend.queue, instance_data);
+        backend.uniform_buffer.write(&backend.queue, &uniform_data);
+        backend.light_buffer.write(&backend.queue, &light_data);
+        backend.shadow_maps.write(&backend.queue, &shadow_uniforms, shadow_layers);
+        backend.queue.submit(Some(encoder.finish()));
 
-        self.vertex_data.clear();
-        self.index_data.clear();
-        self.uniform_data.clear();
-        self.draw_calls.clear();
+        backend.textures.retain(|_, texture| !texture.is_orphaned());
+        let textures = &backend.textures;
+        backend
+            .material_bind_groups
+            .retain(|maps, _| maps.maps().iter().flatten().all(|(id, _)| textures.contains_key(id)));
+        backend.models.retain(|_, model| !model.is_orphaned());
     }
 }
 
-fn create_swap_chain(device: &Device, surface: &Surface, size: [u32; 2]) -> (SwapChainDescriptor, SwapChain) {
+fn create_uniform_bind_group(device: &Device, layout: &BindGroupLayout, buffer: &Buffer) -> BindGroup {
+    device.create_bind_group(&BindGroupDescriptor {
+        layout,
+        entries: &[BindGroupEntry {
+            binding: 0,
+            resource: BindingResource::Buffer(BufferBinding {
+                buffer,
+                offset: 0,
+                size: wgpu::BufferSize::new(std::mem::size_of::<Uniforms>() as _),
+            }),
+        }],
+        label: None,
+    })
+}
+
+fn create_light_bind_group(
+    device: &Device,
+    layout: &BindGroupLayout,
+    buffer: &Buffer,
+    shadow_maps: &ShadowMaps,
+    environment: &EnvironmentMaps,
+) -> BindGroup {
+    device.create_bind_group(&BindGroupDescriptor {
+        layout,
+        entries: &[
+            BindGroupEntry {
+                binding: 0,
+                resource: BindingResource::Buffer(BufferBinding { buffer, offset: 0, size: None }),
+            },
+            BindGroupEntry {
+                binding: 1,
+                resource: BindingResource::Buffer(BufferBinding {
+                    buffer: shadow_maps.uniform_buffer(),
+                    offset: 0,
+                    size: None,
+                }),
+            },
+            BindGroupEntry { binding: 2, resource: BindingResource::TextureView(shadow_maps.view()) },
+            BindGroupEntry { binding: 3, resource: BindingResource::Sampler(shadow_maps.sampler()) },
+            BindGroupEntry { binding: 4, resource: BindingResource::TextureView(environment.irradiance_view()) },
+            BindGroupEntry { binding: 5, resource: BindingResource::TextureView(environment.prefiltered_view()) },
+            BindGroupEntry { binding: 6, resource: BindingResource::TextureView(environment.brdf_lut_view()) },
+            BindGroupEntry { binding: 7, resource: BindingResource::Sampler(environment.sampler()) },
+        ],
+        label: Some("light_bind_group"),
+    })
+}
+
+fn create_pipeline(
+    device: &Device,
+    layout: &PipelineLayout,
+    module: &ShaderModule,
+    format: TextureFormat,
+    sample_count: u32,
+) -> RenderPipeline {
+    device.create_render_pipeline(&RenderPipelineDescriptor {
+        label: None,
+        layout: Some(layout),
+        vertex: VertexState {
+            module,
+            entry_point: "main",
+            buffers: &[
+                VertexBufferLayout {
+                    array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
+                    step_mode: InputStepMode::Vertex,
+                    attributes: &vertex_attr_array![
+                        0 => Float32x3,
+                        1 => Float32x2,
+                        2 => Float32x3,
+                        3 => Float32x4,
+                    ],
+                },
+                // The model matrix, one column per attribute
+                VertexBufferLayout {
+                    array_stride: std::mem::size_of::<InstanceTransform>() as wgpu::BufferAddress,
+                    step_mode: InputStepMode::Instance,
+                    attributes: &vertex_attr_array![
+                        4 => Float32x4,
+                        5 => Float32x4,
+                        6 => Float32x4,
+                        7 => Float32x4,
+                    ],
+                },
+            ],
+        },
+        primitive: PrimitiveState {
+            topology: PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: FrontFace::Ccw,
+            cull_mode: Some(Face::Back),
+            clamp_depth: false,
+            polygon_mode: PolygonMode::Fill,
+            conservative: false,
+        },
+        depth_stencil: Some(DepthStencilState {
+            format: DEPTH_TEXTURE_FORMAT,
+            depth_write_enabled: true,
+            depth_compare: CompareFunction::GreaterEqual,
+            stencil: StencilState::default(),
+            bias: DepthBiasState::default(),
+        }),
+        multisample: MultisampleState { count: sample_count, mask: !0, alpha_to_coverage_enabled: false },
+        fragment: Some(FragmentState {
+            module,
+            entry_point: "main",
+            targets: &[ColorTargetState {
+                format,
+                blend: Some(BlendState::ALPHA_BLENDING),
+                write_mask: ColorWrite::ALL,
+            }],
+        }),
+    })
+}
+
+fn create_material_bind_group(
+    device: &Device,
+    layout: &BindGroupLayout,
+    maps: &[(&TextureView, &Sampler); 4],
+) -> BindGroup {
+    let entries = maps
+        .iter()
+        .enumerate()
+        .flat_map(|(map, &(view, sampler))| {
+            vec![
+                BindGroupEntry { binding: map as u32 * 2, resource: BindingResource::TextureView(view) },
+                BindGroupEntry { binding: map as u32 * 2 + 1, resource: BindingResource::Sampler(sampler) },
+            ]
+        })
+        .collect::<Vec<_>>();
+
+    device.create_bind_group(&BindGroupDescriptor { label: Some("material_bind_group"), layout, entries: &entries })
+}
+
+fn create_swap_chain(
+    device: &Device,
+    surface: &Surface,
+    size: [u32; 2],
+    present_mode: PresentMode,
+) -> (SwapChainDescriptor, SwapChain) {
     let swap_chain_descriptor = SwapChainDescriptor {
         usage: TextureUsage::RENDER_ATTACHMENT,
         format: TEXTURE_FORMAT,
         width: size[0],
         height: size[1],
-        present_mode: PresentMode::Fifo,
+        present_mode,
     };
 
     let swap_chain = device.create_swap_chain(surface, &swap_chain_descriptor);
@@ -460,12 +1475,12 @@ fn create_swap_chain(device: &Device, surface: &Surface, size: [u32; 2]) -> (Swa
     (swap_chain_descriptor, swap_chain)
 }
 
-fn create_depth_texture(device: &Device, size: [u32; 2]) -> (Texture, TextureView) {
+fn create_depth_texture(device: &Device, size: [u32; 2], sample_count: u32) -> (Texture, TextureView) {
     let texture = device.create_texture(&TextureDescriptor {
         label: Some("depth texture"),
         size: Extent3d { width: size[0], height: size[1], depth_or_array_layers: 1 },
         mip_level_count: 1,
-        sample_count: 1,
+        sample_count,
         dimension: TextureDimension::D2,
         format: DEPTH_TEXTURE_FORMAT,
         usage: TextureUsage::RENDER_ATTACHMENT | TextureUsage::SAMPLED,
@@ -475,3 +1490,23 @@ fn create_depth_texture(device: &Device, size: [u32; 2]) -> (Texture, TextureVie
 
     (texture, view)
 }
+
+fn create_msaa_texture(device: &Device, size: [u32; 2], sample_count: u32) -> (Option<Texture>, Option<TextureView>) {
+    if sample_count <= 1 {
+        return (None, None);
+    }
+
+    let texture = device.create_texture(&TextureDescriptor {
+        label: Some("msaa texture"),
+        size: Extent3d { width: size[0], height: size[1], depth_or_array_layers: 1 },
+        mip_level_count: 1,
+        sample_count,
+        dimension: TextureDimension::D2,
+        format: TEXTURE_FORMAT,
+        usage: TextureUsage::RENDER_ATTACHMENT,
+    });
+
+    let view = texture.create_view(&TextureViewDescriptor::default());
+
+    (Some(texture), Some(view))
+}
@@ -1,34 +1,145 @@
 // Copyright 2021 Chay Nabors.
 
+use std::error::Error;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::path::PathBuf;
+
 use tobj::LoadError;
 
 #[derive(Debug)]
 pub enum GearError {
     IOError(std::io::Error),
     NetworkError(laminar::ErrorKind),
-    OpenFileFailed,
-    ParseFileFailed,
-    Unknown,
+    NetworkBindFailed { addresses: Vec<SocketAddr>, source: Box<GearError> },
+    SocketClosed,
+    WindowCreationFailed(winit::error::OsError),
+    WindowOperationFailed(winit::error::ExternalError),
+    AdapterUnavailable,
+    RendererInitFailed(wgpu::RequestDeviceError),
+    AudioDeviceUnavailable(rodio::StreamError),
+    AudioSourceCreationFailed(rodio::PlayError),
+    AudioDecodeFailed(rodio::decoder::DecoderError),
+    GamepadBackendUnavailable(Box<dyn Error + Send + Sync>),
+    ObjLoadFailed(LoadError),
+    GltfLoadFailed(gltf::Error),
+    ConfigError(ron::Error),
+    RecordingError(bincode::Error),
+    UnsupportedFormat,
+    ImageError(image::ImageError),
+    KtxError(ktx2::ParseError),
+    DdsError(ddsfile::Error),
+    InvalidTextureData,
+    UnsupportedSampleCount(u32),
+    BufferLimitExceeded { label: &'static str, size: u64, limit: u64 },
+    AssetLoadFailed { path: PathBuf, source: Box<GearError> },
+}
+
+impl Display for GearError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GearError::IOError(e) => write!(f, "io error: {}", e),
+            GearError::NetworkError(e) => write!(f, "network error: {}", e),
+            GearError::NetworkBindFailed { addresses, .. } => write!(f, "failed to bind socket to {:?}", addresses),
+            GearError::SocketClosed => write!(f, "socket is closed"),
+            GearError::WindowCreationFailed(_) => write!(f, "failed to create window"),
+            GearError::WindowOperationFailed(_) => write!(f, "window operation failed"),
+            GearError::AdapterUnavailable => write!(f, "no suitable graphics adapter found"),
+            GearError::RendererInitFailed(_) => write!(f, "failed to initialize renderer"),
+            GearError::AudioDeviceUnavailable(_) => write!(f, "audio device unavailable"),
+            GearError::AudioSourceCreationFailed(_) => write!(f, "failed to create audio source"),
+            GearError::AudioDecodeFailed(_) => write!(f, "failed to decode audio"),
+            GearError::GamepadBackendUnavailable(_) => write!(f, "gamepad backend unavailable"),
+            GearError::ObjLoadFailed(e) => write!(f, "failed to load obj: {}", e),
+            GearError::GltfLoadFailed(e) => write!(f, "failed to load glTF: {}", e),
+            GearError::ConfigError(e) => write!(f, "invalid config: {}", e),
+            GearError::RecordingError(e) => write!(f, "invalid input recording: {}", e),
+            GearError::UnsupportedFormat => write!(f, "unsupported file format"),
+            GearError::ImageError(e) => write!(f, "failed to decode image: {}", e),
+            GearError::KtxError(e) => write!(f, "failed to parse KTX2 file: {}", e),
+            GearError::DdsError(e) => write!(f, "failed to parse DDS file: {}", e),
+            GearError::InvalidTextureData => write!(f, "texture data doesn't match its dimensions"),
+            GearError::UnsupportedSampleCount(count) => write!(f, "unsupported MSAA sample count {}", count),
+            GearError::BufferLimitExceeded { label, size, limit } => {
+                write!(f, "{} needs {} bytes, more than the {} byte limit", label, size, limit)
+            },
+            GearError::AssetLoadFailed { path, .. } => write!(f, "failed to load asset {}", path.display()),
+        }
+    }
+}
+
+impl Error for GearError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            GearError::IOError(e) => Some(e),
+            GearError::NetworkError(e) => Some(e),
+            GearError::NetworkBindFailed { source, .. } => Some(source.as_ref()),
+            GearError::WindowCreationFailed(e) => Some(e),
+            GearError::WindowOperationFailed(e) => Some(e),
+            GearError::RendererInitFailed(e) => Some(e),
+            GearError::AudioDeviceUnavailable(e) => Some(e),
+            GearError::AudioSourceCreationFailed(e) => Some(e),
+            GearError::AudioDecodeFailed(e) => Some(e),
+            GearError::GamepadBackendUnavailable(e) => Some(e.as_ref()),
+            GearError::ObjLoadFailed(e) => Some(e),
+            GearError::GltfLoadFailed(e) => Some(e),
+            GearError::ConfigError(e) => Some(e),
+            GearError::RecordingError(e) => Some(e),
+            GearError::ImageError(e) => Some(e),
+            GearError::KtxError(e) => Some(e),
+            GearError::DdsError(e) => Some(e),
+            GearError::AssetLoadFailed { source, .. } => Some(source.as_ref()),
+            GearError::SocketClosed
+            | GearError::AdapterUnavailable
+            | GearError::UnsupportedFormat
+            | GearError::InvalidTextureData
+            | GearError::UnsupportedSampleCount(_)
+            | GearError::BufferLimitExceeded { .. } => None,
+        }
+    }
 }
 
 impl From<LoadError> for GearError {
     fn from(e: LoadError) -> Self {
-        match e {
-            LoadError::OpenFileFailed => GearError::OpenFileFailed,
-            LoadError::ReadError
-            | LoadError::UnrecognizedCharacter
-            | LoadError::PositionParseError
-            | LoadError::NormalParseError
-            | LoadError::TexcoordParseError
-            | LoadError::FaceParseError
-            | LoadError::MaterialParseError
-            | LoadError::InvalidObjectName
-            | LoadError::FaceVertexOutOfBounds
-            | LoadError::FaceTexCoordOutOfBounds
-            | LoadError::FaceNormalOutOfBounds
-            | LoadError::FaceColorOutOfBounds => GearError::ParseFileFailed,
-            LoadError::InvalidLoadOptionConfig | LoadError::GenericFailure => GearError::Unknown,
-        }
+        GearError::ObjLoadFailed(e)
+    }
+}
+
+impl From<gltf::Error> for GearError {
+    fn from(e: gltf::Error) -> Self {
+        GearError::GltfLoadFailed(e)
+    }
+}
+
+impl From<ron::Error> for GearError {
+    fn from(e: ron::Error) -> Self {
+        GearError::ConfigError(e)
+    }
+}
+
+impl From<bincode::Error> for GearError {
+    fn from(e: bincode::Error) -> Self {
+        GearError::RecordingError(e)
+    }
+}
+
+impl From<image::ImageError> for GearError {
+    fn from(e: image::ImageError) -> Self {
+        GearError::ImageError(e)
+    }
+}
+
+impl From<ktx2::ParseError> for GearError {
+    fn from(e: ktx2::ParseError) -> Self {
+        GearError::KtxError(e)
+    }
+}
+
+impl From<ddsfile::Error> for GearError {
+    fn from(e: ddsfile::Error) -> Self {
+        GearError::DdsError(e)
     }
 }
 
@@ -47,4 +158,50 @@ impl From<laminar::ErrorKind> for GearError {
     }
 }
 
+impl From<winit::error::OsError> for GearError {
+    fn from(e: winit::error::OsError) -> Self {
+        GearError::WindowCreationFailed(e)
+    }
+}
+
+impl From<winit::error::ExternalError> for GearError {
+    fn from(e: winit::error::ExternalError) -> Self {
+        GearError::WindowOperationFailed(e)
+    }
+}
+
+impl From<wgpu::RequestDeviceError> for GearError {
+    fn from(e: wgpu::RequestDeviceError) -> Self {
+        GearError::RendererInitFailed(e)
+    }
+}
+
+impl From<rodio::StreamError> for GearError {
+    fn from(e: rodio::StreamError) -> Self {
+        GearError::AudioDeviceUnavailable(e)
+    }
+}
+
+impl From<rodio::PlayError> for GearError {
+    fn from(e: rodio::PlayError) -> Self {
+        GearError::AudioSourceCreationFailed(e)
+    }
+}
+
+impl From<rodio::decoder::DecoderError> for GearError {
+    fn from(e: rodio::decoder::DecoderError) -> Self {
+        GearError::AudioDecodeFailed(e)
+    }
+}
+
 pub type Result<T> = std::result::Result<T, GearError>;
+
+pub(crate) trait WithPath<T> {
+    fn with_path<P: AsRef<Path>>(self, path: P) -> Result<T>;
+}
+
+impl<T, E: Into<GearError>> WithPath<T> for std::result::Result<T, E> {
+    fn with_path<P: AsRef<Path>>(self, path: P) -> Result<T> {
+        self.map_err(|e| GearError::AssetLoadFailed { path: path.as_ref().to_path_buf(), source: Box::new(e.into()) })
+    }
+}
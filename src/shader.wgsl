@@ -2,32 +2,227 @@ struct VertexInput {
     [[location(0)]] pos: vec3<f32>;
     [[location(1)]] tex_coord: vec2<f32>;
     [[location(2)]] normal: vec3<f32>;
+    [[location(3)]] tangent: vec4<f32>;
+};
+
+struct InstanceInput {
+    [[location(4)]] model_0: vec4<f32>;
+    [[location(5)]] model_1: vec4<f32>;
+    [[location(6)]] model_2: vec4<f32>;
+    [[location(7)]] model_3: vec4<f32>;
 };
 
 struct VertexOutput {
     [[location(0)]] tex_coord: vec2<f32>;
     [[location(1)]] normal: vec3<f32>;
+    [[location(2)]] tangent: vec4<f32>;
+    [[location(3)]] world_pos: vec3<f32>;
     [[builtin(position)]] pos: vec4<f32>;
 };
 
 [[block]]
 struct Uniforms {
-    model_view_projection: mat4x4<f32>;
+    view_projection: mat4x4<f32>;
+    diffuse: vec4<f32>;
+    specular: vec4<f32>;
+    camera_position: vec4<f32>;
+    emissive: vec4<f32>;
+    // Metalness and roughness factors in x and y
+    metallic_roughness: vec4<f32>;
+    receive_shadows: u32;
+};
+
+// Kind in position.w (0 directional, 1 point, 2 spot), range in direction.w, first shadow map layer or -1 in cone.z
+struct Light {
+    position: vec4<f32>;
+    direction: vec4<f32>;
+    color: vec4<f32>;
+    cone: vec4<f32>;
+};
+
+[[block]]
+struct Lights {
+    ambient: vec4<f32>;
+    count: u32;
+    lights: [[stride(64)]] array<Light>;
+};
+
+[[block]]
+struct Shadows {
+    view_projections: [[stride(64)]] array<mat4x4<f32>, 16>;
+    // View depth at which each of the 4 cascades of a directional light ends
+    cascade_splits: vec4<f32>;
+    camera_forward: vec4<f32>;
+    texel_size: f32;
 };
 
 [[group(0), binding(0)]]
 var<uniform> uniforms: Uniforms;
 
+[[group(1), binding(0)]]
+var diffuse_texture: texture_2d<f32>;
+[[group(1), binding(1)]]
+var diffuse_sampler: sampler;
+
+[[group(2), binding(0)]]
+var normal_texture: texture_2d<f32>;
+[[group(2), binding(1)]]
+var normal_sampler: sampler;
+// Roughness in green and metalness in blue, as in glTF
+[[group(2), binding(2)]]
+var metallic_roughness_texture: texture_2d<f32>;
+[[group(2), binding(3)]]
+var metallic_roughness_sampler: sampler;
+[[group(2), binding(4)]]
+var occlusion_texture: texture_2d<f32>;
+[[group(2), binding(5)]]
+var occlusion_sampler: sampler;
+[[group(2), binding(6)]]
+var emissive_texture: texture_2d<f32>;
+[[group(2), binding(7)]]
+var emissive_sampler: sampler;
+
+[[group(3), binding(0)]]
+var<storage> lights: [[access(read)]] Lights;
+[[group(3), binding(1)]]
+var<uniform> shadows: Shadows;
+[[group(3), binding(2)]]
+var shadow_maps: texture_depth_2d_array;
+[[group(3), binding(3)]]
+var shadow_sampler: sampler_comparison;
+[[group(3), binding(4)]]
+var irradiance_map: texture_cube<f32>;
+[[group(3), binding(5)]]
+var prefiltered_map: texture_cube<f32>;
+[[group(3), binding(6)]]
+var brdf_lut: texture_2d<f32>;
+[[group(3), binding(7)]]
+var environment_sampler: sampler;
+
 [[stage(vertex)]]
-fn main(in: VertexInput) -> VertexOutput {
+fn main(in: VertexInput, instance: InstanceInput) -> VertexOutput {
+    let model = mat4x4<f32>(instance.model_0, instance.model_1, instance.model_2, instance.model_3);
+
     var out: VertexOutput;
     out.tex_coord = in.tex_coord;
-    out.normal = in.normal;
-    out.pos = uniforms.model_view_projection * vec4<f32>(in.pos, 1.0);
+    out.normal = (model * vec4<f32>(in.normal, 0.0)).xyz;
+    out.tangent = vec4<f32>((model * vec4<f32>(in.tangent.xyz, 0.0)).xyz, in.tangent.w);
+    let world_pos = model * vec4<f32>(in.pos, 1.0);
+    out.world_pos = world_pos.xyz;
+    out.pos = uniforms.view_projection * world_pos;
     return out;
 }
 
-[[stage(fragment)]]
-fn main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
-    return vec4<f32>(in.normal, 1.0);
+// World space normal with the tangent space normal map applied, for lighting
+fn surface_normal(in: VertexOutput) -> vec3<f32> {
+    let normal = normalize(in.normal);
+    let mapped = textureSample(normal_texture, normal_sampler, in.tex_coord).xyz * 2.0 - vec3<f32>(1.0, 1.0, 1.0);
+    // Vertices without a tangent can't be normal mapped
+    if (dot(in.tangent.xyz, in.tangent.xyz) == 0.0) {
+        return normal;
+    }
+
+    let tangent = normalize(in.tangent.xyz - normal * dot(normal, in.tangent.xyz));
+    let bitangent = cross(normal, tangent) * in.tangent.w;
+    return normalize(mat3x3<f32>(tangent, bitangent, normal) * mapped);
+}
+
+// Fades smoothly to zero at the light's range
+fn range_attenuation(dist: f32, range: f32) -> f32 {
+    let ratio = dist / range;
+    let falloff = clamp(1.0 - ratio * ratio * ratio * ratio, 0.0, 1.0);
+    return falloff * falloff / (dist * dist + 1.0);
+}
+
+fn spot_attenuation(cos_angle: f32, cos_inner: f32, cos_outer: f32) -> f32 {
+    let t = clamp((cos_angle - cos_outer) / max(cos_inner - cos_outer, 0.0001), 0.0, 1.0);
+    return t * t * (3.0 - 2.0 * t);
+}
+
+// Fraction of light reaching the point, filtered over 3x3 taps that each compare 2x2 texels
+fn shadow_factor(layer: i32, world_pos: vec3<f32>) -> f32 {
+    let clip = shadows.view_projections[layer] * vec4<f32>(world_pos, 1.0);
+    let ndc = clip.xyz / clip.w;
+    let uv = ndc.xy * vec2<f32>(0.5, -0.5) + vec2<f32>(0.5, 0.5);
+    // Nothing outside the light's view is shadowed
+    if (uv.x < 0.0 || uv.x > 1.0 || uv.y < 0.0 || uv.y > 1.0 || ndc.z < 0.0 || ndc.z > 1.0) {
+        return 1.0;
+    }
+
+    var lit: f32 = 0.0;
+    var x: i32 = -1;
+    loop {
+        if (x > 1) {
+            break;
+        }
+
+        var y: i32 = -1;
+        loop {
+            if (y > 1) {
+                break;
+            }
+
+            let offset = vec2<f32>(f32(x), f32(y)) * shadows.texel_size;
+            lit = lit + textureSampleCompare(shadow_maps, shadow_sampler, uv + offset, layer, ndc.z);
+
+            continuing {
+                y = y + 1;
+            }
+        }
+
+        continuing {
+            x = x + 1;
+        }
+    }
+
+    return lit / 9.0;
+}
+
+fn directional_shadow_factor(layer: i32, world_pos: vec3<f32>) -> f32 {
+    let depth = dot(world_pos - uniforms.camera_position.xyz, shadows.camera_forward.xyz);
+    var cascade: i32 = 0;
+    loop {
+        if (cascade == 3 || depth <= shadows.cascade_splits[cascade]) {
+            break;
+        }
+
+        continuing {
+            cascade = cascade + 1;
+        }
+    }
+
+    // Past the shadow distance
+    if (depth > shadows.cascade_splits[cascade]) {
+        return 1.0;
+    }
+
+    return shadow_factor(layer + cascade, world_pos);
+}
+
+// Direction towards light `i` in xyz and its attenuation, including shadows, in w
+fn light_incidence(i: u32, world_pos: vec3<f32>) -> vec4<f32> {
+    let light = lights.lights[i];
+    var light_dir: vec3<f32> = -light.direction.xyz;
+    var attenuation: f32 = 1.0;
+    if (light.position.w != 0.0) {
+        let to_light = light.position.xyz - world_pos;
+        let dist = length(to_light);
+        light_dir = to_light / max(dist, 0.0001);
+        attenuation = range_attenuation(dist, light.direction.w);
+        if (light.position.w == 2.0) {
+            let cos_angle = dot(-light_dir, light.direction.xyz);
+            attenuation = attenuation * spot_attenuation(cos_angle, light.cone.x, light.cone.y);
+        }
+    }
+
+    let shadow_layer = i32(light.cone.z);
+    if (uniforms.receive_shadows != 0u && shadow_layer >= 0 && attenuation > 0.0) {
+        if (light.position.w == 0.0) {
+            attenuation = attenuation * directional_shadow_factor(shadow_layer, world_pos);
+        } else {
+            attenuation = attenuation * shadow_factor(shadow_layer, world_pos);
+        }
+    }
+
+    return vec4<f32>(light_dir, attenuation);
 }
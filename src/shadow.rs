@@ -0,0 +1,540 @@
+// Copyright 2021 Chay Nabors.
+
+use std::borrow::Cow;
+use std::num::NonZeroU32;
+
+use bytemuck::Pod;
+use bytemuck::Zeroable;
+use log::info;
+use log::warn;
+use nalgebra::Isometry3;
+use nalgebra::Matrix4;
+use nalgebra::Point3;
+use nalgebra::UnitQuaternion;
+use nalgebra::Vector3;
+use nalgebra::Vector4;
+use wgpu::vertex_attr_array;
+use wgpu::AddressMode;
+use wgpu::BindGroup;
+use wgpu::BindGroupDescriptor;
+use wgpu::BindGroupEntry;
+use wgpu::BindGroupLayoutDescriptor;
+use wgpu::BindGroupLayoutEntry;
+use wgpu::BindingResource;
+use wgpu::BindingType;
+use wgpu::Buffer;
+use wgpu::BufferBinding;
+use wgpu::BufferBindingType;
+use wgpu::BufferDescriptor;
+use wgpu::BufferUsage;
+use wgpu::CompareFunction;
+use wgpu::DepthBiasState;
+use wgpu::DepthStencilState;
+use wgpu::Device;
+use wgpu::Extent3d;
+use wgpu::Face;
+use wgpu::FilterMode;
+use wgpu::FrontFace;
+use wgpu::InputStepMode;
+use wgpu::MultisampleState;
+use wgpu::PipelineLayoutDescriptor;
+use wgpu::PolygonMode;
+use wgpu::PrimitiveState;
+use wgpu::PrimitiveTopology;
+use wgpu::Queue;
+use wgpu::RenderPipeline;
+use wgpu::RenderPipelineDescriptor;
+use wgpu::Sampler;
+use wgpu::SamplerDescriptor;
+use wgpu::ShaderFlags;
+use wgpu::ShaderModuleDescriptor;
+use wgpu::ShaderSource;
+use wgpu::ShaderStage;
+use wgpu::StencilState;
+use wgpu::Texture;
+use wgpu::TextureDescriptor;
+use wgpu::TextureDimension;
+use wgpu::TextureFormat;
+use wgpu::TextureUsage;
+use wgpu::TextureView;
+use wgpu::TextureViewDescriptor;
+use wgpu::TextureViewDimension;
+use wgpu::VertexBufferLayout;
+use wgpu::VertexState;
+use wgpu::BIND_BUFFER_ALIGNMENT;
+
+use crate::light::DirectionalLight;
+use crate::light::SpotLight;
+use crate::model::Vertex;
+use crate::renderer::InstanceTransform;
+
+/// Most layers in the shadow map array, shared by all shadow casting lights in a frame.
+pub(crate) const MAX_SHADOW_MAPS: usize = 16;
+/// Directional lights split the view into this many cascades, each rendered into its own layer.
+/// Must match the cascade count in `shader.wgsl`.
+pub(crate) const CASCADE_COUNT: usize = 4;
+const SHADOW_MAP_FORMAT: TextureFormat = TextureFormat::Depth32Float;
+/// Blend between logarithmic and uniform cascade splits.
+const CASCADE_SPLIT_LAMBDA: f32 = 0.5;
+
+/// A light's view and projection for one shadow map layer.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+struct ShadowView {
+    view_projection: [[f32; 4]; 4],
+}
+
+/// Shadow parameters read by the main shader.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+pub(crate) struct ShadowUniforms {
+    pub view_projections: [[[f32; 4]; 4]; MAX_SHADOW_MAPS],
+    /// View depth at which each cascade ends.
+    pub cascade_splits: [f32; CASCADE_COUNT],
+    pub camera_forward: [f32; 4],
+    pub texel_size: f32,
+    pub _padding: [f32; 3],
+}
+
+/// A light added with `cast_shadows` set.
+#[derive(Copy, Clone, Debug)]
+pub(crate) enum ShadowCaster {
+    Directional(DirectionalLight),
+    Spot(SpotLight),
+}
+
+impl ShadowCaster {
+    /// Returns a view projection per shadow map layer the light renders into, or none if the light has nothing to
+    /// cast shadows onto.
+    pub fn view_projections(
+        &self,
+        view: &Isometry3<f32>,
+        frustum: Option<&ViewFrustum>,
+        shadow_distance: f32,
+        map_size: u32,
+    ) -> Vec<Matrix4<f32>> {
+        match self {
+            ShadowCaster::Directional(light) => match frustum {
+                Some(frustum) => cascade_view_projections(light, view, frustum, shadow_distance, map_size),
+                None => vec![],
+            },
+            ShadowCaster::Spot(light) => vec![spot_view_projection(light)],
+        }
+    }
+}
+
+/// The corners of the camera frustum in view space as lines parameterized by view depth, which holds for both
+/// perspective and orthographic projections.
+#[derive(Clone, Debug)]
+pub(crate) struct ViewFrustum {
+    near: f32,
+    corners: [(Vector3<f32>, Vector3<f32>); 4],
+}
+
+impl ViewFrustum {
+    /// Returns `None` if the projection can't be inverted.
+    pub fn new(projection: &Matrix4<f32>) -> Option<ViewFrustum> {
+        let inverse = projection.try_inverse()?;
+        let unproject = |x: f32, y: f32, z: f32| {
+            let point = inverse * Vector4::new(x, y, z, 1.);
+            point.xyz() / point.w
+        };
+
+        let mut corners = [(Vector3::zeros(), Vector3::zeros()); 4];
+        for (corner, &(x, y)) in corners.iter_mut().zip(&[(-1., -1.), (1., -1.), (1., 1.), (-1., 1.)]) {
+            // Reverse Z puts the near plane at depth 1
+            let near = unproject(x, y, 1.);
+            let mid = unproject(x, y, 0.5);
+            if !near.iter().chain(mid.iter()).all(|v| v.is_finite()) || (mid.z - near.z).abs() <= f32::EPSILON {
+                return None;
+            }
+            *corner = (near, mid);
+        }
+
+        Some(ViewFrustum { near: -corners[0].0.z, corners })
+    }
+
+    pub fn near(&self) -> f32 {
+        self.near
+    }
+
+    /// The view space corners of the frustum's cross section at `depth`.
+    fn slice(&self, depth: f32) -> [Vector3<f32>; 4] {
+        let mut slice = [Vector3::zeros(); 4];
+        for (point, (near, mid)) in slice.iter_mut().zip(&self.corners) {
+            *point = near + (mid - near) * ((depth + near.z) / (near.z - mid.z));
+        }
+        slice
+    }
+}
+
+/// Splits the view depth range between the near plane and `far` into cascades, returning the depth each one ends at.
+pub(crate) fn cascade_splits(near: f32, far: f32) -> [f32; CASCADE_COUNT] {
+    let near = near.max(1e-3);
+    let far = far.max(near);
+    let mut splits = [far; CASCADE_COUNT];
+    for (i, split) in splits.iter_mut().enumerate().take(CASCADE_COUNT - 1) {
+        let t = (i + 1) as f32 / CASCADE_COUNT as f32;
+        let logarithmic = near * (far / near).powf(t);
+        let uniform = near + (far - near) * t;
+        *split = logarithmic * CASCADE_SPLIT_LAMBDA + uniform * (1. - CASCADE_SPLIT_LAMBDA);
+    }
+    splits
+}
+
+fn cascade_view_projections(
+    light: &DirectionalLight,
+    view: &Isometry3<f32>,
+    frustum: &ViewFrustum,
+    shadow_distance: f32,
+    map_size: u32,
+) -> Vec<Matrix4<f32>> {
+    let direction = light.direction.try_normalize(0.).unwrap_or_else(|| -Vector3::y());
+    let up = up_vector(&direction);
+    let rotation = UnitQuaternion::look_at_rh(&direction, &up);
+    let camera = view.inverse();
+
+    let splits = cascade_splits(frustum.near(), shadow_distance);
+    let mut start = frustum.near();
+    let mut view_projections = Vec::with_capacity(CASCADE_COUNT);
+    for &end in &splits {
+        let corners = frustum
+            .slice(start)
+            .iter()
+            .chain(frustum.slice(end).iter())
+            .map(|corner| camera * Point3::from(*corner))
+            .collect::<Vec<_>>();
+        start = end;
+
+        // A bounding sphere keeps the cascade's size constant as the camera rotates
+        let center = corners.iter().fold(Vector3::zeros(), |sum, corner| sum + corner.coords) / corners.len() as f32;
+        let radius = corners.iter().map(|corner| (corner.coords - center).norm()).fold(0., f32::max);
+        let radius = ((radius * 16.).ceil() / 16.).max(1. / 16.);
+
+        // Moving the cascade in whole texels keeps shadow edges from shimmering as the camera moves
+        let texel_size = 2. * radius / map_size as f32;
+        let mut snapped = rotation * center;
+        snapped.x = (snapped.x / texel_size).floor() * texel_size;
+        snapped.y = (snapped.y / texel_size).floor() * texel_size;
+        let center = Point3::from(rotation.inverse() * snapped);
+
+        // Pulled back so casters outside the cascade but between it and the light are still drawn
+        let eye = center - direction * (radius + shadow_distance);
+        let light_view = Isometry3::look_at_rh(&eye, &center, &up);
+        view_projections.push(orthographic(radius, 0., 2. * radius + shadow_distance) * light_view.to_homogeneous());
+    }
+
+    view_projections
+}
+
+fn spot_view_projection(light: &SpotLight) -> Matrix4<f32> {
+    let direction = light.direction.try_normalize(0.).unwrap_or_else(|| -Vector3::y());
+    let target = light.position + direction;
+    let light_view = Isometry3::look_at_rh(&light.position, &target, &up_vector(&direction));
+    let fov = (light.outer_angle * 2.).min(std::f32::consts::PI - 0.01);
+    let far = light.range.max(0.01);
+    perspective(fov, far * 0.01, far) * light_view.to_homogeneous()
+}
+
+fn up_vector(direction: &Vector3<f32>) -> Vector3<f32> {
+    if direction.y.abs() > 0.99 {
+        Vector3::x()
+    } else {
+        Vector3::y()
+    }
+}
+
+/// An orthographic projection with reverse Z.
+#[rustfmt::skip]
+fn orthographic(radius: f32, near: f32, far: f32) -> Matrix4<f32> {
+    Matrix4::new(
+        1. / radius, 0., 0., 0.,
+        0., 1. / radius, 0., 0.,
+        0., 0., 1. / (far - near), far / (far - near),
+        0., 0., 0., 1.,
+    )
+}
+
+/// A perspective projection with reverse Z and a square aspect ratio.
+#[rustfmt::skip]
+fn perspective(fov: f32, near: f32, far: f32) -> Matrix4<f32> {
+    let f = 1. / (fov / 2.).tan();
+    Matrix4::new(
+        f, 0., 0., 0.,
+        0., f, 0., 0.,
+        0., 0., near / (far - near), near * far / (far - near),
+        0., 0., -1., 0.,
+    )
+}
+
+/// Depth maps rendered from each shadow casting light's view, sampled by the main shader.
+#[derive(Debug)]
+pub(crate) struct ShadowMaps {
+    size: u32,
+    texture: Texture,
+    layer_views: Vec<TextureView>,
+    view: TextureView,
+    sampler: Sampler,
+    view_buffer: Buffer,
+    view_bind_group: BindGroup,
+    uniform_buffer: Buffer,
+    pipeline: RenderPipeline,
+}
+
+impl ShadowMaps {
+    /// Creates shadow maps of `size` squared texels, clamped to the device's texture limit. No layers are allocated
+    /// until `reserve` is called, the shader samples a single texel placeholder until then.
+    pub fn new(device: &Device, size: u32) -> ShadowMaps {
+        let max_size = device.limits().max_texture_dimension_2d;
+        if size > max_size {
+            warn!("Shadow map size {} exceeds the device limit, using {}", size, max_size);
+        }
+        let size = size.clamp(1, max_size);
+        let (texture, view) = create_texture(device, 1, 1);
+
+        // Linear filtering with a comparison compares 2x2 texels at once, smoothing each PCF tap
+        let sampler = device.create_sampler(&SamplerDescriptor {
+            label: Some("shadow_sampler"),
+            address_mode_u: AddressMode::ClampToEdge,
+            address_mode_v: AddressMode::ClampToEdge,
+            address_mode_w: AddressMode::ClampToEdge,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            compare: Some(CompareFunction::GreaterEqual),
+            ..Default::default()
+        });
+
+        let view_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("shadow_view_buffer"),
+            size: MAX_SHADOW_MAPS as u64 * BIND_BUFFER_ALIGNMENT,
+            usage: BufferUsage::UNIFORM | BufferUsage::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let uniform_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("shadow_uniform_buffer"),
+            size: std::mem::size_of::<ShadowUniforms>() as u64,
+            usage: BufferUsage::UNIFORM | BufferUsage::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let view_bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("shadow_view_bind_group_layout"),
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStage::VERTEX,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: true,
+                    min_binding_size: wgpu::BufferSize::new(std::mem::size_of::<ShadowView>() as _),
+                },
+                count: None,
+            }],
+        });
+
+        let view_bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("shadow_view_bind_group"),
+            layout: &view_bind_group_layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: BindingResource::Buffer(BufferBinding {
+                    buffer: &view_buffer,
+                    offset: 0,
+                    size: wgpu::BufferSize::new(std::mem::size_of::<ShadowView>() as _),
+                }),
+            }],
+        });
+
+        let shader_module = device.create_shader_module(&ShaderModuleDescriptor {
+            label: Some("shadow_shader"),
+            source: ShaderSource::Wgsl(Cow::Borrowed(include_str!("shadow.wgsl"))),
+            flags: ShaderFlags::VALIDATION,
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("shadow_pipeline_layout"),
+            bind_group_layouts: &[&view_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("shadow_pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: VertexState {
+                module: &shader_module,
+                entry_point: "main",
+                buffers: &[
+                    VertexBufferLayout {
+                        array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
+                        step_mode: InputStepMode::Vertex,
+                        attributes: &vertex_attr_array![0 => Float32x3],
+                    },
+                    VertexBufferLayout {
+                        array_stride: std::mem::size_of::<InstanceTransform>() as wgpu::BufferAddress,
+                        step_mode: InputStepMode::Instance,
+                        attributes: &vertex_attr_array![
+                            4 => Float32x4,
+                            5 => Float32x4,
+                            6 => Float32x4,
+                            7 => Float32x4,
+                        ],
+                    },
+                ],
+            },
+            primitive: PrimitiveState {
+                topology: PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: FrontFace::Ccw,
+                cull_mode: Some(Face::Back),
+                clamp_depth: false,
+                polygon_mode: PolygonMode::Fill,
+                conservative: false,
+            },
+            // Reverse Z, so the bias pushes depths away from the light by lowering them
+            depth_stencil: Some(DepthStencilState {
+                format: SHADOW_MAP_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: CompareFunction::GreaterEqual,
+                stencil: StencilState::default(),
+                bias: DepthBiasState { constant: -2, slope_scale: -2., clamp: 0. },
+            }),
+            multisample: MultisampleState::default(),
+            fragment: None,
+        });
+
+        ShadowMaps {
+            size,
+            texture,
+            layer_views: vec![],
+            view,
+            sampler,
+            view_buffer,
+            view_bind_group,
+            uniform_buffer,
+            pipeline,
+        }
+    }
+
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    /// Makes room for `layers` shadow maps, returning whether the texture was reallocated and the view changed.
+    /// Layers are allocated in powers of two up to `MAX_SHADOW_MAPS`.
+    pub fn reserve(&mut self, device: &Device, layers: usize) -> bool {
+        if layers <= self.layer_views.len() {
+            return false;
+        }
+
+        let layers = layers.next_power_of_two().min(MAX_SHADOW_MAPS) as u32;
+        info!("Allocating {} shadow map layers of {}x{}", layers, self.size, self.size);
+        let (texture, view) = create_texture(device, self.size, layers);
+        self.layer_views = (0..layers)
+            .map(|layer| {
+                texture.create_view(&TextureViewDescriptor {
+                    label: Some("shadow_map_layer"),
+                    dimension: Some(TextureViewDimension::D2),
+                    base_array_layer: layer,
+                    array_layer_count: NonZeroU32::new(1),
+                    ..Default::default()
+                })
+            })
+            .collect();
+        self.texture = texture;
+        self.view = view;
+        true
+    }
+
+    pub fn layer_view(&self, layer: usize) -> &TextureView {
+        &self.layer_views[layer]
+    }
+
+    pub fn view(&self) -> &TextureView {
+        &self.view
+    }
+
+    pub fn sampler(&self) -> &Sampler {
+        &self.sampler
+    }
+
+    pub fn uniform_buffer(&self) -> &Buffer {
+        &self.uniform_buffer
+    }
+
+    pub fn pipeline(&self) -> &RenderPipeline {
+        &self.pipeline
+    }
+
+    pub fn view_bind_group(&self) -> &BindGroup {
+        &self.view_bind_group
+    }
+
+    /// The dynamic offset selecting `layer`'s view projection in the view bind group.
+    pub fn view_offset(&self, layer: usize) -> wgpu::DynamicOffset {
+        (layer as u64 * BIND_BUFFER_ALIGNMENT) as wgpu::DynamicOffset
+    }
+
+    /// Writes the uniforms and the view projections of the first `layer_count` layers.
+    pub fn write(&self, queue: &Queue, uniforms: &ShadowUniforms, layer_count: usize) {
+        // Each layer reads its view projection at its own dynamic offset, one binding alignment apart
+        let stride = BIND_BUFFER_ALIGNMENT as usize;
+        let mut views = vec![0u8; layer_count.min(MAX_SHADOW_MAPS) * stride];
+        for (view, &view_projection) in views.chunks_exact_mut(stride).zip(&uniforms.view_projections) {
+            view[..std::mem::size_of::<ShadowView>()]
+                .copy_from_slice(bytemuck::bytes_of(&ShadowView { view_projection }));
+        }
+        if !views.is_empty() {
+            queue.write_buffer(&self.view_buffer, 0, &views);
+        }
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::bytes_of(uniforms));
+    }
+}
+
+fn create_texture(device: &Device, size: u32, layers: u32) -> (Texture, TextureView) {
+    let texture = device.create_texture(&TextureDescriptor {
+        label: Some("shadow_maps"),
+        size: Extent3d { width: size, height: size, depth_or_array_layers: layers },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: TextureDimension::D2,
+        format: SHADOW_MAP_FORMAT,
+        usage: TextureUsage::RENDER_ATTACHMENT | TextureUsage::SAMPLED,
+    });
+
+    let view = texture.create_view(&TextureViewDescriptor {
+        label: Some("shadow_maps"),
+        dimension: Some(TextureViewDimension::D2Array),
+        ..Default::default()
+    });
+
+    (texture, view)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_increase_and_end_at_the_far_plane() {
+        let splits = cascade_splits(0.1, 100.);
+        assert!(splits.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(splits[CASCADE_COUNT - 1], 100.);
+    }
+
+    #[test]
+    fn splits_blend_logarithmic_and_uniform() {
+        let first = cascade_splits(0.1, 100.)[0];
+        let logarithmic = 0.1 * 1000f32.powf(0.25);
+        let uniform = 0.1 + 99.9 * 0.25;
+        assert!((first - (logarithmic + uniform) / 2.).abs() < 1e-3);
+    }
+
+    #[test]
+    fn degenerate_ranges_stay_finite() {
+        for &(near, far) in &[(0., 50.), (-1., 50.), (10., 5.)] {
+            let splits = cascade_splits(near, far);
+            assert!(splits.iter().all(|split| split.is_finite() && *split > 0.));
+            assert!(splits.windows(2).all(|pair| pair[0] <= pair[1]));
+        }
+    }
+}
@@ -7,6 +7,7 @@ use std::io::{self,};
 use std::path::Path;
 use std::sync::Arc;
 
+use crate::result::WithPath;
 use crate::Loadable;
 
 pub struct Sound(Arc<Vec<u8>>);
@@ -16,8 +17,8 @@ impl Sound {
         io::Cursor::new(Sound(self.0.clone()))
     }
 
-    pub(crate) fn decoder(self: &Self) -> rodio::Decoder<io::Cursor<Sound>> {
-        rodio::Decoder::new(self.cursor()).unwrap()
+    pub(crate) fn decoder(self: &Self) -> crate::Result<rodio::Decoder<io::Cursor<Sound>>> {
+        Ok(rodio::Decoder::new(self.cursor())?)
     }
 }
 
@@ -27,8 +28,8 @@ impl Loadable for Sound {
         Self: Sized,
     {
         let mut buf = vec![];
-        let mut file = File::open(path)?;
-        file.read_to_end(&mut buf)?;
+        let mut file = File::open(&path).with_path(&path)?;
+        file.read_to_end(&mut buf).with_path(&path)?;
         Ok(Sound(Arc::new(buf)))
     }
 }
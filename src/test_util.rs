@@ -0,0 +1,34 @@
+// Copyright 2021 Chay Nabors.
+
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+
+/// A path in the system temp directory, unique to this process. The file is removed when this is dropped, so
+/// failing tests don't leave it behind.
+pub(crate) struct TempFile {
+    path: PathBuf,
+}
+
+impl TempFile {
+    pub fn new(name: &str) -> Self {
+        Self { path: std::env::temp_dir().join(format!("gear_{}_{}", std::process::id(), name)) }
+    }
+
+    pub fn with_contents<C: AsRef<[u8]>>(name: &str, contents: C) -> Self {
+        let file = Self::new(name);
+        fs::write(&file.path, contents).unwrap();
+        file
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        // The file may never have been created
+        let _ = fs::remove_file(&self.path);
+    }
+}
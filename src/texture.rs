@@ -1,15 +1,661 @@
 // Copyright 2021 Chay Nabors.
 
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::num::NonZeroU32;
+use std::num::NonZeroU8;
 use std::path::Path;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::Weak;
 
-#[derive(Debug)]
-pub struct Texture;
+use ddsfile::D3DFormat;
+use ddsfile::Dds;
+use ddsfile::DxgiFormat;
+use log::warn;
+use wgpu::AddressMode;
+use wgpu::BindGroup;
+use wgpu::BindGroupDescriptor;
+use wgpu::BindGroupEntry;
+use wgpu::BindGroupLayout;
+use wgpu::BindGroupLayoutDescriptor;
+use wgpu::BindGroupLayoutEntry;
+use wgpu::BindingResource;
+use wgpu::BindingType;
+use wgpu::Color;
+use wgpu::ColorTargetState;
+use wgpu::ColorWrite;
+use wgpu::CommandEncoderDescriptor;
+use wgpu::Device;
+use wgpu::Extent3d;
+use wgpu::FilterMode;
+use wgpu::FragmentState;
+use wgpu::ImageCopyTexture;
+use wgpu::ImageDataLayout;
+use wgpu::LoadOp;
+use wgpu::MultisampleState;
+use wgpu::Operations;
+use wgpu::Origin3d;
+use wgpu::PipelineLayoutDescriptor;
+use wgpu::PrimitiveState;
+use wgpu::Queue;
+use wgpu::RenderPassColorAttachment;
+use wgpu::RenderPassDescriptor;
+use wgpu::RenderPipeline;
+use wgpu::RenderPipelineDescriptor;
+use wgpu::Sampler;
+use wgpu::SamplerDescriptor;
+use wgpu::ShaderFlags;
+use wgpu::ShaderModuleDescriptor;
+use wgpu::ShaderSource;
+use wgpu::ShaderStage;
+use wgpu::TextureDescriptor;
+use wgpu::TextureDimension;
+use wgpu::TextureFormat;
+use wgpu::TextureSampleType;
+use wgpu::TextureUsage;
+use wgpu::TextureView;
+use wgpu::TextureViewDescriptor;
+use wgpu::TextureViewDimension;
+use wgpu::VertexState;
+
+use crate::bcn;
+use crate::result::GearError;
+use crate::result::Result;
+use crate::result::WithPath;
+use crate::Loadable;
+
+const MIP_FORMATS: [TextureFormat; 2] = [TextureFormat::Rgba8UnormSrgb, TextureFormat::Rgba8Unorm];
+
+static NEXT_TEXTURE_ID: AtomicU64 = AtomicU64::new(0);
+
+/// How a texture is filtered and addressed when sampled.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SamplerConfig {
+    pub mag_filter: FilterMode,
+    pub min_filter: FilterMode,
+    pub mipmap_filter: FilterMode,
+    pub address_mode_u: AddressMode,
+    pub address_mode_v: AddressMode,
+    pub address_mode_w: AddressMode,
+    /// Maximum anisotropy; 1 disables anisotropic filtering. Rounded down to 2, 4, 8 or 16, and ignored unless every
+    /// filter is linear.
+    pub anisotropy: u8,
+}
+
+impl Default for SamplerConfig {
+    fn default() -> Self {
+        Self {
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            mipmap_filter: FilterMode::Linear,
+            address_mode_u: AddressMode::Repeat,
+            address_mode_v: AddressMode::Repeat,
+            address_mode_w: AddressMode::Repeat,
+            anisotropy: 1,
+        }
+    }
+}
+
+impl SamplerConfig {
+    pub fn nearest() -> Self {
+        Self {
+            mag_filter: FilterMode::Nearest,
+            min_filter: FilterMode::Nearest,
+            mipmap_filter: FilterMode::Nearest,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_address_mode(mut self, address_mode: AddressMode) -> Self {
+        self.address_mode_u = address_mode;
+        self.address_mode_v = address_mode;
+        self.address_mode_w = address_mode;
+        self
+    }
+
+    pub fn with_anisotropy(mut self, anisotropy: u8) -> Self {
+        self.anisotropy = anisotropy;
+        self
+    }
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct TextureData {
+    pub id: u64,
+    pub width: u32,
+    pub height: u32,
+    pub format: TextureFormat,
+    /// Pixel data for each mip level, starting with the full size image.
+    pub levels: Vec<Vec<u8>>,
+    pub generate_mips: bool,
+}
+
+/// An RGBA8 or block compressed image. Cloning is cheap and shares the pixel data;
+/// the GPU copy is created on first bind and freed once every clone is dropped.
+#[derive(Clone, Debug)]
+pub struct Texture {
+    pub(crate) data: Arc<TextureData>,
+    sampler: SamplerConfig,
+}
 
 impl Texture {
-    fn _load<P: AsRef<Path>>(_path: P) -> Result<Self, ()>
-    where
-        Self: Sized,
-    {
-        Ok(Texture)
+    /// Creates a texture whose full mip chain is generated on the GPU at upload.
+    pub fn from_rgba8(width: u32, height: u32, pixels: Vec<u8>) -> Result<Texture> {
+        if width == 0 || height == 0 || pixels.len() != width as usize * height as usize * 4 {
+            return Err(GearError::InvalidTextureData);
+        }
+
+        Ok(Texture::with_data(width, height, TextureFormat::Rgba8UnormSrgb, vec![pixels], true))
+    }
+
+    /// Creates a texture from precomputed mip levels, starting with the full size image.
+    /// Each level must be half the size of the previous one, rounded down to at least 1.
+    pub fn from_rgba8_mips(width: u32, height: u32, levels: Vec<Vec<u8>>) -> Result<Texture> {
+        Texture::from_levels(width, height, TextureFormat::Rgba8UnormSrgb, levels)
+    }
+
+    fn from_levels(width: u32, height: u32, format: TextureFormat, levels: Vec<Vec<u8>>) -> Result<Texture> {
+        if width == 0 || height == 0 || levels.is_empty() || levels.len() as u32 > mip_level_count(width, height) {
+            return Err(GearError::InvalidTextureData);
+        }
+
+        for (i, level) in levels.iter().enumerate() {
+            let [level_width, level_height] = mip_size(width, height, i as u32);
+            if level.len() != level_size(format, level_width, level_height) {
+                return Err(GearError::InvalidTextureData);
+            }
+        }
+
+        Ok(Texture::with_data(width, height, format, levels, false))
+    }
+
+    fn with_data(width: u32, height: u32, format: TextureFormat, levels: Vec<Vec<u8>>, generate_mips: bool) -> Texture {
+        let id = NEXT_TEXTURE_ID.fetch_add(1, Ordering::Relaxed);
+        Texture {
+            data: Arc::new(TextureData { id, width, height, format, levels, generate_mips }),
+            sampler: SamplerConfig::default(),
+        }
+    }
+
+    fn from_ktx2(bytes: &[u8]) -> Result<Texture> {
+        let reader = ktx2::Reader::new(bytes)?;
+        let header = reader.header();
+        if header.supercompression_scheme.is_some()
+            || header.pixel_depth > 1
+            || header.layer_count > 1
+            || header.face_count > 1
+        {
+            return Err(GearError::UnsupportedFormat);
+        }
+
+        let format = match header.format {
+            Some(ktx2::Format::R8G8B8A8_UNORM) => TextureFormat::Rgba8Unorm,
+            Some(ktx2::Format::R8G8B8A8_SRGB) => TextureFormat::Rgba8UnormSrgb,
+            // BC1 without alpha shares the block layout of BC1 with alpha
+            Some(ktx2::Format::BC1_RGB_UNORM_BLOCK) | Some(ktx2::Format::BC1_RGBA_UNORM_BLOCK) => {
+                TextureFormat::Bc1RgbaUnorm
+            },
+            Some(ktx2::Format::BC1_RGB_SRGB_BLOCK) | Some(ktx2::Format::BC1_RGBA_SRGB_BLOCK) => {
+                TextureFormat::Bc1RgbaUnormSrgb
+            },
+            Some(ktx2::Format::BC2_UNORM_BLOCK) => TextureFormat::Bc2RgbaUnorm,
+            Some(ktx2::Format::BC2_SRGB_BLOCK) => TextureFormat::Bc2RgbaUnormSrgb,
+            Some(ktx2::Format::BC3_UNORM_BLOCK) => TextureFormat::Bc3RgbaUnorm,
+            Some(ktx2::Format::BC3_SRGB_BLOCK) => TextureFormat::Bc3RgbaUnormSrgb,
+            Some(ktx2::Format::BC4_UNORM_BLOCK) => TextureFormat::Bc4RUnorm,
+            Some(ktx2::Format::BC4_SNORM_BLOCK) => TextureFormat::Bc4RSnorm,
+            Some(ktx2::Format::BC5_UNORM_BLOCK) => TextureFormat::Bc5RgUnorm,
+            Some(ktx2::Format::BC5_SNORM_BLOCK) => TextureFormat::Bc5RgSnorm,
+            Some(ktx2::Format::BC6H_UFLOAT_BLOCK) => TextureFormat::Bc6hRgbUfloat,
+            Some(ktx2::Format::BC6H_SFLOAT_BLOCK) => TextureFormat::Bc6hRgbSfloat,
+            Some(ktx2::Format::BC7_UNORM_BLOCK) => TextureFormat::Bc7RgbaUnorm,
+            Some(ktx2::Format::BC7_SRGB_BLOCK) => TextureFormat::Bc7RgbaUnormSrgb,
+            _ => return Err(GearError::UnsupportedFormat),
+        };
+
+        let levels = reader.levels().map(<[u8]>::to_vec).collect();
+        Texture::from_levels(header.pixel_width, header.pixel_height.max(1), format, levels)
+    }
+
+    fn from_dds(bytes: &[u8]) -> Result<Texture> {
+        let dds = Dds::read(bytes)?;
+        if dds.get_depth() > 1 || dds.get_num_array_layers() > 1 {
+            return Err(GearError::UnsupportedFormat);
+        }
+
+        // The second value marks BGRA data that needs its red and blue channels swapped
+        let (format, bgra) = match dds.get_dxgi_format() {
+            Some(DxgiFormat::R8G8B8A8_UNorm) => (TextureFormat::Rgba8Unorm, false),
+            Some(DxgiFormat::R8G8B8A8_UNorm_sRGB) => (TextureFormat::Rgba8UnormSrgb, false),
+            Some(DxgiFormat::B8G8R8A8_UNorm) => (TextureFormat::Rgba8Unorm, true),
+            Some(DxgiFormat::B8G8R8A8_UNorm_sRGB) => (TextureFormat::Rgba8UnormSrgb, true),
+            Some(DxgiFormat::BC1_UNorm) => (TextureFormat::Bc1RgbaUnorm, false),
+            Some(DxgiFormat::BC1_UNorm_sRGB) => (TextureFormat::Bc1RgbaUnormSrgb, false),
+            Some(DxgiFormat::BC2_UNorm) => (TextureFormat::Bc2RgbaUnorm, false),
+            Some(DxgiFormat::BC2_UNorm_sRGB) => (TextureFormat::Bc2RgbaUnormSrgb, false),
+            Some(DxgiFormat::BC3_UNorm) => (TextureFormat::Bc3RgbaUnorm, false),
+            Some(DxgiFormat::BC3_UNorm_sRGB) => (TextureFormat::Bc3RgbaUnormSrgb, false),
+            Some(DxgiFormat::BC4_UNorm) => (TextureFormat::Bc4RUnorm, false),
+            Some(DxgiFormat::BC4_SNorm) => (TextureFormat::Bc4RSnorm, false),
+            Some(DxgiFormat::BC5_UNorm) => (TextureFormat::Bc5RgUnorm, false),
+            Some(DxgiFormat::BC5_SNorm) => (TextureFormat::Bc5RgSnorm, false),
+            Some(DxgiFormat::BC6H_UF16) => (TextureFormat::Bc6hRgbUfloat, false),
+            Some(DxgiFormat::BC6H_SF16) => (TextureFormat::Bc6hRgbSfloat, false),
+            Some(DxgiFormat::BC7_UNorm) => (TextureFormat::Bc7RgbaUnorm, false),
+            Some(DxgiFormat::BC7_UNorm_sRGB) => (TextureFormat::Bc7RgbaUnormSrgb, false),
+            Some(_) => return Err(GearError::UnsupportedFormat),
+            None => match dds.get_d3d_format() {
+                Some(D3DFormat::A8B8G8R8) => (TextureFormat::Rgba8UnormSrgb, false),
+                Some(D3DFormat::A8R8G8B8) => (TextureFormat::Rgba8UnormSrgb, true),
+                _ => return Err(GearError::UnsupportedFormat),
+            },
+        };
+
+        // Mip levels are stored back to back, largest first
+        let (width, height) = (dds.get_width(), dds.get_height());
+        let level_count = dds.get_num_mipmap_levels().max(1).min(mip_level_count(width, height));
+        let mut levels = vec![];
+        let mut offset = 0;
+        for level in 0..level_count {
+            let [level_width, level_height] = mip_size(width, height, level);
+            let size = level_size(format, level_width, level_height);
+            let mut pixels = dds.data.get(offset..offset + size).ok_or(GearError::InvalidTextureData)?.to_vec();
+            if bgra {
+                for pixel in pixels.chunks_exact_mut(4) {
+                    pixel.swap(0, 2);
+                }
+            }
+            levels.push(pixels);
+            offset += size;
+        }
+
+        Texture::from_levels(width, height, format, levels)
+    }
+
+    pub fn size(&self) -> [u32; 2] {
+        [self.data.width, self.data.height]
+    }
+
+    /// The number of mip levels the uploaded texture will have.
+    pub fn mip_level_count(&self) -> u32 {
+        match self.data.generate_mips {
+            true => mip_level_count(self.data.width, self.data.height),
+            false => self.data.levels.len() as u32,
+        }
+    }
+
+    /// Reinterprets sRGB encoded texels as linear, as normal maps and other non-color data need.
+    /// The pixel data is only copied if other clones still share it.
+    pub fn into_linear(self) -> Texture {
+        let format = linear_format(self.data.format);
+        if format == self.data.format {
+            return self;
+        }
+
+        let sampler = self.sampler;
+        let data = Arc::try_unwrap(self.data).unwrap_or_else(|data| (*data).clone());
+        Texture::with_data(data.width, data.height, format, data.levels, data.generate_mips).with_sampler(sampler)
+    }
+
+    pub fn is_compressed(&self) -> bool {
+        bcn::block_size(self.data.format).is_some()
+    }
+
+    pub fn sampler(&self) -> SamplerConfig {
+        self.sampler
+    }
+
+    /// Sets the sampler used when this handle is bound. Other clones keep their own sampler.
+    pub fn set_sampler(&mut self, sampler: SamplerConfig) -> &mut Self {
+        self.sampler = sampler;
+        self
+    }
+
+    pub fn with_sampler(mut self, sampler: SamplerConfig) -> Self {
+        self.sampler = sampler;
+        self
+    }
+}
+
+impl Loadable for Texture {
+    fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let extension = path.as_ref().extension().and_then(OsStr::to_str).map(str::to_ascii_lowercase);
+        match extension.as_deref() {
+            Some("ktx2") => Texture::from_ktx2(&std::fs::read(&path).with_path(&path)?).with_path(&path),
+            Some("dds") => Texture::from_dds(&std::fs::read(&path).with_path(&path)?).with_path(&path),
+            _ => {
+                let image = image::open(&path).with_path(&path)?.into_rgba8();
+                let (width, height) = image.dimensions();
+                Texture::from_rgba8(width, height, image.into_raw()).with_path(&path)
+            },
+        }
+    }
+}
+
+fn linear_format(format: TextureFormat) -> TextureFormat {
+    match format {
+        TextureFormat::Rgba8UnormSrgb => TextureFormat::Rgba8Unorm,
+        TextureFormat::Bc1RgbaUnormSrgb => TextureFormat::Bc1RgbaUnorm,
+        TextureFormat::Bc2RgbaUnormSrgb => TextureFormat::Bc2RgbaUnorm,
+        TextureFormat::Bc3RgbaUnormSrgb => TextureFormat::Bc3RgbaUnorm,
+        TextureFormat::Bc7RgbaUnormSrgb => TextureFormat::Bc7RgbaUnorm,
+        format => format,
+    }
+}
+
+fn mip_level_count(width: u32, height: u32) -> u32 {
+    32 - width.max(height).leading_zeros()
+}
+
+fn mip_size(width: u32, height: u32, level: u32) -> [u32; 2] {
+    [(width >> level).max(1), (height >> level).max(1)]
+}
+
+fn level_size(format: TextureFormat, width: u32, height: u32) -> usize {
+    let (bytes_per_row, rows) = level_layout(format, width, height);
+    bytes_per_row as usize * rows as usize
+}
+
+/// Bytes per row and number of rows, where a row of a block compressed format is a row of 4x4 blocks.
+fn level_layout(format: TextureFormat, width: u32, height: u32) -> (u32, u32) {
+    match bcn::block_size(format) {
+        Some(block_size) => ((width + 3) / 4 * block_size, (height + 3) / 4),
+        None if format == TextureFormat::Rgba16Float => (width * 8, height),
+        None => (width * 4, height),
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct GpuTexture {
+    _texture: wgpu::Texture,
+    pub view: TextureView,
+    bind_groups: HashMap<SamplerConfig, BindGroup>,
+    data: Weak<TextureData>,
+}
+
+impl GpuTexture {
+    pub fn upload(
+        device: &Device,
+        queue: &Queue,
+        mip_generator: &MipGenerator,
+        texture_compression_bc: bool,
+        data: &Arc<TextureData>,
+    ) -> GpuTexture {
+        let mip_level_count = match data.generate_mips {
+            true => mip_level_count(data.width, data.height),
+            false => data.levels.len() as u32,
+        };
+
+        // Block compressed data is decoded on the CPU when the adapter can't sample it
+        let block_aligned = data.width % 4 == 0 && data.height % 4 == 0;
+        let decoded = match bcn::block_size(data.format) {
+            Some(_) if !texture_compression_bc || !block_aligned => Some(
+                data.levels
+                    .iter()
+                    .enumerate()
+                    .map(|(level, pixels)| {
+                        let [width, height] = mip_size(data.width, data.height, level as u32);
+                        bcn::decode(data.format, width, height, pixels)
+                    })
+                    .collect::<Vec<_>>(),
+            ),
+            _ => None,
+        };
+        let (format, levels) = match &decoded {
+            Some(levels) => (bcn::decoded_format(data.format), levels),
+            None => (data.format, &data.levels),
+        };
+
+        let mut usage = TextureUsage::SAMPLED | TextureUsage::COPY_DST;
+        if data.generate_mips {
+            usage |= TextureUsage::RENDER_ATTACHMENT;
+        }
+
+        let texture = device.create_texture(&TextureDescriptor {
+            label: Some("texture"),
+            size: Extent3d { width: data.width, height: data.height, depth_or_array_layers: 1 },
+            mip_level_count,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format,
+            usage,
+        });
+
+        for (level, pixels) in levels.iter().enumerate() {
+            let [width, height] = mip_size(data.width, data.height, level as u32);
+            let (bytes_per_row, rows) = level_layout(format, width, height);
+
+            // Copies of block compressed levels cover whole blocks, even past the edge of small mips
+            let size = match bcn::block_size(format) {
+                Some(_) => Extent3d { width: (width + 3) / 4 * 4, height: rows * 4, depth_or_array_layers: 1 },
+                None => Extent3d { width, height, depth_or_array_layers: 1 },
+            };
+
+            queue.write_texture(
+                ImageCopyTexture { texture: &texture, mip_level: level as u32, origin: Origin3d::ZERO },
+                pixels,
+                ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: NonZeroU32::new(bytes_per_row),
+                    rows_per_image: NonZeroU32::new(rows),
+                },
+                size,
+            );
+        }
+
+        if data.generate_mips {
+            mip_generator.generate(device, queue, &texture, format, mip_level_count);
+        }
+
+        let view = texture.create_view(&TextureViewDescriptor::default());
+
+        GpuTexture { _texture: texture, view, bind_groups: HashMap::new(), data: Arc::downgrade(data) }
+    }
+
+    /// Creates the bind group pairing this texture with `sampler` if it doesn't exist yet.
+    pub fn prepare_bind_group(
+        &mut self,
+        device: &Device,
+        layout: &BindGroupLayout,
+        config: SamplerConfig,
+        sampler: &Sampler,
+    ) -> &BindGroup {
+        let view = &self.view;
+        self.bind_groups.entry(config).or_insert_with(|| create_texture_bind_group(device, layout, view, sampler))
+    }
+
+    pub fn bind_group(&self, config: &SamplerConfig) -> Option<&BindGroup> {
+        self.bind_groups.get(config)
+    }
+
+    /// Whether every `Texture` handle referring to this upload has been dropped.
+    pub fn is_orphaned(&self) -> bool {
+        self.data.strong_count() == 0
+    }
+}
+
+pub(crate) fn create_sampler(device: &Device, config: &SamplerConfig) -> Sampler {
+    device.create_sampler(&SamplerDescriptor {
+        label: Some("sampler"),
+        address_mode_u: config.address_mode_u,
+        address_mode_v: config.address_mode_v,
+        address_mode_w: config.address_mode_w,
+        mag_filter: config.mag_filter,
+        min_filter: config.min_filter,
+        mipmap_filter: config.mipmap_filter,
+        anisotropy_clamp: anisotropy_clamp(config),
+        ..Default::default()
+    })
+}
+
+/// The anisotropy closest to `config`'s that wgpu accepts, a power of two up to 16 with every filter linear.
+fn anisotropy_clamp(config: &SamplerConfig) -> Option<NonZeroU8> {
+    if config.anisotropy <= 1 {
+        return None;
+    }
+
+    if [config.mag_filter, config.min_filter, config.mipmap_filter].contains(&FilterMode::Nearest) {
+        warn!("Anisotropic filtering requires linear filters, disabling it");
+        return None;
+    }
+
+    NonZeroU8::new(1 << (7 - config.anisotropy.min(16).leading_zeros()))
+}
+
+pub(crate) fn create_texture_bind_group(
+    device: &Device,
+    layout: &BindGroupLayout,
+    view: &TextureView,
+    sampler: &Sampler,
+) -> BindGroup {
+    device.create_bind_group(&BindGroupDescriptor {
+        label: Some("texture_bind_group"),
+        layout,
+        entries: &[
+            BindGroupEntry { binding: 0, resource: BindingResource::TextureView(view) },
+            BindGroupEntry { binding: 1, resource: BindingResource::Sampler(sampler) },
+        ],
+    })
+}
+
+/// Fills in mip levels by repeatedly rendering each level into the next with linear filtering.
+#[derive(Debug)]
+pub(crate) struct MipGenerator {
+    bind_group_layout: BindGroupLayout,
+    sampler: Sampler,
+    pipelines: HashMap<TextureFormat, RenderPipeline>,
+}
+
+impl MipGenerator {
+    pub fn new(device: &Device) -> MipGenerator {
+        let shader_module = device.create_shader_module(&ShaderModuleDescriptor {
+            label: Some("blit_shader"),
+            source: ShaderSource::Wgsl(Cow::Borrowed(include_str!("blit.wgsl"))),
+            flags: ShaderFlags::VALIDATION,
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("blit_bind_group_layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStage::FRAGMENT,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: true },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStage::FRAGMENT,
+                    ty: BindingType::Sampler { filtering: true, comparison: false },
+                    count: None,
+                },
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("blit_pipeline_layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipelines = MIP_FORMATS
+            .iter()
+            .map(|&format| {
+                let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+                    label: Some("blit_pipeline"),
+                    layout: Some(&pipeline_layout),
+                    vertex: VertexState { module: &shader_module, entry_point: "main", buffers: &[] },
+                    primitive: PrimitiveState::default(),
+                    depth_stencil: None,
+                    multisample: MultisampleState::default(),
+                    fragment: Some(FragmentState {
+                        module: &shader_module,
+                        entry_point: "main",
+                        targets: &[ColorTargetState { format, blend: None, write_mask: ColorWrite::ALL }],
+                    }),
+                });
+                (format, pipeline)
+            })
+            .collect();
+
+        let sampler = device.create_sampler(&SamplerDescriptor {
+            label: Some("blit_sampler"),
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            ..Default::default()
+        });
+
+        MipGenerator { bind_group_layout, sampler, pipelines }
+    }
+
+    pub fn generate(
+        &self,
+        device: &Device,
+        queue: &Queue,
+        texture: &wgpu::Texture,
+        format: TextureFormat,
+        mip_level_count: u32,
+    ) {
+        let pipeline = &self.pipelines[&format];
+        let views = (0..mip_level_count)
+            .map(|level| {
+                texture.create_view(&TextureViewDescriptor {
+                    label: Some("mip_view"),
+                    base_mip_level: level,
+                    mip_level_count: NonZeroU32::new(1),
+                    ..Default::default()
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor { label: Some("mip_encoder") });
+
+        for level in 1..mip_level_count as usize {
+            let bind_group =
+                create_texture_bind_group(device, &self.bind_group_layout, &views[level - 1], &self.sampler);
+
+            let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+                label: Some("mip_pass"),
+                color_attachments: &[RenderPassColorAttachment {
+                    view: &views[level],
+                    resolve_target: None,
+                    ops: Operations { load: LoadOp::Clear(Color::TRANSPARENT), store: true },
+                }],
+                depth_stencil_attachment: None,
+            });
+            render_pass.set_pipeline(pipeline);
+            render_pass.set_bind_group(0, &bind_group, &[]);
+            render_pass.draw(0..3, 0..1);
+        }
+
+        queue.submit(Some(encoder.finish()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn anisotropy_rounds_down_to_a_supported_value() {
+        let clamp = |anisotropy| anisotropy_clamp(&SamplerConfig::default().with_anisotropy(anisotropy)).map(u8::from);
+        assert_eq!(clamp(0), None);
+        assert_eq!(clamp(1), None);
+        assert_eq!(clamp(3), Some(2));
+        assert_eq!(clamp(8), Some(8));
+        assert_eq!(clamp(15), Some(8));
+        assert_eq!(clamp(255), Some(16));
+    }
+
+    #[test]
+    fn anisotropy_requires_linear_filters() {
+        assert_eq!(anisotropy_clamp(&SamplerConfig::nearest().with_anisotropy(16)), None);
     }
 }
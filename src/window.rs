@@ -1,10 +1,17 @@
 // Copyright 2021 Chay Nabors.
 
 use log::info;
+use raw_window_handle::RawWindowHandle;
+use raw_window_handle_04::RawWindowHandle as RawWindowHandle04;
 use winit::dpi::PhysicalPosition;
 use winit::dpi::PhysicalSize;
 #[cfg(target_os = "windows")]
 use winit::platform::windows::WindowBuilderExtWindows;
+use winit::window::CursorGrabMode;
+use winit::window::Fullscreen;
+use winit::window::WindowBuilder;
+
+use crate::result::Result;
 
 #[derive(Clone, Copy, Debug)]
 pub enum WindowEvent {
@@ -12,16 +19,34 @@ pub enum WindowEvent {
     Moved([i32; 2]),
 }
 
+#[derive(Clone, Debug)]
+pub(crate) struct WindowConfig {
+    pub title: String,
+    pub size: Option<[u32; 2]>,
+    pub fullscreen: bool,
+    pub resizable: bool,
+}
+
+impl Default for WindowConfig {
+    fn default() -> Self {
+        Self { title: "gear".to_string(), size: None, fullscreen: false, resizable: true }
+    }
+}
+
 #[derive(Debug)]
 pub struct Window {
     window: Option<winit::window::Window>,
 }
 
 impl Window {
-    pub(crate) fn new(event_loop: &winit::event_loop::EventLoop<()>) -> Self {
+    pub(crate) fn new(event_loop: &winit::event_loop::EventLoop<()>, config: &WindowConfig) -> Result<Self> {
         info!("Initializing windowing backend");
 
-        Self { window: Some(create_window(event_loop)) }
+        Ok(Self { window: Some(create_window(event_loop, config)?) })
+    }
+
+    pub(crate) fn headless() -> Self {
+        Self { window: None }
     }
 
     pub fn rename(&self, name: &str) -> &Self {
@@ -38,16 +63,39 @@ impl Window {
         self
     }
 
-    pub fn set_cursor_grab(&self, grab: bool) -> &Self {
+    pub fn set_cursor_grab(&self, grab: bool) -> Result<&Self> {
         if let Some(window) = &self.window {
-            window.set_cursor_grab(grab).unwrap();
+            if grab {
+                // macOS can't confine the cursor, only lock it in place
+                window
+                    .set_cursor_grab(CursorGrabMode::Confined)
+                    .or_else(|_| window.set_cursor_grab(CursorGrabMode::Locked))?;
+            } else {
+                window.set_cursor_grab(CursorGrabMode::None)?;
+            }
         }
-        self
+        Ok(self)
+    }
+
+    pub fn set_cursor_position(&self, position: [u32; 2]) -> Result<&Self> {
+        if let Some(window) = &self.window {
+            window.set_cursor_position(PhysicalPosition::new(position[0] as f64, position[1] as f64))?;
+        }
+        Ok(self)
     }
 
-    pub fn set_cursor_position(&self, position: [u32; 2]) -> &Self {
+    /// Lets input methods compose text, which then arrives as `ImeEvent`s. The engine allows it while text input is
+    /// enabled.
+    pub(crate) fn set_ime_allowed(&self, allowed: bool) {
         if let Some(window) = &self.window {
-            window.set_cursor_position(PhysicalPosition::new(position[0] as f64, position[1] as f64)).unwrap();
+            window.set_ime_allowed(allowed);
+        }
+    }
+
+    /// Positions the IME candidate window, e.g. under the focused text field.
+    pub fn set_ime_position(&self, position: [u32; 2]) -> &Self {
+        if let Some(window) = &self.window {
+            window.set_ime_position(PhysicalPosition::new(position[0] as f64, position[1] as f64));
         }
         self
     }
@@ -73,19 +121,111 @@ impl Window {
 }
 
 unsafe impl raw_window_handle::HasRawWindowHandle for Window {
-    fn raw_window_handle(&self) -> raw_window_handle::RawWindowHandle {
-        self.window.as_ref().unwrap().raw_window_handle()
+    fn raw_window_handle(&self) -> RawWindowHandle {
+        let handle = raw_window_handle_04::HasRawWindowHandle::raw_window_handle(self.window.as_ref().unwrap());
+        convert_window_handle(handle)
+    }
+}
+
+// wgpu still takes raw-window-handle 0.3, which winit no longer provides
+fn convert_window_handle(handle: RawWindowHandle04) -> RawWindowHandle {
+    match handle {
+        #[cfg(any(
+            target_os = "linux",
+            target_os = "dragonfly",
+            target_os = "freebsd",
+            target_os = "netbsd",
+            target_os = "openbsd"
+        ))]
+        RawWindowHandle04::Xlib(handle) => RawWindowHandle::Xlib(raw_window_handle::unix::XlibHandle {
+            window: handle.window,
+            display: handle.display,
+            ..raw_window_handle::unix::XlibHandle::empty()
+        }),
+        #[cfg(any(
+            target_os = "linux",
+            target_os = "dragonfly",
+            target_os = "freebsd",
+            target_os = "netbsd",
+            target_os = "openbsd"
+        ))]
+        RawWindowHandle04::Xcb(handle) => RawWindowHandle::Xcb(raw_window_handle::unix::XcbHandle {
+            window: handle.window,
+            connection: handle.connection,
+            ..raw_window_handle::unix::XcbHandle::empty()
+        }),
+        #[cfg(any(
+            target_os = "linux",
+            target_os = "dragonfly",
+            target_os = "freebsd",
+            target_os = "netbsd",
+            target_os = "openbsd"
+        ))]
+        RawWindowHandle04::Wayland(handle) => RawWindowHandle::Wayland(raw_window_handle::unix::WaylandHandle {
+            surface: handle.surface,
+            display: handle.display,
+            ..raw_window_handle::unix::WaylandHandle::empty()
+        }),
+        #[cfg(target_os = "windows")]
+        RawWindowHandle04::Win32(handle) => RawWindowHandle::Windows(raw_window_handle::windows::WindowsHandle {
+            hwnd: handle.hwnd,
+            hinstance: handle.hinstance,
+            ..raw_window_handle::windows::WindowsHandle::empty()
+        }),
+        #[cfg(target_os = "macos")]
+        RawWindowHandle04::AppKit(handle) => RawWindowHandle::MacOS(raw_window_handle::macos::MacOSHandle {
+            ns_window: handle.ns_window,
+            ns_view: handle.ns_view,
+            ..raw_window_handle::macos::MacOSHandle::empty()
+        }),
+        #[cfg(target_os = "ios")]
+        RawWindowHandle04::UiKit(handle) => RawWindowHandle::IOS(raw_window_handle::ios::IOSHandle {
+            ui_window: handle.ui_window,
+            ui_view: handle.ui_view,
+            ui_view_controller: handle.ui_view_controller,
+            ..raw_window_handle::ios::IOSHandle::empty()
+        }),
+        #[cfg(target_os = "android")]
+        RawWindowHandle04::AndroidNdk(handle) => RawWindowHandle::Android(raw_window_handle::android::AndroidHandle {
+            a_native_window: handle.a_native_window,
+            ..raw_window_handle::android::AndroidHandle::empty()
+        }),
+        #[cfg(target_arch = "wasm32")]
+        RawWindowHandle04::Web(handle) => RawWindowHandle::Web(raw_window_handle::web::WebHandle {
+            id: handle.id,
+            ..raw_window_handle::web::WebHandle::empty()
+        }),
+        _ => unreachable!("winit created a window handle for another platform"),
+    }
+}
+
+fn window_builder(config: &WindowConfig) -> WindowBuilder {
+    let mut builder = WindowBuilder::new()
+        .with_title(config.title.clone())
+        .with_resizable(config.resizable)
+        .with_fullscreen(if config.fullscreen { Some(Fullscreen::Borderless(None)) } else { None });
+
+    if let Some(size) = config.size {
+        builder = builder.with_inner_size(PhysicalSize::new(size[0], size[1]));
     }
+
+    builder
 }
 
 // This is a workaround since rodio and winit can't run in parallel when drag
 // and drop is enabled on windows
 #[cfg(target_os = "windows")]
-fn create_window(event_loop: &winit::event_loop::EventLoop<()>) -> winit::window::Window {
-    winit::window::WindowBuilder::new().with_drag_and_drop(false).build(event_loop).unwrap()
+fn create_window(
+    event_loop: &winit::event_loop::EventLoop<()>,
+    config: &WindowConfig,
+) -> Result<winit::window::Window> {
+    Ok(window_builder(config).with_drag_and_drop(false).build(event_loop)?)
 }
 
 #[cfg(not(target_os = "windows"))]
-fn create_window(event_loop: &winit::event_loop::EventLoop<()>) -> winit::window::Window {
-    winit::window::WindowBuilder::new().build(event_loop).unwrap()
+fn create_window(
+    event_loop: &winit::event_loop::EventLoop<()>,
+    config: &WindowConfig,
+) -> Result<winit::window::Window> {
+    Ok(window_builder(config).build(event_loop)?)
 }